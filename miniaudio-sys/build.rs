@@ -25,6 +25,10 @@ pub fn main() {
         cc_builder.file("./miniaudio/extras/stb_vorbis.c");
     }
 
+    if cfg!(feature = "ma-enable-speex") {
+        cc_builder.define("MA_ENABLE_SPEEX_RESAMPLER", "");
+    }
+
     cc_builder.compile("libminiaudio");
 
     emit_supported_features();
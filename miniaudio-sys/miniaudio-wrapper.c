@@ -14,4 +14,9 @@
 #include "./miniaudio/extras/dr_wav.h"
 #endif
 
+#ifdef MA_ENABLE_SPEEX_RESAMPLER
+#define MINIAUDIO_SPEEX_RESAMPLER_IMPLEMENTATION
+#include "./miniaudio/extras/speex_resampler/ma_speex_resampler.h"
+#endif
+
 #include "./miniaudio/miniaudio.h"
@@ -7,6 +7,12 @@ pub mod util;
 
 include!(env!("MINIAUDIO_SYS_BINDINGS_FILE"));
 
+// The Speex resampler header is not part of the generated bindings.
+#[cfg(feature = "ma-enable-speex")]
+extern "C" {
+    pub fn ma_speex_resampler_reset_mem(st: *mut libc::c_void) -> libc::c_int;
+}
+
 #[cfg(feature = "bindgen")]
 #[test]
 fn bindgen_is_enabled() {
@@ -0,0 +1,64 @@
+use miniaudio::{Decoder, Device, DeviceConfig, DeviceType, Resampler, ResamplerConfig};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+pub fn main() {
+    let mut decoder = Decoder::from_file("miniaudio/examples/assets/exit.wav", None)
+        .expect("failed to initialize decoder from file");
+    let source_rate = decoder.output_sample_rate();
+
+    // The sample rate is left at the default so that the device follows the native rate of
+    // whatever device the stream is routed to.
+    let mut config = DeviceConfig::new(DeviceType::Playback);
+    config.playback_mut().set_format(decoder.output_format());
+    config
+        .playback_mut()
+        .set_channels(decoder.output_channels());
+
+    let mut device = Device::new(None, &config).expect("failed to open playback device");
+
+    let resampler_config =
+        ResamplerConfig::for_device(source_rate, &device).expect("failed to create config");
+    let mut resampler = Resampler::new(&resampler_config).expect("failed to create resampler");
+
+    // The reroute callback runs right before the data callback on the audio thread, so the new
+    // rate is handed over through an atomic and picked up by the next data callback.
+    let device_rate = Arc::new(AtomicU32::new(device.sample_rate()));
+    let rerouted_rate = Arc::clone(&device_rate);
+    device.on_reroute(move |sample_rate, channels| {
+        println!("Rerouted to {}Hz with {} channels", sample_rate, channels);
+        rerouted_rate.store(sample_rate, Ordering::Release);
+    });
+
+    device.set_data_callback(move |_device, output, _frames| {
+        let sample_rate = device_rate.load(Ordering::Acquire);
+        if sample_rate != resampler.config().sample_rate_out() {
+            resampler
+                .set_rate(source_rate, sample_rate)
+                .expect("failed to change the resampler rate");
+        }
+
+        resampler
+            .fill_device_buffer(output, |input| decoder.read_pcm_frames(input))
+            .expect("failed to resample");
+    });
+
+    device.start().expect("failed to start device");
+
+    println!("Change the default output device to see the resampler follow it.");
+    wait_for_enter();
+    println!("Shutting Down...");
+}
+
+/// Shows a prompt and waits for input on stdin.
+fn wait_for_enter() {
+    use std::io::Write;
+
+    print!("Press ENTER/RETURN to exit...");
+    // Make sure the line above is displayed:
+    std::io::stdout().flush().expect("failed to flush stdout");
+    // Just read some random line off of stdin and discard it:
+    std::io::stdin()
+        .read_line(&mut String::new())
+        .expect("failed to wait for line");
+}
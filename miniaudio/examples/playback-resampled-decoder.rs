@@ -0,0 +1,51 @@
+use miniaudio::{Decoder, Device, DeviceConfig, DeviceType, Resampler, ResamplerConfig};
+
+pub fn main() {
+    let mut decoder = Decoder::from_file("miniaudio/examples/assets/exit.wav", None)
+        .expect("failed to initialize decoder from file");
+
+    // The sample rate is left at the default so that the device uses its native one, which
+    // avoids a conversion inside of miniaudio.
+    let mut config = DeviceConfig::new(DeviceType::Playback);
+    config.playback_mut().set_format(decoder.output_format());
+    config
+        .playback_mut()
+        .set_channels(decoder.output_channels());
+
+    let mut device = Device::new(None, &config).expect("failed to open playback device");
+
+    let resampler_config = ResamplerConfig::for_device(decoder.output_sample_rate(), &device)
+        .expect("failed to create resampler config");
+    let mut resampler = Resampler::new(&resampler_config).expect("failed to create resampler");
+
+    // The resampler writes directly into the buffer of the device, pulling exactly as many frames
+    // out of the decoder as it needs for each period.
+    device.set_data_callback(move |_device, output, _frames| {
+        resampler
+            .fill_device_buffer(output, |input| decoder.read_pcm_frames(input))
+            .expect("failed to resample");
+    });
+
+    device.start().expect("failed to start device");
+
+    println!(
+        "Resampling from {}Hz to {}Hz",
+        resampler_config.sample_rate_in(),
+        resampler_config.sample_rate_out()
+    );
+    wait_for_enter();
+    println!("Shutting Down...");
+}
+
+/// Shows a prompt and waits for input on stdin.
+fn wait_for_enter() {
+    use std::io::Write;
+
+    print!("Press ENTER/RETURN to exit...");
+    // Make sure the line above is displayed:
+    std::io::stdout().flush().expect("failed to flush stdout");
+    // Just read some random line off of stdin and discard it:
+    std::io::stdin()
+        .read_line(&mut String::new())
+        .expect("failed to wait for line");
+}
@@ -0,0 +1,578 @@
+use crate::base::{DitherMode, Error, Format};
+use crate::frames::{Frames, FramesMut};
+use crate::resampling::{
+    ResampleAlgorithm, ResampleAlgorithmType, Resampler, ResamplerConfig, DEFAULT_SINC_BETA,
+    DEFAULT_SINC_WINDOW_LENGTH,
+};
+use crate::sinc::bessel_i0;
+
+/// Half the length of the reference kernel in input frames at the cutoff frequency.
+const REFERENCE_HALF_LENGTH: f64 = 64.0;
+
+/// The Kaiser window beta of the reference kernel, which gives about 100dB of stopband
+/// attenuation.
+const REFERENCE_BETA: f64 = 10.0;
+
+/// The cutoff of the reference relative to the lower of the two Nyquist frequencies.
+const REFERENCE_CUTOFF_FACTOR: f64 = 0.95;
+
+/// The number of entries per input frame in the reference kernel table. Linearly interpolating
+/// the table is accurate to about -120dB.
+const REFERENCE_OVERSAMPLING: f64 = 1024.0;
+
+/// The number of output frames used to find the delay of each algorithm.
+const DELAY_SEARCH_FRAMES: usize = 2048;
+
+/// How far from the latency reported by the resampler its delay is searched for in input frames.
+const DELAY_SEARCH_RANGE: f64 = 4.0;
+
+/// The number of output frames used for the spectrum when measuring aliasing.
+const SPECTRUM_FRAMES: usize = 1024;
+
+/// Frequencies where the reference is this far below its loudest frequency are considered to have
+/// no content (-60dB).
+const EMPTY_BIN_THRESHOLD: f64 = 1.0e-6;
+
+/// The speex qualities that are compared when the Speex resampler is available.
+const SPEEX_QUALITIES: [u32; 3] = [3, 5, 10];
+
+/// How well one algorithm resampled the input in `compare_algorithms`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlgorithmQuality {
+    pub algorithm: ResampleAlgorithm,
+    /// The ratio of the power of the ideal output to the power of the difference between the
+    /// output and the ideal output in decibels. Higher is better.
+    pub snr_db: f64,
+    /// The power of the difference to the ideal output at frequencies where the ideal output has
+    /// no content, relative to the power of the ideal output in decibels. This is where aliasing
+    /// and imaging end up. Lower is better.
+    pub aliasing_db: f64,
+}
+
+/// The results of `compare_algorithms`, with one entry per algorithm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityReport {
+    pub sample_rate_in: u32,
+    pub sample_rate_out: u32,
+    pub results: Vec<AlgorithmQuality>,
+}
+
+impl QualityReport {
+    /// Returns the result for an algorithm if it was compared.
+    pub fn get(&self, algorithm: ResampleAlgorithm) -> Option<&AlgorithmQuality> {
+        self.results.iter().find(|r| r.algorithm == algorithm)
+    }
+
+    /// Returns the result with the highest signal to noise ratio.
+    pub fn best_snr(&self) -> Option<&AlgorithmQuality> {
+        self.results
+            .iter()
+            .max_by(|a, b| a.snr_db.total_cmp(&b.snr_db))
+    }
+}
+
+/// Resamples `input` from `sample_rate_in` to `sample_rate_out` with each algorithm and measures
+/// how close the output is to ideal band-limited resampling, to help pick an algorithm for some
+/// content and CPU budget.
+///
+/// This compares the linear resampler with its low-pass filter off and at miniaudio's default
+/// order, the Speex resampler at qualities 3, 5 and 10 when the `ma-enable-speex` feature is
+/// enabled, and the sinc resampler with its default window. All algorithms run on an `f32` copy of
+/// the input.
+///
+/// The ideal output is computed directly from the input with a long windowed sinc which passes
+/// frequencies up to 95% of the lower Nyquist frequency, so content close to the Nyquist frequency
+/// counts as error for algorithms that let it through. The delay of each algorithm is found by
+/// searching for the one with the smallest error, and only output frames away from the start and
+/// end of the input are measured. The input needs to be long enough for that, at least a few
+/// thousand frames, otherwise this returns `Error::InvalidArgs`. This is meant for offline
+/// analysis and is slow.
+pub fn compare_algorithms(
+    input: &Frames,
+    sample_rate_in: u32,
+    sample_rate_out: u32,
+) -> Result<QualityReport, Error> {
+    if sample_rate_in == 0 || sample_rate_out == 0 {
+        return Err(Error::InvalidArgs);
+    }
+
+    let channels = input.channels() as usize;
+    let mut samples = vec![0.0f32; input.sample_count()];
+    input.convert(
+        &mut FramesMut::wrap(&mut samples[..], Format::F32, input.channels()),
+        DitherMode::None,
+    );
+
+    let reference = Reference::new(&samples, channels, sample_rate_in, sample_rate_out);
+    let input_frames = input.frame_count();
+    let margin = reference.support.ceil() as usize + 1;
+    // The output must have enough frames away from the edges to search for the delay.
+    let min_output_frames = DELAY_SEARCH_FRAMES.max(SPECTRUM_FRAMES) as f64 * reference.ratio;
+    if (input_frames as f64) < 2.0 * margin as f64 + min_output_frames {
+        ma_debug_panic!(
+            "input is too short to compare resampling algorithms ({} frames)",
+            input_frames
+        );
+        return Err(Error::InvalidArgs);
+    }
+
+    let mut results = Vec::new();
+    for algorithm in algorithms_to_compare(input.channels(), sample_rate_in, sample_rate_out) {
+        let mut config = ResamplerConfig::new(
+            Format::F32,
+            input.channels(),
+            sample_rate_in,
+            sample_rate_out,
+            algorithm.algorithm_type(),
+        );
+        config.set_algorithm(algorithm);
+        let mut resampler = Resampler::new(&config)?;
+        let output = resampler.resample_buffer(
+            &Frames::wrap(&samples[..], Format::F32, input.channels()),
+            || true,
+        )?;
+        let output = output.frames();
+        let output = output.as_samples::<f32>();
+        let latency = resampler.input_latency() as f64;
+
+        let delay = reference.find_delay(output, latency, margin);
+        let (snr_db, aliasing_db) = reference.measure(output, delay, margin);
+        results.push(AlgorithmQuality {
+            algorithm,
+            snr_db,
+            aliasing_db,
+        });
+    }
+
+    Ok(QualityReport {
+        sample_rate_in,
+        sample_rate_out,
+        results,
+    })
+}
+
+fn algorithms_to_compare(
+    channels: u32,
+    sample_rate_in: u32,
+    sample_rate_out: u32,
+) -> Vec<ResampleAlgorithm> {
+    let default_linear = ResamplerConfig::new(
+        Format::F32,
+        channels,
+        sample_rate_in,
+        sample_rate_out,
+        ResampleAlgorithmType::Linear,
+    )
+    .algorithm();
+    let lpf_nyquist_factor = match default_linear {
+        ResampleAlgorithm::Linear {
+            lpf_nyquist_factor, ..
+        } => lpf_nyquist_factor,
+        _ => 1.0,
+    };
+
+    let mut algorithms = vec![
+        ResampleAlgorithm::Linear {
+            lpf_order: 0,
+            lpf_nyquist_factor,
+        },
+        default_linear,
+    ];
+
+    if ResampleAlgorithmType::Speex.is_available() {
+        for &quality in SPEEX_QUALITIES.iter() {
+            algorithms.push(ResampleAlgorithm::Speex { quality });
+        }
+    }
+
+    algorithms.push(ResampleAlgorithm::Sinc {
+        window_length: DEFAULT_SINC_WINDOW_LENGTH,
+        beta: DEFAULT_SINC_BETA,
+    });
+    algorithms
+}
+
+/// Computes the ideal output of resampling directly from the input.
+struct Reference<'s> {
+    samples: &'s [f32],
+    channels: usize,
+    /// Input frames per output frame.
+    ratio: f64,
+    /// The distance from an output position to the furthest input frame that is used for it.
+    support: f64,
+    /// One side of the kernel, sampled `REFERENCE_OVERSAMPLING` times per input frame.
+    kernel: Vec<f64>,
+}
+
+impl<'s> Reference<'s> {
+    fn new(
+        samples: &'s [f32],
+        channels: usize,
+        sample_rate_in: u32,
+        sample_rate_out: u32,
+    ) -> Reference<'s> {
+        let ratio = sample_rate_in as f64 / sample_rate_out as f64;
+        let cutoff = REFERENCE_CUTOFF_FACTOR * (1.0 / ratio).min(1.0);
+        let support = REFERENCE_HALF_LENGTH / cutoff;
+        let i0_beta = bessel_i0(REFERENCE_BETA);
+
+        let entries = (support * REFERENCE_OVERSAMPLING).ceil() as usize + 2;
+        let kernel = (0..entries)
+            .map(|index| {
+                let distance = index as f64 / REFERENCE_OVERSAMPLING;
+                if distance >= support {
+                    return 0.0;
+                }
+                let ratio = distance / support;
+                let window = bessel_i0(REFERENCE_BETA * (1.0 - ratio * ratio).sqrt()) / i0_beta;
+                let x = std::f64::consts::PI * cutoff * distance;
+                let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+                cutoff * sinc * window
+            })
+            .collect();
+
+        Reference {
+            samples,
+            channels,
+            ratio,
+            support,
+            kernel,
+        }
+    }
+
+    fn kernel_at(&self, distance: f64) -> f64 {
+        let position = distance.abs() * REFERENCE_OVERSAMPLING;
+        let index = position as usize;
+        if index + 1 >= self.kernel.len() {
+            return 0.0;
+        }
+        let fraction = position - index as f64;
+        self.kernel[index] + (self.kernel[index + 1] - self.kernel[index]) * fraction
+    }
+
+    fn frame_count(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    /// Writes the ideal output at `position` in input frames into `frame`.
+    fn frame_at(&self, position: f64, frame: &mut [f64]) {
+        frame.iter_mut().for_each(|s| *s = 0.0);
+
+        let first = (position - self.support).ceil().max(0.0) as usize;
+        let last = ((position + self.support).floor() as usize).min(self.frame_count() - 1);
+        for index in first..=last {
+            let weight = self.kernel_at(index as f64 - position);
+
+            let input = &self.samples[(index * self.channels)..((index + 1) * self.channels)];
+            for (sample, &value) in frame.iter_mut().zip(input) {
+                *sample += weight * value as f64;
+            }
+        }
+    }
+
+    /// Returns the range of output frames away from the edges of the input for a delay.
+    fn valid_frames(&self, output: &[f32], delay: f64, margin: usize) -> std::ops::Range<usize> {
+        let output_frames = output.len() / self.channels;
+        let first = ((margin as f64 + delay) / self.ratio).ceil().max(0.0) as usize;
+        let last = (((self.frame_count() - margin) as f64 + delay) / self.ratio).floor() as usize;
+        first.min(output_frames)..last.min(output_frames)
+    }
+
+    /// Returns the power of the ideal output and the power of the error for some output frames.
+    fn error(&self, output: &[f32], delay: f64, frames: std::ops::Range<usize>) -> (f64, f64) {
+        let mut frame = vec![0.0; self.channels];
+        let mut signal = 0.0;
+        let mut error = 0.0;
+        for index in frames {
+            self.frame_at(index as f64 * self.ratio - delay, &mut frame);
+            let out = &output[(index * self.channels)..((index + 1) * self.channels)];
+            for (&ideal, &actual) in frame.iter().zip(out) {
+                signal += ideal * ideal;
+                error += (actual as f64 - ideal) * (actual as f64 - ideal);
+            }
+        }
+        (signal, error)
+    }
+
+    /// Finds the delay in input frames with the smallest error, starting around `latency`.
+    fn find_delay(&self, output: &[f32], latency: f64, margin: usize) -> f64 {
+        // Use the same frames for every delay, from the middle of the output so that they are
+        // valid for every delay that is tried.
+        let valid = self.valid_frames(output, latency, margin);
+        let shrink = (DELAY_SEARCH_RANGE / self.ratio).ceil() as usize + 1;
+        let valid = (valid.start + shrink)..valid.end.saturating_sub(shrink);
+        let start = valid.start + valid.len().saturating_sub(DELAY_SEARCH_FRAMES) / 2;
+        let frames = start..(start + DELAY_SEARCH_FRAMES).min(valid.end);
+
+        let search = |from: f64, to: f64, step: f64, best: &mut (f64, f64)| {
+            let mut delay = from;
+            while delay <= to {
+                let (_, error) = self.error(output, delay, frames.clone());
+                if error < best.1 {
+                    *best = (delay, error);
+                }
+                delay += step;
+            }
+        };
+
+        let mut best = (latency, f64::INFINITY);
+        let (from, to) = (latency - DELAY_SEARCH_RANGE, latency + DELAY_SEARCH_RANGE);
+        search(from, to, 1.0 / 8.0, &mut best);
+        let coarse = best.0;
+        search(
+            coarse - 1.0 / 8.0,
+            coarse + 1.0 / 8.0,
+            1.0 / 128.0,
+            &mut best,
+        );
+        best.0
+    }
+
+    /// Returns the signal to noise ratio and the aliasing in decibels.
+    fn measure(&self, output: &[f32], delay: f64, margin: usize) -> (f64, f64) {
+        let valid = self.valid_frames(output, delay, margin);
+        let (signal, error) = self.error(output, delay, valid.clone());
+        let snr_db = 10.0 * (signal / error).log10();
+
+        // Compare the spectra of the ideal output and the error in the middle of the output.
+        let start = valid.start + valid.len().saturating_sub(SPECTRUM_FRAMES) / 2;
+        let length = SPECTRUM_FRAMES.min(valid.end - start);
+        let mut ideal = vec![vec![0.0; length]; self.channels];
+        let mut errors = vec![vec![0.0; length]; self.channels];
+        let mut frame = vec![0.0; self.channels];
+        for offset in 0..length {
+            let index = start + offset;
+            self.frame_at(index as f64 * self.ratio - delay, &mut frame);
+            for channel in 0..self.channels {
+                let actual = output[index * self.channels + channel] as f64;
+                ideal[channel][offset] = frame[channel];
+                errors[channel][offset] = actual - frame[channel];
+            }
+        }
+
+        let mut ideal_power = vec![0.0; length / 2 + 1];
+        let mut error_power = vec![0.0; length / 2 + 1];
+        for channel in 0..self.channels {
+            add_power_spectrum(&ideal[channel], &mut ideal_power);
+            add_power_spectrum(&errors[channel], &mut error_power);
+        }
+
+        let loudest = ideal_power.iter().cloned().fold(0.0, f64::max);
+        let total: f64 = ideal_power.iter().sum();
+        let aliasing: f64 = ideal_power
+            .iter()
+            .zip(error_power.iter())
+            .filter(|&(&ideal, _)| ideal < loudest * EMPTY_BIN_THRESHOLD)
+            .map(|(_, &error)| error)
+            .sum();
+        let aliasing_db = 10.0 * (aliasing / total).log10();
+
+        (snr_db, aliasing_db)
+    }
+}
+
+/// Adds the power spectrum of `samples` with a Hann window to `power`, which has one entry for
+/// every frequency up to the Nyquist frequency.
+fn add_power_spectrum(samples: &[f64], power: &mut [f64]) {
+    let length = samples.len();
+    let windowed: Vec<f64> = samples
+        .iter()
+        .enumerate()
+        .map(|(n, &s)| {
+            let phase = 2.0 * std::f64::consts::PI * n as f64 / length as f64;
+            s * 0.5 * (1.0 - phase.cos())
+        })
+        .collect();
+
+    let (sin, cos): (Vec<f64>, Vec<f64>) = (0..length)
+        .map(|n| (2.0 * std::f64::consts::PI * n as f64 / length as f64).sin_cos())
+        .unzip();
+
+    for (bin, p) in power.iter_mut().enumerate() {
+        let mut re = 0.0;
+        let mut im = 0.0;
+        for (n, &s) in windowed.iter().enumerate() {
+            let index = (bin * n) % length;
+            re += s * cos[index];
+            im -= s * sin[index];
+        }
+        *p += re * re + im * im;
+    }
+}
+
+/// The number of input frames that are resampled at a time by `benchmark_resampler`, which is
+/// about the size of a device period.
+#[cfg(feature = "bench")]
+const BENCH_CHUNK_FRAMES: usize = 1024;
+
+/// The number of chunks that are resampled before the timing starts.
+#[cfg(feature = "bench")]
+const BENCH_WARMUP_CHUNKS: usize = 16;
+
+/// The result of `benchmark_resampler`.
+#[cfg(feature = "bench")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResamplerBench {
+    /// The duration of the audio that was resampled in seconds at the input sample rate.
+    pub input_seconds: f64,
+    /// The wall time spent resampling.
+    pub elapsed: std::time::Duration,
+    /// Seconds of audio resampled per second of wall time. A real-time factor of 100 means that
+    /// one core can resample about 100 streams with this config in real time.
+    pub realtime_factor: f64,
+}
+
+/// Measures how long a resampler with `config` takes to resample `input_seconds` of audio, for
+/// estimating how many streams can be resampled at the same time.
+///
+/// The input is a mix of tones and noise in the format of the config and it is resampled in
+/// chunks of 1024 frames, like a device callback would. A few chunks are resampled before the
+/// timing starts so that the caches and the resampler's state are warmed up. The result depends on
+/// the machine and what else it is doing, so run it a few times and use a build with
+/// optimizations.
+#[cfg(feature = "bench")]
+pub fn benchmark_resampler(
+    config: &ResamplerConfig,
+    input_seconds: f64,
+) -> Result<ResamplerBench, Error> {
+    if !input_seconds.is_finite() || input_seconds <= 0.0 {
+        ma_debug_panic!(
+            "benchmark duration must be positive (got {})",
+            input_seconds
+        );
+        return Err(Error::InvalidArgs);
+    }
+
+    let format = config.format();
+    let channels = config.channels();
+    let mut resampler = Resampler::new(config)?;
+
+    // Tones with some noise so that the input isn't trivial for any algorithm.
+    let mut samples = vec![0.0f32; BENCH_CHUNK_FRAMES * channels as usize];
+    let mut noise = 0x1234_5678u32;
+    for (index, sample) in samples.iter_mut().enumerate() {
+        let t = (index / channels as usize) as f32 / config.sample_rate_in() as f32;
+        noise = noise.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let noise = (noise >> 8) as f32 / (1 << 24) as f32 - 0.5;
+        *sample = 0.3 * (std::f32::consts::TAU * 440.0 * t).sin()
+            + 0.2 * (std::f32::consts::TAU * 5000.0 * t).sin()
+            + 0.1 * noise;
+    }
+    let mut input = crate::frames::FramesBuffer::new(format, channels, BENCH_CHUNK_FRAMES);
+    Frames::wrap(&samples[..], Format::F32, channels)
+        .convert(&mut input.frames_mut(), DitherMode::None);
+
+    let output_frames = resampler.expected_output_frame_count(BENCH_CHUNK_FRAMES as u64) as usize;
+    let mut output = crate::frames::FramesBuffer::new(format, channels, output_frames + 16);
+
+    for _ in 0..BENCH_WARMUP_CHUNKS {
+        resampler.process_pcm_frames(&mut output.frames_mut(), &input.frames())?;
+    }
+
+    let total_frames = (input_seconds * config.sample_rate_in() as f64).ceil() as u64;
+    let mut processed = 0;
+    let start = std::time::Instant::now();
+    while processed < total_frames {
+        let (consumed, _) =
+            resampler.process_pcm_frames(&mut output.frames_mut(), &input.frames())?;
+        if consumed == 0 {
+            return Err(Error::InvalidOperation);
+        }
+        processed += consumed;
+    }
+    let elapsed = start.elapsed();
+
+    let input_seconds = processed as f64 / config.sample_rate_in() as f64;
+    Ok(ResamplerBench {
+        input_seconds,
+        elapsed,
+        realtime_factor: input_seconds / elapsed.as_secs_f64().max(f64::MIN_POSITIVE),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A few seconds of mono tones spread up to close to the Nyquist frequency.
+    fn tones(sample_rate: u32, frame_count: usize) -> Vec<f32> {
+        (0..frame_count)
+            .map(|i| {
+                let t = i as f64 / sample_rate as f64;
+                [440.0, 3000.0, 9000.0, 17000.0]
+                    .iter()
+                    .map(|f| 0.2 * (std::f64::consts::TAU * f * t).sin())
+                    .sum::<f64>() as f32
+            })
+            .collect()
+    }
+
+    fn linear_without_lpf(report: &QualityReport) -> &AlgorithmQuality {
+        report
+            .results
+            .iter()
+            .find(|r| matches!(r.algorithm, ResampleAlgorithm::Linear { lpf_order: 0, .. }))
+            .unwrap()
+    }
+
+    #[test]
+    fn better_algorithms_measure_better_than_linear_without_a_filter() {
+        let samples = tones(44100, 16384);
+        let report =
+            compare_algorithms(&Frames::wrap(&samples[..], Format::F32, 1), 44100, 48000).unwrap();
+        assert_eq!(report.sample_rate_in, 44100);
+        assert_eq!(report.sample_rate_out, 48000);
+        let linear = linear_without_lpf(&report);
+
+        // Speex is only compiled in with its feature, the sinc resampler is always there.
+        let better = if cfg!(feature = "ma-enable-speex") {
+            report.get(ResampleAlgorithm::Speex { quality: 10 })
+        } else {
+            report.get(ResampleAlgorithm::Sinc {
+                window_length: DEFAULT_SINC_WINDOW_LENGTH,
+                beta: DEFAULT_SINC_BETA,
+            })
+        }
+        .unwrap();
+
+        assert!(
+            better.snr_db > linear.snr_db + 20.0,
+            "{:?} vs {:?}",
+            better,
+            linear
+        );
+        assert!(
+            better.aliasing_db < linear.aliasing_db - 20.0,
+            "{:?} vs {:?}",
+            better,
+            linear
+        );
+        assert!(report.best_snr().unwrap().snr_db >= better.snr_db);
+    }
+
+    #[cfg(feature = "bench")]
+    #[test]
+    fn benchmark_reports_a_positive_realtime_factor() {
+        let config =
+            ResamplerConfig::new(Format::S16, 2, 44100, 48000, ResampleAlgorithmType::Linear);
+        let bench = benchmark_resampler(&config, 0.5).unwrap();
+        assert!(bench.input_seconds >= 0.5);
+        assert!(bench.realtime_factor.is_finite());
+        assert!(bench.realtime_factor > 0.0);
+    }
+
+    #[cfg(feature = "bench")]
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "benchmark duration must be positive")
+    )]
+    fn benchmark_of_no_audio_is_rejected() {
+        let config =
+            ResamplerConfig::new(Format::S16, 2, 44100, 48000, ResampleAlgorithmType::Linear);
+        assert!(matches!(
+            benchmark_resampler(&config, 0.0),
+            Err(Error::InvalidArgs)
+        ));
+    }
+}
@@ -418,6 +418,12 @@ impl Format {
             Self::F32 => 4,
         }
     }
+
+    /// The size of one sample in this format in bytes. This is the same as `size_in_bytes`.
+    #[inline]
+    pub fn bytes_per_sample(self) -> usize {
+        self.size_in_bytes()
+    }
 }
 
 impl Default for Format {
@@ -518,3 +524,64 @@ pub const MIN_CHANNELS: usize = sys::MA_MIN_CHANNELS as usize;
 pub const MAX_CHANNELS: usize = sys::MA_MAX_CHANNELS as usize;
 
 pub const MAX_FILTER_ORDER: usize = sys::MA_MAX_FILTER_ORDER as _;
+
+/// The maximum number of channels that miniaudio supports. This is the same as `MAX_CHANNELS`.
+#[inline]
+pub const fn max_channels() -> u32 {
+    MAX_CHANNELS as u32
+}
+
+/// Converts a linear gain factor into decibels, where a factor of 1.0 is 0 dB.
+///
+/// A factor of 0.0 (or less) is silence and returns negative infinity.
+#[inline]
+pub fn gain_to_db(linear: f32) -> f32 {
+    if linear <= 0.0 {
+        return f32::NEG_INFINITY;
+    }
+    unsafe { sys::ma_factor_to_gain_db(linear) }
+}
+
+/// Converts a gain in decibels into a linear gain factor, where 0 dB is a factor of 1.0.
+///
+/// Negative infinity returns a factor of 0.0.
+#[inline]
+pub fn db_to_gain(db: f32) -> f32 {
+    if db == f32::NEG_INFINITY {
+        return 0.0;
+    }
+    unsafe { sys::ma_gain_db_to_factor(db) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gains_and_decibels_convert_both_ways() {
+        assert_eq!(gain_to_db(0.0), f32::NEG_INFINITY);
+        assert_eq!(gain_to_db(-1.0), f32::NEG_INFINITY);
+        assert_eq!(db_to_gain(f32::NEG_INFINITY), 0.0);
+        assert!(gain_to_db(1.0).abs() < 1e-6);
+        assert!((gain_to_db(0.5) + 6.0206).abs() < 1e-3);
+        assert!((db_to_gain(-20.0) - 0.1).abs() < 1e-6);
+
+        for &db in &[-60.0f32, -6.0, 0.0, 3.0, 12.0] {
+            assert!((gain_to_db(db_to_gain(db)) - db).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn out_of_memory_results_map_to_out_of_memory() {
+        assert!(matches!(
+            Error::from_c_result(sys::MA_OUT_OF_MEMORY),
+            Err(Error::OutOfMemory)
+        ));
+        assert!(matches!(
+            Error::from_c_error(sys::MA_OUT_OF_MEMORY),
+            Error::OutOfMemory
+        ));
+        assert_eq!(Error::OutOfMemory as sys::ma_result, sys::MA_OUT_OF_MEMORY);
+        assert!(Error::from_c_result(sys::MA_SUCCESS as _).is_ok());
+    }
+}
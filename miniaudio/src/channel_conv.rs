@@ -92,6 +92,99 @@ impl ChannelConverterConfig {
 
         self.0.weights[channel_in_index][channel_out_index] = weight;
     }
+
+    /// Sets the weights for folding surround down to stereo according to `standard` and switches
+    /// the mixing mode to `ChannelMixMode::CustomWeights`.
+    ///
+    /// The output channel map must be `FrontLeft` and `FrontRight` in any order, otherwise
+    /// `Error::InvalidArgs` is returned. Input channels are mixed by their position in the input
+    /// channel map: the front left and right channels are passed through, the center and the
+    /// surrounds (the back or side channels) are mixed as described by `DownmixStandard`, and all
+    /// other channels including the LFE are dropped. The weights are not normalized, so loud
+    /// surround material can clip.
+    pub fn set_downmix_standard(&mut self, standard: DownmixStandard) -> Result<(), Error> {
+        let map_out = self.channel_map_out();
+        let left = map_out.iter().position(|&c| c == Channel::FrontLeft);
+        let right = map_out.iter().position(|&c| c == Channel::FrontRight);
+        let (left, right) = match (map_out.len(), left, right) {
+            (2, Some(left), Some(right)) => (left, right),
+            _ => {
+                ma_debug_panic!("downmix standards can only be used with a stereo output");
+                return Err(Error::InvalidArgs);
+            }
+        };
+
+        let weights = standard.weights();
+        for index in 0..self.0.channelsIn as usize {
+            let (to_left, to_right) = match self.channel_map_in()[index] {
+                Channel::FrontLeft => (1.0, 0.0),
+                Channel::FrontRight => (0.0, 1.0),
+                Channel::FrontCenter => (weights.center, weights.center),
+                Channel::BackLeft | Channel::SideLeft => weights.surround_left,
+                Channel::BackRight | Channel::SideRight => weights.surround_right,
+                _ => (0.0, 0.0),
+            };
+            self.set_weight(index, left, to_left);
+            self.set_weight(index, right, to_right);
+        }
+
+        self.0.mixingMode = ChannelMixMode::CustomWeights as _;
+        Ok(())
+    }
+}
+
+/// How a surround channel is mixed into the left and right output channels respectively.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DownmixWeights {
+    /// The gain of the center channel in both outputs.
+    pub center: f32,
+    pub surround_left: (f32, f32),
+    pub surround_right: (f32, f32),
+}
+
+/// Standard coefficients for folding 5.1 down to stereo, used with
+/// `ChannelConverterConfig::set_downmix_standard`. The LFE channel is dropped by all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownmixStandard {
+    /// ITU-R BS.775 (Lo/Ro): the center and each surround channel are mixed into their side at
+    /// -3dB (0.7071), so `L = FL + 0.7071 C + 0.7071 SL` and `R = FR + 0.7071 C + 0.7071 SR`. This
+    /// is the standard for broadcast.
+    Itu,
+    /// Dolby Surround (Lt/Rt): the center is mixed at -3dB and the surrounds are summed to mono at
+    /// -3dB and mixed in at -3dB, out of phase in the left channel, so `L = FL + 0.7071 C -
+    /// 0.5 SL - 0.5 SR` and `R = FR + 0.7071 C + 0.5 SL + 0.5 SR`. A matrix decoder can recover the
+    /// surround channel from the difference.
+    Dolby,
+    /// Dolby Pro Logic II (Lt/Rt): like `Dolby` but the surrounds keep some of their separation,
+    /// so `L = FL + 0.7071 C - 0.8660 SL - 0.5 SR` and `R = FR + 0.7071 C + 0.5 SL + 0.8660 SR`.
+    ProLogic,
+}
+
+impl DownmixStandard {
+    /// Returns the coefficients of this standard.
+    pub fn weights(self) -> DownmixWeights {
+        let minus_3db = std::f32::consts::FRAC_1_SQRT_2;
+        match self {
+            DownmixStandard::Itu => DownmixWeights {
+                center: minus_3db,
+                surround_left: (minus_3db, 0.0),
+                surround_right: (0.0, minus_3db),
+            },
+            DownmixStandard::Dolby => DownmixWeights {
+                center: minus_3db,
+                surround_left: (-0.5, 0.5),
+                surround_right: (-0.5, 0.5),
+            },
+            DownmixStandard::ProLogic => {
+                let major = 0.866_025_4;
+                DownmixWeights {
+                    center: minus_3db,
+                    surround_left: (-major, 0.5),
+                    surround_right: (-0.5, major),
+                }
+            }
+        }
+    }
 }
 
 // FIXME For now this can use the default clone implementation because as far as I can tell
@@ -211,6 +304,12 @@ impl ChannelConverter {
         output: &mut FramesMut,
         input: &Frames,
     ) -> Result<(), Error> {
+        ma_trace_span!(
+            "ChannelConverter::process_pcm_frames",
+            output_frames = output.frame_count(),
+            input_frames = input.frame_count(),
+        );
+
         if output.format() != input.format() {
             ma_debug_panic!(
                 "output and input format did not match (output: {:?}, input: {:?}",
@@ -243,3 +342,96 @@ impl Drop for ChannelConverter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SURROUND_5_1: [Channel; 6] = [
+        Channel::FrontLeft,
+        Channel::FrontRight,
+        Channel::FrontCenter,
+        Channel::Lfe,
+        Channel::BackLeft,
+        Channel::BackRight,
+    ];
+
+    #[test]
+    fn itu_downmix_uses_the_bs_775_coefficients() {
+        // ITU-R BS.775 Table 2: Lo = L + 0.7071 C + 0.7071 Ls and Ro = R + 0.7071 C + 0.7071 Rs.
+        let weights = DownmixStandard::Itu.weights();
+        // 0.7071 is -3dB, i.e. half the power.
+        assert!((weights.center * weights.center - 0.5).abs() < 1.0e-6);
+        assert!((20.0 * weights.center.log10() + 3.0).abs() < 0.02);
+        assert_eq!(weights.surround_left, (weights.center, 0.0));
+        assert_eq!(weights.surround_right, (0.0, weights.center));
+
+        let mut config = ChannelConverterConfig::new(
+            Format::F32,
+            &SURROUND_5_1,
+            &[Channel::FrontLeft, Channel::FrontRight],
+            ChannelMixMode::Rectangular,
+        );
+        config.set_downmix_standard(DownmixStandard::Itu).unwrap();
+        assert_eq!(config.mixing_mode(), ChannelMixMode::CustomWeights);
+
+        // One frame per input channel with only that channel at full scale, so each output frame
+        // holds the gains of one input channel.
+        let mut input = [0.0f32; 6 * 6];
+        for channel in 0..6 {
+            input[channel * 6 + channel] = 1.0;
+        }
+        let mut output = [0.0f32; 6 * 2];
+        ChannelConverter::new(&config)
+            .unwrap()
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut output, Format::F32, 2),
+                &Frames::wrap(&input, Format::F32, 6),
+            )
+            .unwrap();
+
+        let c = weights.center;
+        let expected = [1.0, 0.0, 0.0, 1.0, c, c, 0.0, 0.0, c, 0.0, 0.0, c];
+        for (actual, expected) in output.iter().zip(expected.iter()) {
+            assert!((actual - expected).abs() < 1.0e-6, "{:?}", output);
+        }
+    }
+
+    #[test]
+    fn downmix_follows_the_order_of_the_output_channels() {
+        let mut config = ChannelConverterConfig::new(
+            Format::F32,
+            &SURROUND_5_1,
+            &[Channel::FrontRight, Channel::FrontLeft],
+            ChannelMixMode::Rectangular,
+        );
+        config.set_downmix_standard(DownmixStandard::Itu).unwrap();
+        assert_eq!(config.weight(0, 1), 1.0);
+        assert_eq!(config.weight(0, 0), 0.0);
+        assert_eq!(config.weight(4, 1), DownmixStandard::Itu.weights().center);
+        assert_eq!(config.weight(3, 0), 0.0);
+        assert_eq!(config.weight(3, 1), 0.0);
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "downmix standards can only be used with a stereo output")
+    )]
+    fn downmix_to_more_than_stereo_is_rejected() {
+        let mut config = ChannelConverterConfig::new(
+            Format::F32,
+            &SURROUND_5_1,
+            &[
+                Channel::FrontLeft,
+                Channel::FrontRight,
+                Channel::FrontCenter,
+            ],
+            ChannelMixMode::Rectangular,
+        );
+        assert!(matches!(
+            config.set_downmix_standard(DownmixStandard::Itu),
+            Err(Error::InvalidArgs)
+        ));
+    }
+}
@@ -78,7 +78,16 @@ impl DataConverterConfig {
         self.0.sampleRateOut
     }
 
+    /// Sets the resampling algorithm. `ResampleAlgorithm::Sinc` is not implemented by miniaudio so
+    /// it can't be used here and is ignored. Use `try_set_resampling` to find out whether the
+    /// algorithm was accepted.
     pub fn set_resampling(&mut self, algo: ResampleAlgorithm) {
+        let _ = self.try_set_resampling(algo);
+    }
+
+    /// Sets the resampling algorithm, returning `Error::InvalidArgs` and leaving the config
+    /// unchanged for `ResampleAlgorithm::Sinc`, which is not implemented by miniaudio.
+    pub fn try_set_resampling(&mut self, algo: ResampleAlgorithm) -> Result<(), Error> {
         match algo {
             ResampleAlgorithm::Linear {
                 lpf_order,
@@ -93,7 +102,14 @@ impl DataConverterConfig {
                 self.0.resampling.algorithm = sys::ma_resample_algorithm_speex;
                 self.0.resampling.speex.quality = quality as _;
             }
+
+            ResampleAlgorithm::Sinc { .. } => {
+                ma_debug_panic!("the sinc resampler can't be used by a data converter");
+                return Err(Error::InvalidArgs);
+            }
         }
+
+        Ok(())
     }
 
     pub fn resampling(&self) -> ResampleAlgorithm {
@@ -139,6 +155,12 @@ impl DataConverter {
         output: &mut FramesMut,
         input: &Frames,
     ) -> Result<(u64, u64), Error> {
+        ma_trace_span!(
+            "DataConverter::process_pcm_frames",
+            output_frames = output.frame_count(),
+            input_frames = input.frame_count(),
+        );
+
         let mut input_frame_count = input.frame_count() as u64;
         let mut output_frame_count = output.frame_count() as u64;
 
@@ -170,7 +192,7 @@ impl DataConverter {
     pub fn required_input_frame_count(&self, output_frame_count: u64) -> u64 {
         unsafe {
             sys::ma_data_converter_get_required_input_frame_count(
-                &self as *const _ as *mut _,
+                &self.0 as *const _ as *mut _,
                 output_frame_count,
             )
         }
@@ -179,18 +201,18 @@ impl DataConverter {
     pub fn expected_output_frame_count(&self, input_frame_count: u64) -> u64 {
         unsafe {
             sys::ma_data_converter_get_expected_output_frame_count(
-                &self as *const _ as *mut _,
+                &self.0 as *const _ as *mut _,
                 input_frame_count,
             )
         }
     }
 
     pub fn input_latency(&self) -> u64 {
-        unsafe { sys::ma_data_converter_get_input_latency(&self as *const _ as *mut _) }
+        unsafe { sys::ma_data_converter_get_input_latency(&self.0 as *const _ as *mut _) }
     }
 
     pub fn output_latency(&self) -> u64 {
-        unsafe { sys::ma_data_converter_get_output_latency(&self as *const _ as *mut _) }
+        unsafe { sys::ma_data_converter_get_output_latency(&self.0 as *const _ as *mut _) }
     }
 }
 
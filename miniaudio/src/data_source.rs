@@ -0,0 +1,397 @@
+use crate::base::{to_bool32, Error, Format};
+use crate::frames::{bytes_per_frame, FramesMut};
+use crate::mixing::MixerSource;
+use miniaudio_sys as sys;
+use std::os::raw::c_void;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Something that produces frames in a single format, like miniaudio's decoders and waveforms.
+///
+/// Implement this for procedural audio or network streams and wrap it in a `CustomDataSource`
+/// to pass it to miniaudio as a `ma_data_source`. Any `DataSource` can also be read by
+/// `Resampler::read` by calling `read_pcm_frames` from its input callback.
+pub trait DataSource {
+    /// Reads up to `output.frame_count()` frames into `output` and returns the number of frames
+    /// that were read. `output` always has the format and channel count returned by
+    /// `data_format`. Returning less than the requested number of frames means that the source
+    /// has ended.
+    fn read_pcm_frames(&mut self, output: &mut FramesMut) -> u64;
+
+    /// Returns the format, channel count and sample rate of the frames that are read from the
+    /// source respectively. This must not change while the source is being read.
+    fn data_format(&self) -> (Format, u32, u32);
+
+    /// Seeks to a frame. Sources that can't seek don't have to implement this, but looping reads
+    /// need it to go back to the start.
+    fn seek_to_pcm_frame(&mut self, frame_index: u64) -> Result<(), Error> {
+        let _ = frame_index;
+        Err(Error::NotImplemented)
+    }
+
+    /// Returns the index of the next frame that will be read.
+    fn cursor(&self) -> Result<u64, Error> {
+        Err(Error::NotImplemented)
+    }
+
+    /// Returns the length of the source in frames. Sources with an unknown length (e.g. streams)
+    /// don't have to implement this.
+    fn length(&self) -> Result<u64, Error> {
+        Err(Error::NotImplemented)
+    }
+}
+
+/// The layout that miniaudio expects for a data source: a struct that starts with the callbacks.
+#[repr(C)]
+struct RawCustomDataSource<T> {
+    callbacks: sys::ma_data_source_callbacks,
+    source: T,
+}
+
+/// Wraps a `DataSource` so that it can be used by miniaudio as a `ma_data_source`.
+///
+/// The source is boxed so that the pointer returned by `as_raw_ptr` stays the same when the
+/// wrapper is moved. Panics in the callbacks of the source are caught and reported to miniaudio as
+/// an error since they can't unwind through miniaudio.
+///
+/// miniaudio 0.10 doesn't have an engine to play data sources with, but a `CustomDataSource` can
+/// be added to a `Mixer` to play it on a device.
+pub struct CustomDataSource<T: DataSource> {
+    inner: Box<RawCustomDataSource<T>>,
+}
+
+impl<T: DataSource> CustomDataSource<T> {
+    pub fn new(source: T) -> CustomDataSource<T> {
+        CustomDataSource {
+            inner: Box::new(RawCustomDataSource {
+                callbacks: sys::ma_data_source_callbacks {
+                    onRead: Some(custom_data_source_read::<T>),
+                    onSeek: Some(custom_data_source_seek::<T>),
+                    onMap: None,
+                    onUnmap: None,
+                    onGetDataFormat: Some(custom_data_source_get_data_format::<T>),
+                    onGetCursor: Some(custom_data_source_get_cursor::<T>),
+                    onGetLength: Some(custom_data_source_get_length::<T>),
+                },
+                source,
+            }),
+        }
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &T {
+        &self.inner.source
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner.source
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner.source
+    }
+
+    /// Returns a pointer that can be passed to miniaudio wherever it takes a `ma_data_source`.
+    /// The pointer is valid for as long as this wrapper is alive.
+    #[inline]
+    pub fn as_raw_ptr(&mut self) -> *mut sys::ma_data_source {
+        &mut *self.inner as *mut RawCustomDataSource<T> as *mut sys::ma_data_source
+    }
+
+    /// Reads frames through miniaudio's data source API. If `looping` is true the source is
+    /// seeked back to the start when it ends, so the output is filled completely as long as the
+    /// source implements `seek_to_pcm_frame`.
+    ///
+    /// `output` must have the format and channel count of the source.
+    pub fn read_pcm_frames(&mut self, output: &mut FramesMut, looping: bool) -> Result<u64, Error> {
+        let (format, channels, _) = self.inner.source.data_format();
+        if output.format() != format || output.channels() != channels {
+            ma_debug_panic!(
+                "output did not match data source (output: {:?} {}, source: {:?} {})",
+                output.format(),
+                output.channels(),
+                format,
+                channels
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        let mut frames_read = 0;
+        let result = unsafe {
+            sys::ma_data_source_read_pcm_frames(
+                self.as_raw_ptr(),
+                output.as_mut_ptr() as *mut _,
+                output.frame_count() as u64,
+                &mut frames_read,
+                to_bool32(looping),
+            )
+        };
+
+        match Error::from_c_result(result) {
+            Ok(()) | Err(Error::AtEnd) => Ok(frames_read),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Seeks to a frame through miniaudio's data source API.
+    pub fn seek_to_pcm_frame(&mut self, frame_index: u64) -> Result<(), Error> {
+        Error::from_c_result(unsafe {
+            sys::ma_data_source_seek_to_pcm_frame(self.as_raw_ptr(), frame_index)
+        })
+    }
+
+    #[inline]
+    pub fn data_format(&self) -> (Format, u32, u32) {
+        self.inner.source.data_format()
+    }
+
+    #[inline]
+    pub fn cursor(&self) -> Result<u64, Error> {
+        self.inner.source.cursor()
+    }
+
+    #[inline]
+    pub fn length(&self) -> Result<u64, Error> {
+        self.inner.source.length()
+    }
+}
+
+/// The source must produce `f32` frames with the mixer's channel count.
+impl<T: DataSource> MixerSource for CustomDataSource<T> {
+    fn read_pcm_frames(&mut self, output: &mut FramesMut) -> u64 {
+        CustomDataSource::read_pcm_frames(self, output, false).unwrap_or(0)
+    }
+}
+
+/// Only ever called by miniaudio with a pointer from `CustomDataSource::as_raw_ptr`.
+unsafe fn custom_data_source<'a, T>(data_source: *mut sys::ma_data_source) -> &'a mut T {
+    &mut (*(data_source as *mut RawCustomDataSource<T>)).source
+}
+
+unsafe extern "C" fn custom_data_source_read<T: DataSource>(
+    data_source: *mut sys::ma_data_source,
+    frames_out: *mut c_void,
+    frame_count: sys::ma_uint64,
+    frames_read: *mut sys::ma_uint64,
+) -> sys::ma_result {
+    if data_source.is_null() || frames_out.is_null() {
+        return sys::MA_INVALID_ARGS;
+    }
+
+    let source = custom_data_source::<T>(data_source);
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let (format, channels, _) = source.data_format();
+        let byte_count = frame_count as usize * bytes_per_frame(format, channels);
+        let bytes = std::slice::from_raw_parts_mut(frames_out as *mut u8, byte_count);
+        source.read_pcm_frames(&mut FramesMut::wrap(bytes, format, channels))
+    }));
+
+    match result {
+        Ok(read) => {
+            if !frames_read.is_null() {
+                *frames_read = read;
+            }
+            if read == 0 && frame_count > 0 {
+                sys::MA_AT_END
+            } else {
+                sys::MA_SUCCESS as sys::ma_result
+            }
+        }
+        Err(_) => sys::MA_ERROR,
+    }
+}
+
+unsafe extern "C" fn custom_data_source_seek<T: DataSource>(
+    data_source: *mut sys::ma_data_source,
+    frame_index: sys::ma_uint64,
+) -> sys::ma_result {
+    if data_source.is_null() {
+        return sys::MA_INVALID_ARGS;
+    }
+
+    let source = custom_data_source::<T>(data_source);
+    match catch_unwind(AssertUnwindSafe(|| source.seek_to_pcm_frame(frame_index))) {
+        Ok(Ok(())) => sys::MA_SUCCESS as sys::ma_result,
+        Ok(Err(err)) => err as sys::ma_result,
+        Err(_) => sys::MA_ERROR,
+    }
+}
+
+unsafe extern "C" fn custom_data_source_get_data_format<T: DataSource>(
+    data_source: *mut sys::ma_data_source,
+    format: *mut sys::ma_format,
+    channels: *mut sys::ma_uint32,
+    sample_rate: *mut sys::ma_uint32,
+) -> sys::ma_result {
+    if data_source.is_null() {
+        return sys::MA_INVALID_ARGS;
+    }
+
+    let source = custom_data_source::<T>(data_source);
+    let (source_format, source_channels, source_sample_rate) =
+        match catch_unwind(AssertUnwindSafe(|| source.data_format())) {
+            Ok(data_format) => data_format,
+            Err(_) => return sys::MA_ERROR,
+        };
+
+    if !format.is_null() {
+        *format = source_format as sys::ma_format;
+    }
+    if !channels.is_null() {
+        *channels = source_channels;
+    }
+    if !sample_rate.is_null() {
+        *sample_rate = source_sample_rate;
+    }
+    sys::MA_SUCCESS as sys::ma_result
+}
+
+unsafe extern "C" fn custom_data_source_get_cursor<T: DataSource>(
+    data_source: *mut sys::ma_data_source,
+    cursor: *mut sys::ma_uint64,
+) -> sys::ma_result {
+    if data_source.is_null() || cursor.is_null() {
+        return sys::MA_INVALID_ARGS;
+    }
+
+    let source = custom_data_source::<T>(data_source);
+    match catch_unwind(AssertUnwindSafe(|| source.cursor())) {
+        Ok(Ok(value)) => {
+            *cursor = value;
+            sys::MA_SUCCESS as sys::ma_result
+        }
+        Ok(Err(err)) => err as sys::ma_result,
+        Err(_) => sys::MA_ERROR,
+    }
+}
+
+unsafe extern "C" fn custom_data_source_get_length<T: DataSource>(
+    data_source: *mut sys::ma_data_source,
+    length: *mut sys::ma_uint64,
+) -> sys::ma_result {
+    if data_source.is_null() || length.is_null() {
+        return sys::MA_INVALID_ARGS;
+    }
+
+    let source = custom_data_source::<T>(data_source);
+    match catch_unwind(AssertUnwindSafe(|| source.length())) {
+        Ok(Ok(value)) => {
+            *length = value;
+            sys::MA_SUCCESS as sys::ma_result
+        }
+        Ok(Err(err)) => err as sys::ma_result,
+        Err(_) => sys::MA_ERROR,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mono source whose samples are the indices of their frames.
+    struct Ramp {
+        cursor: u64,
+        length: u64,
+        panics: bool,
+    }
+
+    impl Ramp {
+        fn new(length: u64) -> Ramp {
+            Ramp {
+                cursor: 0,
+                length,
+                panics: false,
+            }
+        }
+    }
+
+    impl DataSource for Ramp {
+        fn read_pcm_frames(&mut self, output: &mut FramesMut) -> u64 {
+            assert!(!self.panics, "the source panicked");
+            let frames = (output.frame_count() as u64).min(self.length - self.cursor);
+            for (index, sample) in output.as_samples_mut::<f32>()[..frames as usize]
+                .iter_mut()
+                .enumerate()
+            {
+                *sample = (self.cursor + index as u64) as f32;
+            }
+            self.cursor += frames;
+            frames
+        }
+
+        fn data_format(&self) -> (Format, u32, u32) {
+            (Format::F32, 1, 48000)
+        }
+
+        fn seek_to_pcm_frame(&mut self, frame_index: u64) -> Result<(), Error> {
+            if frame_index > self.length {
+                return Err(Error::InvalidArgs);
+            }
+            self.cursor = frame_index;
+            Ok(())
+        }
+
+        fn cursor(&self) -> Result<u64, Error> {
+            Ok(self.cursor)
+        }
+
+        fn length(&self) -> Result<u64, Error> {
+            Ok(self.length)
+        }
+    }
+
+    #[test]
+    fn custom_data_source_reads_until_the_end() {
+        let mut source = CustomDataSource::new(Ramp::new(100));
+        let mut output = [0.0f32; 64];
+
+        let read = source
+            .read_pcm_frames(&mut FramesMut::wrap(&mut output, Format::F32, 1), false)
+            .unwrap();
+        assert_eq!(read, 64);
+        assert_eq!(output[63], 63.0);
+        assert_eq!(source.cursor().unwrap(), 64);
+
+        let read = source
+            .read_pcm_frames(&mut FramesMut::wrap(&mut output, Format::F32, 1), false)
+            .unwrap();
+        assert_eq!(read, 36);
+        assert_eq!(output[35], 99.0);
+
+        let read = source
+            .read_pcm_frames(&mut FramesMut::wrap(&mut output, Format::F32, 1), false)
+            .unwrap();
+        assert_eq!(read, 0);
+    }
+
+    #[test]
+    fn custom_data_source_loops_back_to_the_start() {
+        let mut source = CustomDataSource::new(Ramp::new(10));
+        let mut output = [0.0f32; 25];
+
+        let read = source
+            .read_pcm_frames(&mut FramesMut::wrap(&mut output, Format::F32, 1), true)
+            .unwrap();
+        assert_eq!(read, 25);
+        for (index, &sample) in output.iter().enumerate() {
+            assert_eq!(sample, (index % 10) as f32);
+        }
+
+        source.seek_to_pcm_frame(4).unwrap();
+        assert_eq!(source.get_ref().cursor, 4);
+        assert!(source.seek_to_pcm_frame(11).is_err());
+    }
+
+    #[test]
+    fn custom_data_source_reports_panics_as_errors() {
+        let mut ramp = Ramp::new(10);
+        ramp.panics = true;
+        let mut source = CustomDataSource::new(ramp);
+
+        let mut output = [0.0f32; 10];
+        assert!(matches!(
+            source.read_pcm_frames(&mut FramesMut::wrap(&mut output, Format::F32, 1), false),
+            Err(Error::Generic)
+        ));
+    }
+}
@@ -1,5 +1,7 @@
 use crate::base::*;
+use crate::frames::{bytes_per_frame, Frames, FramesBuffer};
 use crate::lock::{RwLockReadGuard, RwLockWriteGuard, SpinRwLock};
+use crate::resampling::{ResampleAlgorithm, Resampler, ResamplerConfig};
 use crate::{Error, Format, FramesMut};
 use miniaudio_sys as sys;
 use std::ffi::CString;
@@ -22,6 +24,41 @@ impl DecoderConfig {
     }
 }
 
+/// Technical properties of a decoded stream.
+///
+/// miniaudio does not read container tags like title or artist so only the properties of the
+/// audio itself are available.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecoderMetadata {
+    /// The total length of the stream in frames, or 0 if it is not known.
+    pub length_in_pcm_frames: u64,
+    /// The format that the decoding backend produces samples in. This is not necessarily the bit
+    /// depth that the file was encoded with since some backends always decode to the same format.
+    pub format: Format,
+    pub channels: u32,
+    pub sample_rate: u32,
+}
+
+impl DecoderMetadata {
+    /// The number of bits per sample of `format`.
+    #[inline]
+    pub fn bits_per_sample(&self) -> u32 {
+        self.format.size_in_bytes() as u32 * 8
+    }
+
+    /// The duration of the stream, or `None` if the length is not known.
+    pub fn duration(&self) -> Option<std::time::Duration> {
+        if self.length_in_pcm_frames == 0 || self.sample_rate == 0 {
+            return None;
+        }
+
+        Some(crate::frames::frames_to_duration(
+            self.length_in_pcm_frames,
+            self.sample_rate,
+        ))
+    }
+}
+
 #[repr(transparent)]
 pub struct RawDecoder {
     inner: sys::ma_decoder,
@@ -30,6 +67,8 @@ pub struct RawDecoder {
 impl RawDecoder {
     #[inline]
     pub fn read_pcm_frames(&mut self, output: &mut FramesMut) -> u64 {
+        ma_trace_span!("Decoder::read_pcm_frames", frames = output.frame_count());
+
         assert!(
             output.format() == self.output_format(),
             "output and decoder format did not match (output: {:?}, input: {:?}",
@@ -53,11 +92,25 @@ impl RawDecoder {
 
     #[inline]
     pub fn seek_to_pcm_frame(&mut self, frame_index: u64) -> Result<(), Error> {
+        ma_trace_span!("Decoder::seek_to_pcm_frame", frame_index);
+
         Error::from_c_result(unsafe {
             sys::ma_decoder_seek_to_pcm_frame(&self.inner as *const _ as *mut _, frame_index)
         })
     }
 
+    /// Returns the properties of the stream before it is converted to the output format.
+    pub fn metadata(&self) -> DecoderMetadata {
+        DecoderMetadata {
+            length_in_pcm_frames: unsafe {
+                sys::ma_decoder_get_length_in_pcm_frames(&self.inner as *const _ as *mut _)
+            },
+            format: Format::from_c(self.inner.internalFormat),
+            channels: self.inner.internalChannels,
+            sample_rate: self.inner.internalSampleRate,
+        }
+    }
+
     pub fn output_format(&self) -> Format {
         Format::from_c(self.inner.outputFormat)
     }
@@ -187,6 +240,11 @@ impl SyncDecoder {
         self.inner.write().length_in_pcm_frames()
     }
 
+    #[inline]
+    pub fn metadata(&self) -> DecoderMetadata {
+        self.inner.write().metadata()
+    }
+
     #[inline]
     pub fn output_format(&self) -> Format {
         self.inner.read().output_format()
@@ -398,3 +456,253 @@ impl Drop for Decoder {
 
 unsafe impl Send for Decoder {}
 unsafe impl Sync for Decoder {}
+
+/// The number of frames that `ResampledDecoder` decodes at a time.
+const RESAMPLED_DECODER_CHUNK_FRAMES: usize = 1024;
+
+/// A decoder that is read at a fixed sample rate, e.g. to play a file through a device at the
+/// device's rate.
+///
+/// The decoder is resampled with a `Resampler`, so its output format must be `u8`, `s16` or `f32`.
+/// Use `from_file` to have the decoder convert the format and channel count as well. At the end
+/// of the stream the frames that are still cached in the resampler are drained, so no audio is
+/// cut off.
+pub struct ResampledDecoder {
+    decoder: Decoder,
+    resampler: Resampler,
+    /// Decoded frames that haven't been consumed by the resampler yet.
+    decoded: FramesBuffer,
+    decoded_start: usize,
+    decoded_end: usize,
+    decoder_at_end: bool,
+}
+
+impl ResampledDecoder {
+    /// Wraps `decoder` so that it is read at `sample_rate` with its own format and channel
+    /// count.
+    pub fn new(
+        decoder: Decoder,
+        sample_rate: u32,
+        algorithm: ResampleAlgorithm,
+    ) -> Result<ResampledDecoder, Error> {
+        let format = decoder.output_format();
+        let channels = decoder.output_channels();
+
+        let mut config = ResamplerConfig::new(
+            format,
+            channels,
+            decoder.output_sample_rate(),
+            sample_rate,
+            algorithm.algorithm_type(),
+        );
+        config.set_algorithm(algorithm);
+
+        Ok(ResampledDecoder {
+            resampler: Resampler::new(&config)?,
+            decoded: FramesBuffer::new(format, channels, RESAMPLED_DECODER_CHUNK_FRAMES),
+            decoded_start: 0,
+            decoded_end: 0,
+            decoder_at_end: false,
+            decoder,
+        })
+    }
+
+    /// Opens a file that is read with `format`, `channels` and `sample_rate`, e.g. the ones of
+    /// the frames that a device asks for. The decoder converts the format and channel count and
+    /// the resampler converts the sample rate.
+    pub fn from_file<P: AsRef<Path>>(
+        file: P,
+        format: Format,
+        channels: u32,
+        sample_rate: u32,
+        algorithm: ResampleAlgorithm,
+    ) -> Result<ResampledDecoder, Error> {
+        // A sample rate of 0 keeps the rate of the file.
+        let config = DecoderConfig::new(format, channels, 0);
+        ResampledDecoder::new(
+            Decoder::from_file(file, Some(&config))?,
+            sample_rate,
+            algorithm,
+        )
+    }
+
+    /// Reads frames at the output sample rate into `output` and returns the number of frames that
+    /// were read. Fewer frames than fit into `output` are only read at the end of the stream, and
+    /// once the stream has ended this returns 0.
+    ///
+    /// `output` must have the format and channel count of the decoder.
+    pub fn read_pcm_frames(&mut self, output: &mut FramesMut) -> Result<u64, Error> {
+        let format = self.decoded.format();
+        let channels = self.decoded.channels();
+        if output.format() != format || output.channels() != channels {
+            ma_debug_panic!(
+                "output did not match decoder (output: {:?}/{}, decoder: {:?}/{})",
+                output.format(),
+                output.channels(),
+                format,
+                channels
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        let bytes_per_frame = bytes_per_frame(format, channels);
+        let output_frames = output.frame_count();
+        let mut produced = 0;
+        while produced < output_frames {
+            let mut rest = FramesMut::wrap::<u8>(
+                &mut output.as_bytes_mut()[(produced * bytes_per_frame)..],
+                format,
+                channels,
+            );
+
+            if self.decoded_start == self.decoded_end {
+                if self.decoder_at_end {
+                    let (written, _drained) = self.resampler.finish(&mut rest)?;
+                    produced += written as usize;
+                    break;
+                }
+
+                let read = self.decoder.read_pcm_frames(&mut self.decoded.frames_mut()) as usize;
+                self.decoded_start = 0;
+                self.decoded_end = read;
+                self.decoder_at_end = read == 0;
+                continue;
+            }
+
+            let input = Frames::wrap::<u8>(
+                &self.decoded.as_bytes()
+                    [(self.decoded_start * bytes_per_frame)..(self.decoded_end * bytes_per_frame)],
+                format,
+                channels,
+            );
+            let (consumed, written) = self.resampler.process_pcm_frames(&mut rest, &input)?;
+            if consumed == 0 && written == 0 {
+                break;
+            }
+            self.decoded_start += consumed as usize;
+            produced += written as usize;
+        }
+
+        Ok(produced as u64)
+    }
+
+    /// Returns true once the decoder has reached the end of the stream. There can still be
+    /// frames left to read from the resampler.
+    #[inline]
+    pub fn decoder_at_end(&self) -> bool {
+        self.decoder_at_end
+    }
+
+    /// The sample rate of the frames that are read.
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.resampler.config().sample_rate_out()
+    }
+
+    #[inline]
+    pub fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    #[inline]
+    pub fn resampler(&self) -> &Resampler {
+        &self.resampler
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 16-bit WAV file with the given interleaved samples.
+    fn wav_bytes(samples: &[i16], channels: u16, sample_rate: u32) -> Vec<u8> {
+        let block_align = channels * 2;
+        let data_size = samples.len() as u32 * 2;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&channels.to_le_bytes());
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        wav.extend_from_slice(&block_align.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_size.to_le_bytes());
+        for sample in samples {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+        wav
+    }
+
+    #[test]
+    fn resampled_decoder_reads_the_whole_stream_and_its_tail() {
+        let samples: Vec<i16> = (0..4410)
+            .map(|i| ((i % 101) * 300 - 15000) as i16)
+            .collect();
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 4,
+            lpf_nyquist_factor: 1.0,
+        };
+
+        let decoder = Decoder::from_memory(wav_bytes(&samples, 1, 44100), None).unwrap();
+        let mut decoder = ResampledDecoder::new(decoder, 48000, linear).unwrap();
+        assert_eq!(decoder.sample_rate(), 48000);
+
+        let mut output = Vec::new();
+        let mut chunk = [0i16; 100];
+        loop {
+            let read = decoder
+                .read_pcm_frames(&mut FramesMut::wrap(&mut chunk, Format::S16, 1))
+                .unwrap() as usize;
+            output.extend_from_slice(&chunk[..read]);
+            if read < chunk.len() {
+                break;
+            }
+        }
+        assert!(decoder.decoder_at_end());
+        assert_eq!(
+            decoder
+                .read_pcm_frames(&mut FramesMut::wrap(&mut chunk, Format::S16, 1))
+                .unwrap(),
+            0
+        );
+
+        // Resampling the samples all at once, tail included, gives the same output.
+        let mut config =
+            ResamplerConfig::new(Format::S16, 1, 44100, 48000, linear.algorithm_type());
+        config.set_algorithm(linear);
+        let expected = Resampler::new(&config)
+            .unwrap()
+            .resample_buffer(&Frames::wrap(&samples, Format::S16, 1), || true)
+            .unwrap();
+        assert_eq!(&output[..], expected.frames().as_samples::<i16>());
+    }
+
+    #[test]
+    fn metadata_describes_the_stream_before_conversion() {
+        let samples = vec![0i16; 1000 * 2];
+        let config = DecoderConfig::new(Format::F32, 1, 48000);
+        let decoder = Decoder::from_memory(wav_bytes(&samples, 2, 22050), Some(&config)).unwrap();
+        assert_eq!(decoder.output_format(), Format::F32);
+        assert_eq!(decoder.output_channels(), 1);
+
+        let metadata = decoder.metadata();
+        assert_eq!(
+            metadata,
+            DecoderMetadata {
+                length_in_pcm_frames: 1000,
+                format: Format::S16,
+                channels: 2,
+                sample_rate: 22050,
+            }
+        );
+        assert_eq!(metadata.bits_per_sample(), 16);
+        assert_eq!(
+            metadata.duration(),
+            Some(crate::frames::frames_to_duration(1000, 22050))
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crate::base::*;
-use crate::frames::{Frames, FramesMut};
+use crate::effects::FadeCurve;
+use crate::frames::{bytes_per_frame, frames_to_duration, Frames, FramesBuffer, FramesMut};
 use crate::resampling::ResampleAlgorithm;
 use miniaudio_sys as sys;
 use std::ffi::{CStr, CString, NulError};
@@ -9,6 +10,7 @@ use std::os::raw::c_void;
 use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::ptr;
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
 
 type MADeviceConfigPlayback = sys::ma_device_config__bindgen_ty_2;
@@ -318,8 +320,18 @@ impl DeviceConfig {
         }
     }
 
+    /// Sets the resampling algorithm. `ResampleAlgorithm::Sinc` is not implemented by miniaudio so
+    /// it can't be used here and is ignored. Use `try_set_resampling` to find out whether the
+    /// algorithm was accepted.
     #[inline]
     pub fn set_resampling(&mut self, algo: ResampleAlgorithm) {
+        let _ = self.try_set_resampling(algo);
+    }
+
+    /// Sets the resampling algorithm, returning `Error::InvalidArgs` and leaving the config
+    /// unchanged for `ResampleAlgorithm::Sinc`, which is not implemented by miniaudio.
+    #[inline]
+    pub fn try_set_resampling(&mut self, algo: ResampleAlgorithm) -> Result<(), Error> {
         match algo {
             ResampleAlgorithm::Linear {
                 lpf_order,
@@ -334,7 +346,14 @@ impl DeviceConfig {
                 self.0.resampling.algorithm = sys::ma_resample_algorithm_speex;
                 self.0.resampling.speex.quality = quality as _;
             }
+
+            ResampleAlgorithm::Sinc { .. } => {
+                ma_debug_panic!("the sinc resampler can't be used by a device");
+                return Err(Error::InvalidArgs);
+            }
         }
+
+        Ok(())
     }
 
     /// Sets the data callback for this device config.
@@ -369,12 +388,63 @@ impl DeviceConfig {
         }
     }
 
+    /// Returns the length of the gain ramp that is applied to the output when the device is
+    /// started.
+    pub fn start_fade_ms(&self) -> u32 {
+        self.user_data().map(|u| u.start_fade_ms).unwrap_or(0)
+    }
+
+    /// Makes the device fade its output in over `ms` milliseconds every time it is started, so
+    /// that playback doesn't start with a click. The gain ramp is applied to the frames written by
+    /// the data callback. 0 disables it, which is the default.
+    pub fn set_start_fade_ms(&mut self, ms: u32) {
+        let user_data = self.ensure_user_data();
+        unsafe { (*user_data).start_fade_ms = ms };
+    }
+
+    /// Returns the length of the gain ramp that is applied to the output when the device is
+    /// stopped.
+    pub fn stop_fade_ms(&self) -> u32 {
+        self.user_data().map(|u| u.stop_fade_ms).unwrap_or(0)
+    }
+
+    /// Makes the device fade its output out over `ms` milliseconds before it is stopped with
+    /// `Device::stop`, so that playback doesn't end with a click. `Device::stop` blocks until the
+    /// fade is done, so this also adds up to `ms` milliseconds and a period to it. 0 disables it,
+    /// which is the default.
+    pub fn set_stop_fade_ms(&mut self, ms: u32) {
+        let user_data = self.ensure_user_data();
+        unsafe { (*user_data).stop_fade_ms = ms };
+    }
+
+    /// Returns the curve of the gain ramps that are applied when the device is started and
+    /// stopped.
+    pub fn fade_curve(&self) -> FadeCurve {
+        self.user_data()
+            .map(|u| u.fade_curve)
+            .unwrap_or(FadeCurve::Linear)
+    }
+
+    /// Sets the curve of the start and stop fades. The stop fade runs the curve backwards. The
+    /// default is `FadeCurve::Linear`.
+    pub fn set_fade_curve(&mut self, curve: FadeCurve) {
+        let user_data = self.ensure_user_data();
+        unsafe { (*user_data).fade_curve = curve };
+    }
+
+    fn user_data(&self) -> Option<&DeviceConfigUserData> {
+        unsafe { self.0.pUserData.cast::<DeviceConfigUserData>().as_ref() }
+    }
+
     /// This will ensure that user data is initialized and return an unsafe mutable pointer to it.
     fn ensure_user_data(&mut self) -> *mut DeviceConfigUserData {
         if self.0.pUserData.is_null() {
             self.0.pUserData = Box::into_raw(Box::new(DeviceConfigUserData {
                 data_callback_factory: None,
                 stop_callback_factory: None,
+                start_fade_ms: 0,
+                stop_fade_ms: 0,
+                fade_curve: FadeCurve::Linear,
             })) as *mut _;
         }
         self.0.pUserData.cast()
@@ -394,10 +464,17 @@ pub type DataCallback = dyn FnMut(&RawDevice, &mut FramesMut, &Frames);
 pub type StopCallback = dyn FnMut(&RawDevice);
 pub type BoxedDataCallback = Box<DataCallback>;
 pub type BoxedStopCallback = Box<StopCallback>;
+pub type PostProcessCallback = dyn FnMut(&mut FramesMut);
+pub type BoxedPostProcessCallback = Box<PostProcessCallback>;
+pub type RerouteCallback = dyn FnMut(u32, u32);
+pub type BoxedRerouteCallback = Box<RerouteCallback>;
 
 pub struct DeviceConfigUserData {
     data_callback_factory: Option<Box<dyn Fn() -> BoxedDataCallback>>,
     stop_callback_factory: Option<Box<dyn Fn() -> BoxedStopCallback>>,
+    start_fade_ms: u32,
+    stop_fade_ms: u32,
+    fade_curve: FadeCurve,
 }
 
 // FIXME it might be better to just set the callbacks to some noop functions by default
@@ -405,6 +482,141 @@ pub struct DeviceConfigUserData {
 pub struct DeviceUserData {
     data_callback: MaybePoisoned<Option<BoxedDataCallback>>,
     stop_callback: MaybePoisoned<Option<BoxedStopCallback>>,
+    post_process: Option<BoxedPostProcessCallback>,
+    reroute: Option<RerouteWatch>,
+    fade: OutputFade,
+    reinit: ReinitConfig,
+    /// Set by the audio thread when the data callback is poisoned, so that `Device::stop` can
+    /// check it without racing with the audio thread.
+    data_callback_poisoned: AtomicBool,
+}
+
+/// The config a device was opened with, kept around so that `Device::switch_to` can open another
+/// device with exactly the same settings.
+struct ReinitConfig {
+    /// The config without its device IDs and user data, which are kept separately below.
+    config: sys::ma_device_config,
+    playback_id: Option<DeviceId>,
+    capture_id: Option<DeviceId>,
+    start_fade_ms: u32,
+    stop_fade_ms: u32,
+    fade_curve: FadeCurve,
+}
+
+impl ReinitConfig {
+    fn new(config: &DeviceConfig) -> ReinitConfig {
+        let mut raw = config.0;
+        raw.playback.pDeviceID = ptr::null_mut();
+        raw.capture.pDeviceID = ptr::null_mut();
+        raw.pUserData = ptr::null_mut();
+
+        ReinitConfig {
+            config: raw,
+            playback_id: config.playback().device_id().cloned(),
+            capture_id: config.capture().device_id().cloned(),
+            start_fade_ms: config.start_fade_ms(),
+            stop_fade_ms: config.stop_fade_ms(),
+            fade_curve: config.fade_curve(),
+        }
+    }
+
+    /// Creates a config that will initialize a device with the same settings as the original one.
+    fn to_device_config(&self) -> DeviceConfig {
+        let mut config = DeviceConfig(self.config);
+        config
+            .playback_mut()
+            .set_device_id(self.playback_id.clone());
+        config.capture_mut().set_device_id(self.capture_id.clone());
+        config.set_start_fade_ms(self.start_fade_ms);
+        config.set_stop_fade_ms(self.stop_fade_ms);
+        config.set_fade_curve(self.fade_curve);
+        config
+    }
+}
+
+/// Calls a callback when the sample rate or channel count of a device changes while it is
+/// running. miniaudio 0.10 has no notification for this, so it is checked before every period.
+struct RerouteWatch {
+    callback: BoxedRerouteCallback,
+    sample_rate: u32,
+    channels: u32,
+}
+
+/// How much longer than the stop fade `Device::stop` waits at most, on top of twice the length of
+/// the fade, for the data callback to finish it.
+const MAX_STOP_FADE_WAIT_MARGIN: std::time::Duration = std::time::Duration::from_millis(200);
+
+const FADE_RUNNING: u8 = 0;
+const FADE_STOPPING: u8 = 1;
+const FADE_SILENT: u8 = 2;
+
+/// The gain ramps that are applied to the output of a device when it is started and stopped.
+///
+/// The state is changed by `Device::start` and `Device::stop` and read by the audio thread, so it
+/// is kept in atomics.
+struct OutputFade {
+    start_frames: u64,
+    stop_frames: u64,
+    curve: FadeCurve,
+    /// The number of frames that were output since the device was started, or since it started
+    /// fading out.
+    position: AtomicU64,
+    state: AtomicU8,
+}
+
+impl OutputFade {
+    fn new(start_frames: u64, stop_frames: u64, curve: FadeCurve) -> OutputFade {
+        OutputFade {
+            start_frames,
+            stop_frames,
+            curve,
+            position: AtomicU64::new(0),
+            state: AtomicU8::new(FADE_RUNNING),
+        }
+    }
+
+    /// Applies the current ramp to the output of one call of the data callback.
+    fn apply(&self, output: &mut FramesMut) {
+        let frame_count = output.frame_count();
+        if frame_count == 0 {
+            return;
+        }
+
+        let position = self.position.load(Ordering::Acquire);
+        match self.state.load(Ordering::Acquire) {
+            FADE_RUNNING => {
+                if position >= self.start_frames {
+                    return;
+                }
+
+                let fade_frames = ((self.start_frames - position) as usize).min(frame_count);
+                for frame in 0..fade_frames {
+                    let t = (position + frame as u64) as f32 / self.start_frames as f32;
+                    output.apply_gain_to_range(frame, 1, self.curve.gain_at(t));
+                }
+                self.position
+                    .store(position + frame_count as u64, Ordering::Release);
+            }
+
+            FADE_STOPPING => {
+                let remaining = self.stop_frames.saturating_sub(position) as usize;
+                let fade_frames = remaining.min(frame_count);
+                for frame in 0..fade_frames {
+                    let t = (position + frame as u64) as f32 / self.stop_frames as f32;
+                    output.apply_gain_to_range(frame, 1, self.curve.gain_at(1.0 - t));
+                }
+                output.apply_gain_to_range(fade_frames, frame_count - fade_frames, 0.0);
+
+                self.position
+                    .store(position + frame_count as u64, Ordering::Release);
+                if remaining <= frame_count {
+                    self.state.store(FADE_SILENT, Ordering::Release);
+                }
+            }
+
+            _ => output.apply_gain_to_range(0, frame_count, 0.0),
+        }
+    }
 }
 
 unsafe extern "C" fn device_data_callback_trampoline(
@@ -423,7 +635,7 @@ unsafe extern "C" fn device_data_callback_trampoline(
         let mut output = if output_ptr.is_null() {
             FramesMut::wrap::<u8>(&mut empty_output, output_format, output_channels)
         } else {
-            let bytes_per_frame = output_format.size_in_bytes() * output_channels as usize;
+            let bytes_per_frame = bytes_per_frame(output_format, output_channels);
             FramesMut::wrap::<u8>(
                 std::slice::from_raw_parts_mut(
                     output_ptr.cast(),
@@ -439,7 +651,7 @@ unsafe extern "C" fn device_data_callback_trampoline(
         let input = if input_ptr.is_null() {
             Frames::wrap::<u8>(&empty_input, output_format, output_channels)
         } else {
-            let bytes_per_frame = input_format.size_in_bytes() * input_channels as usize;
+            let bytes_per_frame = bytes_per_frame(input_format, input_channels);
             Frames::wrap::<u8>(
                 std::slice::from_raw_parts(
                     input_ptr.cast(),
@@ -459,14 +671,51 @@ unsafe extern "C" fn device_data_callback_trampoline(
         // propagating the panic as soon as I get the change on the main thread by poisoning the
         // device. Not sure if this is a good strategy though.
         let mut maybe_poison = Ok(());
-        if let MaybePoisoned::CanUse(Some(ref mut data_callback)) = (*user_data).data_callback {
-            maybe_poison = catch_unwind(AssertUnwindSafe(|| {
-                (data_callback)(device.as_ref(), &mut output, &input);
-            }));
+        if let Some(ref mut reroute) = (*user_data).reroute {
+            let (sample_rate, channels) = if output_ptr.is_null() {
+                device.as_ref().capture().internal_route()
+            } else {
+                device.as_ref().playback().internal_route()
+            };
+
+            if (sample_rate, channels) != (reroute.sample_rate, reroute.channels) {
+                reroute.sample_rate = sample_rate;
+                reroute.channels = channels;
+                maybe_poison = catch_unwind(AssertUnwindSafe(|| {
+                    (reroute.callback)(sample_rate, channels);
+                }));
+                if maybe_poison.is_err() {
+                    (*user_data).reroute = None;
+                }
+            }
+        }
+
+        if maybe_poison.is_ok() {
+            if let MaybePoisoned::CanUse(Some(ref mut data_callback)) = (*user_data).data_callback {
+                maybe_poison = catch_unwind(AssertUnwindSafe(|| {
+                    (data_callback)(device.as_ref(), &mut output, &input);
+                }));
+            }
+        }
+
+        (*user_data).fade.apply(&mut output);
+
+        if maybe_poison.is_ok() && output.frame_count() > 0 {
+            if let Some(ref mut post_process) = (*user_data).post_process {
+                maybe_poison = catch_unwind(AssertUnwindSafe(|| {
+                    (post_process)(&mut output);
+                }));
+                if maybe_poison.is_err() {
+                    (*user_data).post_process = None;
+                }
+            }
         }
 
         if let Err(data_callback_poison) = maybe_poison {
             (*user_data).data_callback = MaybePoisoned::Poison(data_callback_poison);
+            (*user_data)
+                .data_callback_poisoned
+                .store(true, Ordering::Release);
             if let Err(stop_callback_poison) =
                 catch_unwind(|| panic!("a panic occurred in the data callback"))
             {
@@ -499,6 +748,9 @@ unsafe extern "C" fn device_stop_callback_trampoline(device_ptr: *mut sys::ma_de
                 catch_unwind(|| panic!("a panic occurred in the stop callback"))
             {
                 (*user_data).data_callback = MaybePoisoned::Poison(data_callback_poison);
+                (*user_data)
+                    .data_callback_poisoned
+                    .store(true, Ordering::Release);
             };
         }
     }
@@ -679,7 +931,15 @@ impl ContextConfig {
     /// Initializes a ContextConfig object.
     #[inline]
     pub fn new() -> ContextConfig {
-        ContextConfig(unsafe { sys::ma_context_config_init() })
+        #[allow(unused_mut)]
+        let mut config = ContextConfig(unsafe { sys::ma_context_config_init() });
+
+        #[cfg(feature = "tracing")]
+        {
+            config.0.logCallback = Some(context_log_callback_tracing);
+        }
+
+        config
     }
 
     #[inline]
@@ -729,6 +989,27 @@ impl ContextConfig {
     }
 }
 
+/// Forwards miniaudio's log messages to `tracing`.
+#[cfg(feature = "tracing")]
+unsafe extern "C" fn context_log_callback_tracing(
+    _context: *mut sys::ma_context,
+    _device: *mut sys::ma_device,
+    log_level: u32,
+    message: *const std::os::raw::c_char,
+) {
+    if message.is_null() {
+        return;
+    }
+
+    let message = CStr::from_ptr(message).to_string_lossy();
+    match log_level {
+        sys::MA_LOG_LEVEL_ERROR => tracing::event!(tracing::Level::ERROR, "{}", message),
+        sys::MA_LOG_LEVEL_WARNING => tracing::event!(tracing::Level::WARN, "{}", message),
+        sys::MA_LOG_LEVEL_INFO => tracing::event!(tracing::Level::INFO, "{}", message),
+        _ => tracing::event!(tracing::Level::DEBUG, "{}", message),
+    }
+}
+
 impl Default for ContextConfig {
     fn default() -> Self {
         Self::new()
@@ -922,6 +1203,12 @@ impl RawContext {
             ptr::null()
         };
 
+        // Contexts always need a config to forward log messages.
+        #[cfg(feature = "tracing")]
+        let default_config = ContextConfig::new();
+        #[cfg(feature = "tracing")]
+        let config = config.or(Some(&default_config));
+
         let result = unsafe {
             sys::ma_context_init(
                 backends_ptr,
@@ -975,6 +1262,39 @@ impl RawContext {
         })
     }
 
+    /// Returns true if the device with the given ID reports that it natively supports the given
+    /// format, channel count and sample rate.
+    ///
+    /// This is a best effort check based on the information returned by `get_device_info`. Some
+    /// backends don't report their supported formats or only report a range that the device may
+    /// not actually accept, and when the backend reports nothing for a property that property is
+    /// assumed to be supported. A device that reports a configuration as unsupported can usually
+    /// still be opened with it because miniaudio converts to and from the device's native format,
+    /// so this is mainly useful for picking a configuration that avoids conversion. This also
+    /// returns false if the device info could not be retrieved at all.
+    pub fn is_format_supported(
+        &self,
+        device_type: DeviceType,
+        device_id: &DeviceId,
+        format: Format,
+        channels: u32,
+        sample_rate: u32,
+        share_mode: ShareMode,
+    ) -> bool {
+        let info = match self.get_device_info(device_type, device_id, share_mode) {
+            Ok(info) => info,
+            Err(_) => return false,
+        };
+
+        let format_supported = info.formats().is_empty() || info.formats().contains(&format);
+        let channels_supported = info.max_channels() == 0
+            || (info.min_channels()..=info.max_channels()).contains(&channels);
+        let sample_rate_supported = info.max_sample_rate() == 0
+            || (info.min_sample_rate()..=info.max_sample_rate()).contains(&sample_rate);
+
+        format_supported && channels_supported && sample_rate_supported
+    }
+
     pub fn backend(&self) -> Backend {
         Backend::from_c(self.0.backend)
     }
@@ -1212,12 +1532,18 @@ impl RawDevice {
             )
         };
 
-        unsafe { (*(Arc::deref(&device).as_ptr() as *mut RawDevice)).create_device_user_data() };
+        unsafe {
+            (*(Arc::deref(&device).as_ptr() as *mut RawDevice)).create_device_user_data(config)
+        };
 
         map_result!(result, unsafe { std::mem::transmute(device) })
     }
 
-    fn create_device_user_data(&mut self) {
+    fn create_device_user_data(&mut self, config: &DeviceConfig) {
+        let reinit = ReinitConfig::new(config);
+        let sample_rate = self.0.sampleRate as u64;
+        let fade_frames = |ms: u32| ms as u64 * sample_rate / 1000;
+
         if !self.0.pUserData.is_null() {
             let config_user_data = self.0.pUserData.cast::<DeviceConfigUserData>();
 
@@ -1234,14 +1560,32 @@ impl RawDevice {
                     .map(|f| (f)())
             });
 
+            let fade = unsafe {
+                OutputFade::new(
+                    fade_frames((*config_user_data).start_fade_ms),
+                    fade_frames((*config_user_data).stop_fade_ms),
+                    (*config_user_data).fade_curve,
+                )
+            };
+
             self.0.pUserData = Box::into_raw(Box::new(DeviceUserData {
                 data_callback,
                 stop_callback,
+                post_process: None,
+                reroute: None,
+                fade,
+                reinit,
+                data_callback_poisoned: AtomicBool::new(false),
             })) as *mut _;
         } else {
             self.0.pUserData = Box::into_raw(Box::new(DeviceUserData {
                 data_callback: MaybePoisoned::CanUse(None),
                 stop_callback: MaybePoisoned::CanUse(None),
+                post_process: None,
+                reroute: None,
+                fade: OutputFade::new(0, 0, FadeCurve::Linear),
+                reinit,
+                data_callback_poisoned: AtomicBool::new(false),
             })) as *mut _;
         }
     }
@@ -1251,6 +1595,9 @@ impl RawDevice {
         let mut user_data =
             unsafe { Box::<DeviceUserData>::from_raw(self.0.pUserData as *mut DeviceUserData) };
         user_data.data_callback = MaybePoisoned::CanUse(callback);
+        user_data
+            .data_callback_poisoned
+            .store(false, Ordering::Release);
         std::mem::forget(user_data);
     }
 
@@ -1262,6 +1609,57 @@ impl RawDevice {
         std::mem::forget(user_data);
     }
 
+    fn set_raw_post_process(&mut self, callback: Option<BoxedPostProcessCallback>) {
+        assert!(!self.0.pUserData.is_null());
+        let mut user_data =
+            unsafe { Box::<DeviceUserData>::from_raw(self.0.pUserData as *mut DeviceUserData) };
+        user_data.post_process = callback;
+        std::mem::forget(user_data);
+    }
+
+    fn set_raw_reroute_callback(&mut self, callback: Option<BoxedRerouteCallback>) {
+        assert!(!self.0.pUserData.is_null());
+        let (sample_rate, channels) = match self.device_type() {
+            DeviceType::Capture => self.capture().internal_route(),
+            _ => self.playback().internal_route(),
+        };
+
+        let mut user_data =
+            unsafe { Box::<DeviceUserData>::from_raw(self.0.pUserData as *mut DeviceUserData) };
+        user_data.reroute = callback.map(|callback| RerouteWatch {
+            callback,
+            sample_rate,
+            channels,
+        });
+        std::mem::forget(user_data);
+    }
+
+    /// Returns a new reference to the context that was passed into this device via `new`, or
+    /// `None` if the device owns its context.
+    fn shared_context(&self) -> Option<Context> {
+        if self.is_owner_of_context() || self.0.pContext.is_null() {
+            return None;
+        }
+
+        // The device holds onto a reference created with `Arc::into_raw` in `alloc`, so we add
+        // another strong reference for the returned context.
+        unsafe {
+            let context_ptr = self.0.pContext as *const RawContext;
+            Arc::increment_strong_count(context_ptr);
+            Some(Context(Arc::from_raw(context_ptr)))
+        }
+    }
+
+    /// Creates a config that will initialize a device with the same settings as this one.
+    fn reinit_config(&self) -> DeviceConfig {
+        assert!(!self.0.pUserData.is_null());
+        unsafe {
+            (*self.0.pUserData.cast::<DeviceUserData>())
+                .reinit
+                .to_device_config()
+        }
+    }
+
     /// This will return the context **owned** by this device. A context that was passed into this
     /// device via `new` is **not** owned by this device and if you need a reference to that use
     /// `context` instead.
@@ -1305,6 +1703,8 @@ impl RawDevice {
     /// This should not be called from a callback.
     #[inline]
     pub unsafe fn raw_start(&self) -> Result<(), Error> {
+        ma_trace_span!("Device::start");
+
         Error::from_c_result(sys::ma_device_start(&self.0 as *const _ as *mut _))
     }
 
@@ -1315,6 +1715,8 @@ impl RawDevice {
     /// This should not be called from a callback.
     #[inline]
     pub unsafe fn raw_stop(&self) -> Result<(), Error> {
+        ma_trace_span!("Device::stop");
+
         Error::from_c_result(sys::ma_device_stop(&self.0 as *const _ as *mut _))
     }
 
@@ -1384,6 +1786,11 @@ impl RawDevice {
         from_bool32(self.0.isOwnerOfContext())
     }
 
+    #[inline]
+    pub fn device_type(&self) -> DeviceType {
+        DeviceType::from_c(self.0.type_)
+    }
+
     #[inline]
     pub fn sample_rate(&self) -> u32 {
         self.0.sampleRate
@@ -1405,6 +1812,47 @@ impl RawDevice {
         }
     }
 
+    /// Returns the latency of the backend's buffers in frames at the device's sample rate.
+    ///
+    /// This is the period size multiplied by the number of periods that the backend settled on,
+    /// which may be different from what was requested in the config. For playback devices it is
+    /// the delay between a frame being written in the data callback and it being heard, for
+    /// capture devices the delay between a frame being recorded and it being delivered to the
+    /// callback, and for duplex devices the sum of both (the round trip). It does not include any
+    /// latency added by the hardware itself or by resampling, which is reported separately by the
+    /// resampler.
+    pub fn current_latency_frames(&self) -> u64 {
+        let sample_rate = self.sample_rate() as u64;
+        let latency = |internal_sample_rate: u32, period_size: u32, periods: u32| {
+            let frames = period_size as u64 * periods as u64;
+            if internal_sample_rate == 0 {
+                frames
+            } else {
+                frames * sample_rate / internal_sample_rate as u64
+            }
+        };
+
+        let playback = self.playback();
+        let playback_latency = latency(
+            playback.internal_sample_rate(),
+            playback.internal_period_size_in_frames(),
+            playback.internal_periods(),
+        );
+
+        let capture = self.capture();
+        let capture_latency = latency(
+            capture.internal_sample_rate(),
+            capture.internal_period_size_in_frames(),
+            capture.internal_periods(),
+        );
+
+        match self.device_type() {
+            DeviceType::Playback => playback_latency,
+            DeviceType::Capture | DeviceType::Loopback => capture_latency,
+            DeviceType::Duplex => playback_latency + capture_latency,
+        }
+    }
+
     #[inline]
     pub fn capture(&self) -> &DeviceCapture {
         unsafe { &*(&self.0.capture as *const MADeviceCapture as *const DeviceCapture) }
@@ -1494,22 +1942,269 @@ impl Device {
             .set_raw_stop_callback(Some(Box::new(callback)));
     }
 
+    /// Sets a hook that runs on the output after the data callback has filled it, e.g. for a
+    /// master limiter or a loudness meter that would otherwise have to be added to every data
+    /// callback. The hook sees the frames that are passed on to the backend, including the start
+    /// and stop fades of the device, and can change them. It is not called for capture devices.
+    ///
+    /// A panic in the hook poisons the device like a panic in the data callback and removes the
+    /// hook.
+    ///
+    /// ### Panics
+    ///
+    /// * This will panic if it is called after the device has been started.
+    /// * This will also panic if there is more than one reference to the same device (if this has
+    ///   been cloned).
+    pub fn set_post_process<F>(&mut self, callback: F)
+    where
+        F: FnMut(&mut FramesMut) + Send + 'static,
+    {
+        if self.is_started() {
+            panic!("cannot set the post process hook after the device has been started");
+        }
+
+        Arc::get_mut(&mut self.0)
+            .expect(
+                "cannot set post process hook while there is more than one reference to a device",
+            )
+            .set_raw_post_process(Some(Box::new(callback)));
+    }
+
+    /// Sets a callback that is called with the new internal sample rate and channel count of the
+    /// device when they change while it is running, e.g. because the default output device
+    /// changed and miniaudio rerouted the stream to a device with a different native sample rate.
+    /// These are the properties the backend runs at. For properties that were left at their
+    /// defaults in the config the device follows them, so a resampler feeding the device can then
+    /// be updated with `Resampler::set_rate` to avoid pitch errors. miniaudio converts to the
+    /// properties that were requested explicitly. See the `playback-reroute-resampler` example.
+    ///
+    /// miniaudio 0.10 doesn't notify about reroutes, so the internal properties are compared
+    /// before every period instead and the callback is called from the audio thread right before
+    /// the data callback of the first period with the new properties. They are the ones of the
+    /// playback side, or of the capture side for capture devices.
+    ///
+    /// A panic in the callback poisons the device like a panic in the data callback and removes
+    /// the callback.
+    ///
+    /// ### Panics
+    ///
+    /// * This will panic if it is called after the device has been started.
+    /// * This will also panic if there is more than one reference to the same device (if this has
+    ///   been cloned).
+    pub fn on_reroute<F>(&mut self, callback: F)
+    where
+        F: FnMut(u32, u32) + Send + 'static,
+    {
+        if self.is_started() {
+            panic!("cannot set the reroute callback after the device has been started");
+        }
+
+        Arc::get_mut(&mut self.0)
+            .expect(
+                "cannot set reroute callback while there is more than one reference to a device",
+            )
+            .set_raw_reroute_callback(Some(Box::new(callback)));
+    }
+
     /// Starts the device. For playback devices this begins playback. For capture devices this
     /// begins recording.
     /// Use `stop` to stop this device.
     ///
     /// **WARNING** This should not be called from a callback.
     pub fn start(&self) -> Result<(), Error> {
+        if let Some(fade) = self.output_fade() {
+            fade.position.store(0, Ordering::Release);
+            fade.state.store(FADE_RUNNING, Ordering::Release);
+        }
         unsafe { self.0.raw_start() }
     }
 
     /// Stops this device. For playback devices this stops playback. For capture devices this stops
     /// recording. Use `start` to start this device again.
     ///
+    /// If the config had a stop fade this blocks until the output has faded out. The audio thread
+    /// is polled every millisecond for at most twice the length of the fade plus 200 milliseconds,
+    /// so that this can't hang if the backend stops calling back. There is no wait if the data
+    /// callback was poisoned by a panic, since the device only outputs silence then.
+    ///
     /// **WARNING** This should not be called from a callback.
     pub fn stop(&self) -> Result<(), Error> {
+        let has_playback = matches!(
+            DeviceType::from_c(self.0 .0.type_),
+            DeviceType::Playback | DeviceType::Duplex
+        );
+
+        if let Some(fade) = self.output_fade() {
+            if fade.stop_frames > 0
+                && has_playback
+                && self.is_started()
+                && !self.data_callback_poisoned()
+            {
+                fade.position.store(0, Ordering::Release);
+                fade.state.store(FADE_STOPPING, Ordering::Release);
+
+                // Give up waiting if the backend stops calling back, e.g. because the device was
+                // unplugged, so that this can't hang.
+                let fade_duration = frames_to_duration(fade.stop_frames, self.sample_rate());
+                let deadline =
+                    std::time::Instant::now() + fade_duration * 2 + MAX_STOP_FADE_WAIT_MARGIN;
+                while fade.state.load(Ordering::Acquire) != FADE_SILENT
+                    && std::time::Instant::now() < deadline
+                {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+            }
+        }
+
         unsafe { self.0.raw_stop() }
     }
+
+    fn data_callback_poisoned(&self) -> bool {
+        unsafe {
+            self.0
+                 .0
+                .pUserData
+                .cast::<DeviceUserData>()
+                .as_ref()
+                .map(|u| u.data_callback_poisoned.load(Ordering::Acquire))
+                .unwrap_or(false)
+        }
+    }
+
+    fn output_fade(&self) -> Option<&OutputFade> {
+        unsafe {
+            self.0
+                 .0
+                .pUserData
+                .cast::<DeviceUserData>()
+                .as_ref()
+                .map(|u| &u.fade)
+        }
+    }
+
+    /// Synchronously runs the data callback for exactly `frame_count` frames instead of waiting for
+    /// the backend to do it.
+    ///
+    /// This is meant for testing callback logic without sleeping and is only available for devices
+    /// on the null backend that are not started, otherwise `Error::InvalidOperation` is returned.
+    /// The callback is called from the current thread in chunks of at most one period with
+    /// silent input, and the output that it writes is discarded.
+    pub fn advance(&self, frame_count: u64) -> Result<(), Error> {
+        if self.context().backend() != Backend::Null || self.is_started() {
+            return Err(Error::InvalidOperation);
+        }
+
+        let device_type = DeviceType::from_c(self.0 .0.type_);
+        let has_playback = matches!(device_type, DeviceType::Playback | DeviceType::Duplex);
+        let has_capture = !matches!(device_type, DeviceType::Playback);
+
+        let period_size = if has_playback {
+            self.playback().internal_period_size_in_frames()
+        } else {
+            self.capture().internal_period_size_in_frames()
+        };
+        let chunk_frames = if period_size == 0 { 1024 } else { period_size };
+
+        let playback = self.playback();
+        let capture = self.capture();
+        let mut output = FramesBuffer::new(
+            playback.format(),
+            playback.channels(),
+            chunk_frames as usize,
+        );
+        let input = FramesBuffer::new(capture.format(), capture.channels(), chunk_frames as usize);
+
+        let device_ptr = &self.0 .0 as *const sys::ma_device as *mut sys::ma_device;
+        let mut remaining = frame_count;
+        while remaining > 0 {
+            let frames = remaining.min(chunk_frames as u64) as u32;
+            unsafe {
+                device_data_callback_trampoline(
+                    device_ptr,
+                    if has_playback {
+                        output.as_bytes_mut().as_mut_ptr().cast()
+                    } else {
+                        ptr::null_mut()
+                    },
+                    if has_capture {
+                        input.as_bytes().as_ptr().cast()
+                    } else {
+                        ptr::null()
+                    },
+                    frames,
+                );
+            }
+            remaining -= frames as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Moves this device over to the device with the given ID, keeping the config that the current
+    /// device was opened with and its callbacks.
+    ///
+    /// Playback and duplex devices switch their playback side to `id` and capture and loopback
+    /// devices switch their capture side. If the device was started it is stopped while switching
+    /// and started again afterwards.
+    ///
+    /// If the new device fails to open this device keeps using the old one (and is restarted if it
+    /// was started) and the error is returned.
+    ///
+    /// This returns `Error::InvalidOperation` if there is more than one reference to this device
+    /// (if it has been cloned).
+    ///
+    /// **WARNING** This should not be called from a callback.
+    pub fn switch_to(&mut self, id: &DeviceId) -> Result<(), Error> {
+        if Arc::get_mut(&mut self.0).is_none() {
+            return Err(Error::InvalidOperation);
+        }
+
+        let mut config = self.0.reinit_config();
+        match config.device_type() {
+            DeviceType::Playback | DeviceType::Duplex => {
+                config.playback_mut().set_device_id(Some(id.clone()))
+            }
+            DeviceType::Capture | DeviceType::Loopback => {
+                config.capture_mut().set_device_id(Some(id.clone()))
+            }
+        }
+
+        let was_started = self.is_started();
+        if was_started {
+            self.stop()?;
+        }
+
+        let mut new_device = match RawDevice::alloc(self.0.shared_context(), &config) {
+            Ok(new_device) => new_device,
+            Err(err) => {
+                if was_started {
+                    self.start()?;
+                }
+                return Err(err);
+            }
+        };
+
+        // Both devices are stopped so neither of their callbacks can be running and it's safe to
+        // move the callbacks over to the new device. The old device gets the new device's empty
+        // user data and drops it along with itself. The fade and the reinit config belong to the
+        // new device though (its sample rate may differ), so those are swapped back.
+        let raw = Arc::get_mut(&mut self.0).expect("device should not be shared");
+        let new_raw = Arc::get_mut(&mut new_device).expect("new device should not be shared");
+        std::mem::swap(&mut raw.0.pUserData, &mut new_raw.0.pUserData);
+        unsafe {
+            let old_user_data = &mut *raw.0.pUserData.cast::<DeviceUserData>();
+            let new_user_data = &mut *new_raw.0.pUserData.cast::<DeviceUserData>();
+            std::mem::swap(&mut old_user_data.fade, &mut new_user_data.fade);
+            std::mem::swap(&mut old_user_data.reinit, &mut new_user_data.reinit);
+        }
+
+        self.0 = new_device;
+        if was_started {
+            self.start()?;
+        }
+
+        Ok(())
+    }
 }
 
 impl std::ops::Deref for Device {
@@ -1561,6 +2256,27 @@ impl DeviceCapture {
                 as *const [Channel; MAX_CHANNELS])
         }
     }
+
+    /// The sample rate that the backend is actually running at.
+    pub fn internal_sample_rate(&self) -> u32 {
+        self.0.internalSampleRate
+    }
+
+    /// The size of a single backend period in frames at the internal sample rate.
+    pub fn internal_period_size_in_frames(&self) -> u32 {
+        self.0.internalPeriodSizeInFrames
+    }
+
+    /// The number of periods in the backend's buffer.
+    pub fn internal_periods(&self) -> u32 {
+        self.0.internalPeriods
+    }
+
+    /// The sample rate and channel count that the backend is running at, which is what changes
+    /// when miniaudio reroutes the stream.
+    fn internal_route(&self) -> (u32, u32) {
+        (self.0.internalSampleRate, self.0.internalChannels)
+    }
 }
 
 #[repr(transparent)]
@@ -1605,6 +2321,27 @@ impl DevicePlayback {
         }
     }
 
+    /// The sample rate that the backend is actually running at.
+    pub fn internal_sample_rate(&self) -> u32 {
+        self.0.internalSampleRate
+    }
+
+    /// The size of a single backend period in frames at the internal sample rate.
+    pub fn internal_period_size_in_frames(&self) -> u32 {
+        self.0.internalPeriodSizeInFrames
+    }
+
+    /// The number of periods in the backend's buffer.
+    pub fn internal_periods(&self) -> u32 {
+        self.0.internalPeriods
+    }
+
+    /// The sample rate and channel count that the backend is running at, which is what changes
+    /// when miniaudio reroutes the stream.
+    fn internal_route(&self) -> (u32, u32) {
+        (self.0.internalSampleRate, self.0.internalChannels)
+    }
+
     // FIXME I'm not sure if these are supposed to be public.
     //       If they are, they should be implemented in here as well as `DeviceCapture`.
 
@@ -1616,23 +2353,315 @@ impl DevicePlayback {
     //     self.0.internalChannels
     // }
 
-    // pub fn internal_sample_rate(&self) -> u32 {
-    //     self.0.internalSampleRate
-    // }
-
     // pub fn internal_channel_map(&self) -> &[Channel; MAX_CHANNELS] {
     //     unsafe { std::mem::transmute(&self.0.internalChannelMap) }
     // }
 
-    // pub fn internal_period_size_in_frames(&self) -> u32 {
-    //     self.0.internalPeriodSizeInFrames
-    // }
-
-    // pub fn internal_periods(&self) -> u32 {
-    //     self.0.internalPeriods
-    // }
-
     // pub fn converter(&self) -> &DataConverter {
     //     // FIXME implement this
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switch_to_keeps_config_of_started_duplex_device() {
+        let context = Context::new(&[Backend::Null], None).unwrap();
+        let mut playback_id = None;
+        context
+            .with_playback_devices(|devices| playback_id = Some(devices[0].id().clone()))
+            .unwrap();
+        let playback_id = playback_id.unwrap();
+
+        let mut config = DeviceConfig::new(DeviceType::Duplex);
+        config.set_sample_rate(44100);
+        config.set_period_size_in_frames(256);
+        config.set_periods(3);
+        config.set_no_clip(true);
+        config.set_stop_fade_ms(5);
+        config.set_fade_curve(FadeCurve::SCurve);
+        config.playback_mut().set_format(Format::F32);
+        config.playback_mut().set_channels(2);
+        config.capture_mut().set_format(Format::F32);
+        config.capture_mut().set_channels(1);
+
+        let frames = Arc::new(AtomicU64::new(0));
+        let mut device = Device::new(Some(context), &config).unwrap();
+        let callback_frames = Arc::clone(&frames);
+        device.set_data_callback(move |_, output, _| {
+            callback_frames.fetch_add(output.frame_count() as u64, Ordering::Relaxed);
+        });
+
+        // The null backend only has one playback device, so this switches to the device that is
+        // already open. It still has to be closed and opened again.
+        let old_raw = Arc::as_ptr(&device.0);
+        device.start().unwrap();
+        device.switch_to(&playback_id).unwrap();
+        assert!(!ptr::eq(Arc::as_ptr(&device.0), old_raw));
+        assert!(device.is_started());
+        device.stop().unwrap();
+
+        assert_eq!(device.sample_rate(), 44100);
+        assert_eq!(device.playback().format(), Format::F32);
+        assert_eq!(device.playback().channels(), 2);
+        assert_eq!(device.capture().channels(), 1);
+
+        let reinit = device.reinit_config();
+        assert_eq!(reinit.period_size_in_frames(), 256);
+        assert_eq!(reinit.periods(), 3);
+        assert!(reinit.no_clip());
+        assert_eq!(reinit.stop_fade_ms(), 5);
+        assert_eq!(reinit.fade_curve(), FadeCurve::SCurve);
+        assert!(reinit.playback().device_id().is_some());
+        assert!(reinit.capture().device_id().is_none());
+
+        // The callback moved over to the new device.
+        let before = frames.load(Ordering::Relaxed);
+        device.advance(100).unwrap();
+        assert_eq!(frames.load(Ordering::Relaxed), before + 100);
+    }
+
+    fn null_playback_config(start_fade_ms: u32, stop_fade_ms: u32) -> DeviceConfig {
+        let mut config = DeviceConfig::new(DeviceType::Playback);
+        config.set_sample_rate(48000);
+        config.set_start_fade_ms(start_fade_ms);
+        config.set_stop_fade_ms(stop_fade_ms);
+        config.playback_mut().set_format(Format::F32);
+        config.playback_mut().set_channels(1);
+        config
+    }
+
+    #[test]
+    fn start_fade_ramps_up_the_output() {
+        let context = Context::new(&[Backend::Null], None).unwrap();
+        let mut device = Device::new(Some(context), &null_playback_config(1, 0)).unwrap();
+        device.set_data_callback(|_, output, _| {
+            output
+                .as_samples_mut::<f32>()
+                .iter_mut()
+                .for_each(|s| *s = 1.0);
+        });
+
+        let output = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let post_process_output = Arc::clone(&output);
+        device.set_post_process(move |frames| {
+            let samples = frames.as_samples::<f32>().to_vec();
+            post_process_output.lock().unwrap().extend(samples);
+        });
+
+        // 1ms at 48kHz.
+        device.advance(96).unwrap();
+        let output = output.lock().unwrap();
+        assert_eq!(output.len(), 96);
+        assert_eq!(output[0], 0.0);
+        assert!(output[..48].windows(2).all(|w| w[0] < w[1]));
+        assert!(output[48..].iter().all(|&s| s == 1.0));
+    }
+
+    #[test]
+    fn output_fade_follows_its_curve() {
+        for &curve in &[FadeCurve::Linear, FadeCurve::EqualPower, FadeCurve::SCurve] {
+            let fade = OutputFade::new(8, 8, curve);
+            let mut samples = [1.0f32; 8];
+            fade.apply(&mut FramesMut::wrap(&mut samples, Format::F32, 1));
+            for (frame, &sample) in samples.iter().enumerate() {
+                assert_eq!(sample, curve.gain_at(frame as f32 / 8.0), "{:?}", curve);
+            }
+
+            fade.state.store(FADE_STOPPING, Ordering::Release);
+            fade.position.store(0, Ordering::Release);
+            let mut samples = [1.0f32; 8];
+            fade.apply(&mut FramesMut::wrap(&mut samples, Format::F32, 1));
+            for (frame, &sample) in samples.iter().enumerate() {
+                assert_eq!(
+                    sample,
+                    curve.gain_at(1.0 - frame as f32 / 8.0),
+                    "{:?}",
+                    curve
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stop_does_not_wait_for_the_fade_of_a_poisoned_device() {
+        let context = Context::new(&[Backend::Null], None).unwrap();
+        let mut device = Device::new(Some(context), &null_playback_config(0, 2000)).unwrap();
+        device.set_data_callback(|_, _, _| panic!("poisoning the data callback"));
+        device.advance(1).unwrap();
+
+        device.start().unwrap();
+        let stop_started = std::time::Instant::now();
+        device.stop().unwrap();
+        assert!(stop_started.elapsed() < std::time::Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn on_reroute_is_called_once_when_the_sample_rate_changes() {
+        let context = Context::new(&[Backend::Null], None).unwrap();
+        let mut device = Device::new(Some(context), &null_playback_config(0, 0)).unwrap();
+
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let reroute_calls = Arc::clone(&calls);
+        device.on_reroute(move |sample_rate, channels| {
+            reroute_calls.lock().unwrap().push((sample_rate, channels));
+        });
+        device.set_data_callback(|_, _, _| {});
+
+        device.advance(96).unwrap();
+        assert!(calls.lock().unwrap().is_empty());
+
+        // The sample rate that was requested stays the same, only the one the backend runs at
+        // changes when miniaudio reroutes the stream.
+        Arc::get_mut(&mut device.0)
+            .unwrap()
+            .0
+            .playback
+            .internalSampleRate = 44100;
+        device.advance(96).unwrap();
+        device.advance(96).unwrap();
+        assert_eq!(*calls.lock().unwrap(), [(44100, 1)]);
+
+        Arc::get_mut(&mut device.0)
+            .unwrap()
+            .0
+            .playback
+            .internalChannels = 2;
+        device.advance(96).unwrap();
+        assert_eq!(*calls.lock().unwrap(), [(44100, 1), (44100, 2)]);
+        assert_eq!(device.sample_rate(), 48000);
+    }
+
+    #[test]
+    fn a_panic_in_on_reroute_poisons_the_device() {
+        let context = Context::new(&[Backend::Null], None).unwrap();
+        let mut device = Device::new(Some(context), &null_playback_config(0, 0)).unwrap();
+        device.on_reroute(|_, _| panic!("poisoning the reroute callback"));
+        device.set_data_callback(|_, _, _| {});
+
+        Arc::get_mut(&mut device.0)
+            .unwrap()
+            .0
+            .playback
+            .internalSampleRate = 44100;
+        device.advance(96).unwrap();
+        assert!(device.data_callback_poisoned());
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "the sinc resampler can't be used by a device")
+    )]
+    fn try_set_resampling_rejects_sinc_and_keeps_the_config() {
+        let mut config = DeviceConfig::new(DeviceType::Playback);
+        config.set_resampling(ResampleAlgorithm::Speex { quality: 5 });
+        let result = config.try_set_resampling(ResampleAlgorithm::Sinc {
+            window_length: 32,
+            beta: 8.0,
+        });
+        assert!(matches!(result, Err(Error::InvalidArgs)));
+        assert!(matches!(
+            config.resampling(),
+            ResampleAlgorithm::Speex { quality: 5 }
+        ));
+    }
+
+    #[test]
+    fn switch_to_restarts_the_old_device_when_the_new_one_fails_to_open() {
+        let context = Context::new(&[Backend::Null], None).unwrap();
+        let mut playback_id = None;
+        context
+            .with_playback_devices(|devices| playback_id = Some(devices[0].id().clone()))
+            .unwrap();
+        let playback_id = playback_id.unwrap();
+
+        let frames = Arc::new(AtomicU64::new(0));
+        let mut device = Device::new(Some(context), &null_playback_config(0, 0)).unwrap();
+        let callback_frames = Arc::clone(&frames);
+        device.set_data_callback(move |_, output, _| {
+            callback_frames.fetch_add(output.frame_count() as u64, Ordering::Relaxed);
+        });
+
+        // The null backend opens any device ID, so make the new device fail by asking for a
+        // loopback device, which it doesn't support.
+        unsafe {
+            (*device.0 .0.pUserData.cast::<DeviceUserData>())
+                .reinit
+                .config
+                .deviceType = sys::ma_device_type_loopback;
+        }
+
+        let old_raw = Arc::as_ptr(&device.0);
+        device.start().unwrap();
+        let result = device.switch_to(&playback_id);
+        assert!(matches!(result, Err(Error::DeviceTypeNotSupported)));
+        assert!(ptr::eq(Arc::as_ptr(&device.0), old_raw));
+        assert!(device.is_started());
+        device.stop().unwrap();
+
+        // The callback is still set on the old device.
+        let before = frames.load(Ordering::Relaxed);
+        device.advance(100).unwrap();
+        assert_eq!(frames.load(Ordering::Relaxed), before + 100);
+    }
+
+    #[test]
+    fn current_latency_frames_is_the_size_of_the_backend_buffers() {
+        let context = Context::new(&[Backend::Null], None).unwrap();
+        let mut config = null_playback_config(0, 0);
+        config.set_period_size_in_frames(256);
+        config.set_periods(3);
+        let device = Device::new(Some(context.clone()), &config).unwrap();
+        assert_eq!(device.playback().internal_period_size_in_frames(), 256);
+        assert_eq!(device.playback().internal_periods(), 3);
+        assert_eq!(device.current_latency_frames(), 768);
+
+        // A duplex device has the latency of both sides.
+        let mut config = DeviceConfig::new(DeviceType::Duplex);
+        config.set_sample_rate(48000);
+        config.set_period_size_in_frames(128);
+        config.set_periods(2);
+        config.playback_mut().set_format(Format::F32);
+        config.capture_mut().set_format(Format::F32);
+        let device = Device::new(Some(context), &config).unwrap();
+        assert_eq!(device.current_latency_frames(), 512);
+    }
+
+    #[test]
+    fn is_format_supported_checks_the_null_device_info() {
+        let context = Context::new(&[Backend::Null], None).unwrap();
+        let mut id = None;
+        context
+            .with_playback_devices(|devices| id = Some(devices[0].id().clone()))
+            .unwrap();
+        let id = id.unwrap();
+        let supported = |id: &DeviceId, format, channels: u32, sample_rate| {
+            context.is_format_supported(
+                DeviceType::Playback,
+                id,
+                format,
+                channels,
+                sample_rate,
+                ShareMode::Shared,
+            )
+        };
+
+        // The null device supports every format, 1 to MAX_CHANNELS channels and 8kHz to
+        // 384kHz.
+        assert!(supported(&id, Format::F32, 2, 48000));
+        assert!(supported(&id, Format::S16, 1, 8000));
+        assert!(supported(&id, Format::U8, MAX_CHANNELS as u32, 384000));
+        assert!(!supported(&id, Format::F32, 2, 4000));
+        assert!(!supported(&id, Format::F32, 2, 768000));
+        assert!(!supported(&id, Format::F32, 0, 48000));
+        assert!(!supported(&id, Format::F32, MAX_CHANNELS as u32 + 1, 48000));
+
+        // The null backend doesn't know any other device, so its info can't be retrieved.
+        let mut unknown = id.clone();
+        unknown.0.nullbackend = 1;
+        assert!(!supported(&unknown, Format::F32, 2, 48000));
+    }
+}
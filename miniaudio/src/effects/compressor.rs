@@ -0,0 +1,307 @@
+use super::{flush_denormal, process_as_f32};
+use crate::base::{db_to_gain, gain_to_db, Error};
+use crate::frames::{Frames, FramesMut};
+
+/// A feed forward compressor with a soft knee.
+///
+/// The level of each frame is the peak level across all of its channels, so every channel gets
+/// the same amount of gain reduction and the stereo image is preserved. The gain reduction follows
+/// that level through an envelope follower using the attack time when the reduction is increasing
+/// and the release time when it is decreasing.
+pub struct Compressor {
+    channels: u32,
+    sample_rate: u32,
+    threshold_db: f32,
+    ratio: f32,
+    attack_ms: f32,
+    release_ms: f32,
+    knee_db: f32,
+    makeup_gain_db: f32,
+    flush_denormals: bool,
+    envelope_db: f32,
+    scratch: Vec<f32>,
+}
+
+impl Compressor {
+    pub fn new(channels: u32, sample_rate: u32) -> Compressor {
+        Compressor {
+            channels,
+            sample_rate,
+            threshold_db: -20.0,
+            ratio: 4.0,
+            attack_ms: 10.0,
+            release_ms: 100.0,
+            knee_db: 6.0,
+            makeup_gain_db: 0.0,
+            flush_denormals: true,
+            envelope_db: 0.0,
+            scratch: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn channels(&self) -> u32 {
+        self.channels
+    }
+
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[inline]
+    pub fn threshold_db(&self) -> f32 {
+        self.threshold_db
+    }
+
+    /// Sets the level in decibels above which the signal is compressed.
+    #[inline]
+    pub fn set_threshold_db(&mut self, threshold_db: f32) {
+        self.threshold_db = threshold_db;
+    }
+
+    #[inline]
+    pub fn ratio(&self) -> f32 {
+        self.ratio
+    }
+
+    /// Sets the compression ratio. A ratio of 4 means that every 4dB above the threshold will
+    /// come out as 1dB above the threshold. Ratios below 1 are clamped to 1 (no compression).
+    #[inline]
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio.max(1.0);
+    }
+
+    #[inline]
+    pub fn attack_ms(&self) -> f32 {
+        self.attack_ms
+    }
+
+    /// Sets the time in milliseconds that the compressor takes to react to a signal going over
+    /// the threshold.
+    #[inline]
+    pub fn set_attack_ms(&mut self, attack_ms: f32) {
+        self.attack_ms = attack_ms.max(0.0);
+    }
+
+    #[inline]
+    pub fn release_ms(&self) -> f32 {
+        self.release_ms
+    }
+
+    /// Sets the time in milliseconds that the compressor takes to recover after the signal goes
+    /// back under the threshold.
+    #[inline]
+    pub fn set_release_ms(&mut self, release_ms: f32) {
+        self.release_ms = release_ms.max(0.0);
+    }
+
+    #[inline]
+    pub fn knee_db(&self) -> f32 {
+        self.knee_db
+    }
+
+    /// Sets the width of the knee in decibels centered around the threshold. Within the knee the
+    /// ratio is gradually increased. A knee of 0 is a hard knee.
+    #[inline]
+    pub fn set_knee_db(&mut self, knee_db: f32) {
+        self.knee_db = knee_db.max(0.0);
+    }
+
+    #[inline]
+    pub fn makeup_gain_db(&self) -> f32 {
+        self.makeup_gain_db
+    }
+
+    /// Sets the gain in decibels that is applied after compression.
+    #[inline]
+    pub fn set_makeup_gain_db(&mut self, makeup_gain_db: f32) {
+        self.makeup_gain_db = makeup_gain_db;
+    }
+
+    #[inline]
+    pub fn flush_denormals(&self) -> bool {
+        self.flush_denormals
+    }
+
+    /// Sets whether the envelope is flushed to zero when it becomes denormal, which is on by
+    /// default. The flushed values are far too small to change the gain.
+    #[inline]
+    pub fn set_flush_denormals(&mut self, flush_denormals: bool) {
+        self.flush_denormals = flush_denormals;
+    }
+
+    /// Returns the amount of gain reduction in decibels that was being applied at the end of the
+    /// last processed buffer. This is 0 when no compression is happening and positive otherwise,
+    /// so it can be used directly for a gain reduction meter.
+    #[inline]
+    pub fn gain_reduction_db(&self) -> f32 {
+        -self.envelope_db
+    }
+
+    /// Resets the envelope follower.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.envelope_db = 0.0;
+    }
+
+    /// Returns the static gain change in decibels (always <= 0) for a signal level.
+    fn gain_computer(&self, level_db: f32) -> f32 {
+        let over = level_db - self.threshold_db;
+        let slope = 1.0 / self.ratio - 1.0;
+
+        if self.knee_db > 0.0 && 2.0 * over.abs() <= self.knee_db {
+            let x = over + self.knee_db / 2.0;
+            slope * x * x / (2.0 * self.knee_db)
+        } else if over > 0.0 {
+            slope * over
+        } else {
+            0.0
+        }
+    }
+
+    pub fn process_pcm_frames(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+    ) -> Result<(), Error> {
+        let channels = self.channels as usize;
+        let attack = time_coefficient(self.attack_ms, self.sample_rate);
+        let release = time_coefficient(self.release_ms, self.sample_rate);
+        let makeup_gain_db = self.makeup_gain_db;
+        let flush = self.flush_denormals;
+
+        let mut scratch = std::mem::take(&mut self.scratch);
+        let mut envelope_db = self.envelope_db;
+
+        let result = process_as_f32(self.channels, &mut scratch, output, input, |samples| {
+            for frame in samples.chunks_exact_mut(channels) {
+                let level = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+                let target_db = self.gain_computer(gain_to_db(level));
+
+                let coefficient = if target_db < envelope_db {
+                    attack
+                } else {
+                    release
+                };
+                envelope_db = coefficient * envelope_db + (1.0 - coefficient) * target_db;
+                if flush {
+                    envelope_db = flush_denormal(envelope_db);
+                }
+
+                let gain = db_to_gain(envelope_db + makeup_gain_db);
+                frame.iter_mut().for_each(|s| *s *= gain);
+            }
+        });
+
+        self.envelope_db = envelope_db;
+        self.scratch = scratch;
+        result
+    }
+}
+
+/// Returns the one pole smoothing coefficient for a time constant in milliseconds.
+fn time_coefficient(time_ms: f32, sample_rate: u32) -> f32 {
+    if time_ms <= 0.0 {
+        0.0
+    } else {
+        (-1000.0 / (time_ms * sample_rate as f32)).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::Format;
+
+    fn process(compressor: &mut Compressor, level: f32, frame_count: usize) -> Vec<f32> {
+        let input = vec![level; frame_count];
+        let mut output = vec![0.0f32; frame_count];
+        compressor
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut output[..], Format::F32, 1),
+                &Frames::wrap(&input[..], Format::F32, 1),
+            )
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn gain_computer_has_a_soft_knee() {
+        let mut compressor = Compressor::new(1, 48000);
+        compressor.set_threshold_db(-20.0);
+        compressor.set_ratio(4.0);
+        compressor.set_knee_db(6.0);
+
+        assert_eq!(compressor.gain_computer(-30.0), 0.0);
+        assert_eq!(compressor.gain_computer(-23.0), 0.0);
+        assert!((compressor.gain_computer(-20.0) + 0.5625).abs() < 1e-6);
+        assert!((compressor.gain_computer(-17.0) + 2.25).abs() < 1e-6);
+        assert!((compressor.gain_computer(0.0) + 15.0).abs() < 1e-6);
+
+        compressor.set_knee_db(0.0);
+        assert_eq!(compressor.gain_computer(-20.0), 0.0);
+        assert!((compressor.gain_computer(-16.0) + 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn loud_signals_are_compressed_and_quiet_ones_are_not() {
+        let mut compressor = Compressor::new(1, 48000);
+        compressor.set_threshold_db(-20.0);
+        compressor.set_ratio(4.0);
+        compressor.set_knee_db(0.0);
+        compressor.set_attack_ms(0.0);
+
+        // -40dB stays below the threshold.
+        let output = process(&mut compressor, 0.01, 100);
+        assert!(output.iter().all(|&s| (s - 0.01).abs() < 1e-6));
+        assert_eq!(compressor.gain_reduction_db(), 0.0);
+
+        // 0dB is 20dB over the threshold and comes out 5dB over it.
+        let output = process(&mut compressor, 1.0, 100);
+        assert!((compressor.gain_reduction_db() - 15.0).abs() < 1e-3);
+        assert!((gain_to_db(output[99]) + 15.0).abs() < 1e-3);
+
+        compressor.set_makeup_gain_db(6.0);
+        let output = process(&mut compressor, 1.0, 100);
+        assert!((gain_to_db(output[99]) + 9.0).abs() < 1e-3);
+
+        // After the signal is gone the reduction is released again.
+        compressor.set_release_ms(1.0);
+        process(&mut compressor, 0.0, 4800);
+        assert!(compressor.gain_reduction_db() < 1e-3);
+        compressor.reset();
+        assert_eq!(compressor.gain_reduction_db(), 0.0);
+    }
+
+    #[test]
+    fn envelope_is_flushed_instead_of_getting_stuck_as_a_denormal() {
+        let release = |flush_denormals: bool| {
+            let mut compressor = Compressor::new(1, 48000);
+            compressor.set_attack_ms(0.0);
+            compressor.set_release_ms(1.0);
+            compressor.set_flush_denormals(flush_denormals);
+            process(&mut compressor, 1.0, 100);
+            process(&mut compressor, 0.0, 48000);
+            compressor.gain_reduction_db()
+        };
+
+        // Without flushing, the envelope decays until rounding keeps it at the same denormal.
+        let stuck = release(false);
+        assert!(stuck != 0.0 && stuck.abs() < f32::MIN_POSITIVE);
+        assert_eq!(release(true), 0.0);
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "effect has no channels"))]
+    fn zero_channels_are_rejected() {
+        let mut compressor = Compressor::new(0, 44100);
+        let mut output = [0.0f32; 4];
+        let result = compressor.process_pcm_frames(
+            &mut FramesMut::wrap(&mut output, Format::F32, 0),
+            &Frames::wrap(&[0.5f32; 4], Format::F32, 0),
+        );
+        assert!(matches!(result, Err(Error::InvalidArgs)));
+        assert_eq!(output, [0.0; 4]);
+    }
+}
@@ -0,0 +1,105 @@
+/// Curves for fading and crossfading.
+///
+/// A curve maps a position `t` between 0 (start of the fade) and 1 (end of the fade) to a linear
+/// gain between 0 and 1, so a fade in uses `gain_at(t)` directly and a fade out uses
+/// `gain_at(1.0 - t)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FadeCurve {
+    /// The gain changes linearly. Crossfading with this curve dips by about 6dB in the middle.
+    Linear,
+    /// A quarter sine. The power of a crossfade between uncorrelated signals stays constant.
+    EqualPower,
+    /// The gain changes linearly in decibels over a 60dB range, which sounds even to the ear.
+    Exponential,
+    /// A smoothstep curve that starts and ends slowly.
+    SCurve,
+}
+
+impl FadeCurve {
+    /// Returns the gain at `t`, which is clamped between 0 and 1.
+    pub fn gain_at(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match *self {
+            FadeCurve::Linear => t,
+            FadeCurve::EqualPower => (t * std::f32::consts::FRAC_PI_2).sin(),
+            FadeCurve::Exponential => (1000.0f32.powf(t) - 1.0) / 999.0,
+            FadeCurve::SCurve => t * t * (3.0 - 2.0 * t),
+        }
+    }
+
+    /// Returns the gains of the outgoing and incoming signal respectively at position `t` of a
+    /// crossfade.
+    #[inline]
+    pub fn crossfade_gains(&self, t: f32) -> (f32, f32) {
+        (self.gain_at(1.0 - t), self.gain_at(t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [FadeCurve; 4] = [
+        FadeCurve::Linear,
+        FadeCurve::EqualPower,
+        FadeCurve::Exponential,
+        FadeCurve::SCurve,
+    ];
+
+    #[test]
+    fn curves_go_from_silence_to_full_gain() {
+        for curve in CURVES.iter() {
+            assert_eq!(curve.gain_at(0.0), 0.0, "{:?}", curve);
+            assert!((curve.gain_at(1.0) - 1.0).abs() < 1e-6, "{:?}", curve);
+            assert_eq!(curve.gain_at(-1.0), curve.gain_at(0.0), "{:?}", curve);
+            assert_eq!(curve.gain_at(2.0), curve.gain_at(1.0), "{:?}", curve);
+
+            let gains: Vec<f32> = (0..=100).map(|t| curve.gain_at(t as f32 / 100.0)).collect();
+            assert!(
+                gains.windows(2).all(|pair| pair[0] < pair[1]),
+                "{:?}",
+                curve
+            );
+        }
+    }
+
+    #[test]
+    fn equal_power_crossfades_keep_the_power() {
+        for t in 0..=10 {
+            let (outgoing, incoming) = FadeCurve::EqualPower.crossfade_gains(t as f32 / 10.0);
+            assert!((outgoing * outgoing + incoming * incoming - 1.0).abs() < 1e-5);
+        }
+
+        let (outgoing, incoming) = FadeCurve::Linear.crossfade_gains(0.25);
+        assert_eq!((outgoing, incoming), (0.75, 0.25));
+    }
+
+    #[test]
+    fn curves_have_their_documented_midpoints_and_endpoints() {
+        assert_eq!(FadeCurve::Linear.gain_at(0.5), 0.5);
+        assert!(
+            (FadeCurve::EqualPower.gain_at(0.5) - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-4
+        );
+        assert_eq!(FadeCurve::SCurve.gain_at(0.5), 0.5);
+        // 30dB below full gain is 1/sqrt(1000), offset so that the curve starts at 0.
+        let exponential = (1000.0f32.sqrt() - 1.0) / 999.0;
+        assert!((FadeCurve::Exponential.gain_at(0.5) - exponential).abs() < 1e-6);
+
+        for curve in CURVES.iter() {
+            assert_eq!(curve.gain_at(0.0), 0.0, "{:?}", curve);
+            assert!((curve.gain_at(1.0) - 1.0).abs() < 1e-6, "{:?}", curve);
+            let (outgoing, incoming) = curve.crossfade_gains(0.0);
+            assert!(
+                (outgoing - 1.0).abs() < 1e-6 && incoming == 0.0,
+                "{:?}",
+                curve
+            );
+            let (outgoing, incoming) = curve.crossfade_gains(1.0);
+            assert!(
+                outgoing == 0.0 && (incoming - 1.0).abs() < 1e-6,
+                "{:?}",
+                curve
+            );
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Effects that are implemented in Rust on top of the crate's frame types rather than wrapping
+//! miniaudio. They accept frames in any format and process them as interleaved `f32` samples,
+//! converting to and from a scratch buffer when the frames are not already `f32`.
+//!
+//! Effects with feedback (the reverb's filters and the compressor's envelope) decay towards zero
+//! and would end up with denormal floats, which are very slow to compute with on some CPUs. By
+//! default these effects flush values that are too small to be normal to zero. The flushed values
+//! are below -700dB so this can't be heard, but it costs a comparison per sample, so it can be
+//! turned off with `set_flush_denormals(false)` when the thread already runs with the CPU's
+//! flush-to-zero mode enabled.
+
+mod compressor;
+mod fade;
+mod reverb;
+mod stereo_width;
+
+pub use compressor::*;
+pub use fade::*;
+pub use reverb::*;
+pub use stereo_width::*;
+
+use crate::base::{DitherMode, Error, Format};
+use crate::frames::{Frames, FramesMut};
+
+/// Returns 0 for values that are too small to be represented as a normal `f32`.
+#[inline]
+pub(crate) fn flush_denormal(value: f32) -> f32 {
+    if value.abs() < f32::MIN_POSITIVE {
+        0.0
+    } else {
+        value
+    }
+}
+
+/// Checks the frames passed into an effect and then runs `process` over the input samples as
+/// interleaved `f32`, writing the processed samples into `output`.
+pub(crate) fn process_as_f32<F>(
+    channels: u32,
+    scratch: &mut Vec<f32>,
+    output: &mut FramesMut,
+    input: &Frames,
+    process: F,
+) -> Result<(), Error>
+where
+    F: FnOnce(&mut [f32]),
+{
+    if channels == 0 {
+        ma_debug_panic!("effect has no channels");
+        return Err(Error::InvalidArgs);
+    }
+
+    if output.format() != input.format() {
+        ma_debug_panic!(
+            "output and input format did not match (output: {:?}, input: {:?}",
+            output.format(),
+            input.format()
+        );
+        return Err(Error::InvalidArgs);
+    }
+
+    if output.channels() != channels || input.channels() != channels {
+        ma_debug_panic!(
+            "output and input channels did not match effect (output: {}, input: {}, effect: {})",
+            output.channels(),
+            input.channels(),
+            channels
+        );
+        return Err(Error::InvalidArgs);
+    }
+
+    if output.frame_count() != input.frame_count() {
+        ma_debug_panic!(
+            "output and input buffers did not have the same frame count (output: {}, input: {})",
+            output.frame_count(),
+            input.frame_count()
+        );
+        return Err(Error::InvalidArgs);
+    }
+
+    scratch.clear();
+    scratch.resize(input.sample_count(), 0.0);
+
+    input.convert(
+        &mut FramesMut::wrap(&mut scratch[..], Format::F32, channels),
+        DitherMode::None,
+    );
+    process(&mut scratch[..]);
+    Frames::wrap(&scratch[..], Format::F32, channels).convert(output, DitherMode::None);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flush_denormal_only_flushes_denormals() {
+        assert_eq!(flush_denormal(f32::MIN_POSITIVE / 2.0), 0.0);
+        assert_eq!(flush_denormal(-f32::MIN_POSITIVE / 2.0), 0.0);
+        assert_eq!(flush_denormal(f32::MIN_POSITIVE), f32::MIN_POSITIVE);
+        assert_eq!(flush_denormal(-1e-30), -1e-30);
+        assert_eq!(flush_denormal(0.5), 0.5);
+    }
+}
@@ -0,0 +1,370 @@
+use super::{flush_denormal, process_as_f32};
+use crate::base::Error;
+use crate::frames::{Frames, FramesMut};
+
+// Tunings from the original Freeverb. These are delay lengths in frames at 44100Hz and are scaled
+// for other sample rates.
+const COMB_TUNINGS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNINGS: [usize; 4] = [556, 441, 341, 225];
+const STEREO_SPREAD: usize = 23;
+const TUNING_SAMPLE_RATE: f32 = 44100.0;
+
+const FIXED_GAIN: f32 = 0.015;
+const SCALE_WET: f32 = 3.0;
+const SCALE_DRY: f32 = 2.0;
+const SCALE_DAMP: f32 = 0.4;
+const SCALE_ROOM: f32 = 0.28;
+const OFFSET_ROOM: f32 = 0.7;
+const ALLPASS_FEEDBACK: f32 = 0.5;
+
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    filter_store: f32,
+}
+
+impl Comb {
+    fn new(len: usize) -> Comb {
+        Comb {
+            buffer: vec![0.0; len.max(1)],
+            index: 0,
+            filter_store: 0.0,
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, input: f32, feedback: f32, damp: f32, flush: bool) -> f32 {
+        let output = self.buffer[self.index];
+        self.filter_store = output * (1.0 - damp) + self.filter_store * damp;
+        if flush {
+            self.filter_store = flush_denormal(self.filter_store);
+        }
+        self.buffer[self.index] = input + self.filter_store * feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        output
+    }
+}
+
+struct AllPass {
+    buffer: Vec<f32>,
+    index: usize,
+}
+
+impl AllPass {
+    fn new(len: usize) -> AllPass {
+        AllPass {
+            buffer: vec![0.0; len.max(1)],
+            index: 0,
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, input: f32, flush: bool) -> f32 {
+        let mut buffered = self.buffer[self.index];
+        if flush {
+            buffered = flush_denormal(buffered);
+        }
+        self.buffer[self.index] = input + buffered * ALLPASS_FEEDBACK;
+        self.index = (self.index + 1) % self.buffer.len();
+        buffered - input
+    }
+}
+
+/// The comb and all-pass filters used for a single output channel.
+struct Tank {
+    combs: Vec<Comb>,
+    allpasses: Vec<AllPass>,
+}
+
+impl Tank {
+    fn new(sample_rate: u32, spread: usize) -> Tank {
+        let scale = |len: usize| {
+            ((len + spread) as f32 * sample_rate as f32 / TUNING_SAMPLE_RATE).round() as usize
+        };
+
+        Tank {
+            combs: COMB_TUNINGS
+                .iter()
+                .map(|&len| Comb::new(scale(len)))
+                .collect(),
+            allpasses: ALLPASS_TUNINGS
+                .iter()
+                .map(|&len| AllPass::new(scale(len)))
+                .collect(),
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, input: f32, feedback: f32, damp: f32, flush: bool) -> f32 {
+        let mut output = 0.0;
+        for comb in self.combs.iter_mut() {
+            output += comb.process(input, feedback, damp, flush);
+        }
+        for allpass in self.allpasses.iter_mut() {
+            output = allpass.process(output, flush);
+        }
+        output
+    }
+}
+
+/// A Freeverb style reverb made up of 8 parallel comb filters followed by 4 all-pass filters for
+/// each channel.
+///
+/// The dry signal is passed through without any latency. The wet signal has no lookahead either,
+/// but the first reflections only show up after the shortest comb filter delay, which is roughly
+/// 25ms regardless of the sample rate.
+///
+/// Every channel is fed the same mono mix of the input, and the delay lines of each channel are
+/// offset from one another slightly (the Freeverb "stereo spread") so that the tails of the
+/// channels are decorrelated which is what makes the reverb sound wide. For stereo the width
+/// controls how much of each channel's tail is mixed into the other, with a width of 0 giving the
+/// same tail on both channels. For any other channel count each channel only uses its own tail.
+pub struct Reverb {
+    channels: u32,
+    sample_rate: u32,
+    room_size: f32,
+    damping: f32,
+    wet: f32,
+    dry: f32,
+    width: f32,
+    flush_denormals: bool,
+    tanks: Vec<Tank>,
+    scratch: Vec<f32>,
+}
+
+impl Reverb {
+    pub fn new(channels: u32, sample_rate: u32) -> Reverb {
+        Reverb {
+            channels,
+            sample_rate,
+            room_size: 0.5,
+            damping: 0.5,
+            wet: 1.0 / SCALE_WET,
+            dry: 0.0,
+            width: 1.0,
+            flush_denormals: true,
+            tanks: (0..channels as usize)
+                .map(|channel| Tank::new(sample_rate, channel * STEREO_SPREAD))
+                .collect(),
+            scratch: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn channels(&self) -> u32 {
+        self.channels
+    }
+
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[inline]
+    pub fn room_size(&self) -> f32 {
+        self.room_size
+    }
+
+    /// Sets the size of the room between 0 and 1. Larger rooms have longer tails.
+    #[inline]
+    pub fn set_room_size(&mut self, room_size: f32) {
+        self.room_size = room_size.clamp(0.0, 1.0);
+    }
+
+    #[inline]
+    pub fn damping(&self) -> f32 {
+        self.damping
+    }
+
+    /// Sets how quickly high frequencies decay between 0 and 1.
+    #[inline]
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping.clamp(0.0, 1.0);
+    }
+
+    #[inline]
+    pub fn wet(&self) -> f32 {
+        self.wet
+    }
+
+    /// Sets the level of the reverberated signal between 0 and 1.
+    #[inline]
+    pub fn set_wet(&mut self, wet: f32) {
+        self.wet = wet.clamp(0.0, 1.0);
+    }
+
+    #[inline]
+    pub fn dry(&self) -> f32 {
+        self.dry
+    }
+
+    /// Sets the level of the unprocessed signal between 0 and 1.
+    #[inline]
+    pub fn set_dry(&mut self, dry: f32) {
+        self.dry = dry.clamp(0.0, 1.0);
+    }
+
+    #[inline]
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Sets the stereo width of the reverberated signal between 0 and 1.
+    #[inline]
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, 1.0);
+    }
+
+    #[inline]
+    pub fn flush_denormals(&self) -> bool {
+        self.flush_denormals
+    }
+
+    /// Sets whether the filter state is flushed to zero when it becomes denormal, which is on by
+    /// default. The tail decays into denormals after the input goes silent, which can make the
+    /// reverb a lot slower on some CPUs. Only turn this off if the processing thread already has
+    /// the CPU's flush-to-zero mode enabled.
+    #[inline]
+    pub fn set_flush_denormals(&mut self, flush_denormals: bool) {
+        self.flush_denormals = flush_denormals;
+    }
+
+    /// Clears the tail of the reverb.
+    pub fn reset(&mut self) {
+        for tank in self.tanks.iter_mut() {
+            for comb in tank.combs.iter_mut() {
+                comb.buffer.iter_mut().for_each(|s| *s = 0.0);
+                comb.filter_store = 0.0;
+            }
+            for allpass in tank.allpasses.iter_mut() {
+                allpass.buffer.iter_mut().for_each(|s| *s = 0.0);
+            }
+        }
+    }
+
+    pub fn process_pcm_frames(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+    ) -> Result<(), Error> {
+        let channels = self.channels as usize;
+        let feedback = self.room_size * SCALE_ROOM + OFFSET_ROOM;
+        let damp = self.damping * SCALE_DAMP;
+        let wet = self.wet * SCALE_WET;
+        let dry = self.dry * SCALE_DRY;
+        let wet1 = wet * (self.width / 2.0 + 0.5);
+        let wet2 = wet * ((1.0 - self.width) / 2.0);
+        let flush = self.flush_denormals;
+        let tanks = &mut self.tanks;
+
+        process_as_f32(self.channels, &mut self.scratch, output, input, |samples| {
+            let mut tails = [0.0f32; crate::base::MAX_CHANNELS];
+
+            for frame in samples.chunks_exact_mut(channels) {
+                let mono = frame.iter().sum::<f32>() * FIXED_GAIN;
+
+                for (tail, tank) in tails.iter_mut().zip(tanks.iter_mut()) {
+                    *tail = tank.process(mono, feedback, damp, flush);
+                }
+
+                if channels == 2 {
+                    let (left, right) = (tails[0], tails[1]);
+                    frame[0] = left * wet1 + right * wet2 + frame[0] * dry;
+                    frame[1] = right * wet1 + left * wet2 + frame[1] * dry;
+                } else {
+                    for (sample, tail) in frame.iter_mut().zip(tails.iter()) {
+                        *sample = tail * (wet1 + wet2) + *sample * dry;
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::Format;
+
+    fn process(reverb: &mut Reverb, input: &[f32]) -> Vec<f32> {
+        let channels = reverb.channels();
+        let mut output = vec![0.0f32; input.len()];
+        reverb
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut output[..], Format::F32, channels),
+                &Frames::wrap(input, Format::F32, channels),
+            )
+            .unwrap();
+        output
+    }
+
+    fn impulse(sample_count: usize) -> Vec<f32> {
+        let mut samples = vec![0.0f32; sample_count];
+        samples[0] = 1.0;
+        samples
+    }
+
+    #[test]
+    fn dry_signal_is_passed_through() {
+        let mut reverb = Reverb::new(1, 44100);
+        reverb.set_wet(0.0);
+        reverb.set_dry(0.5);
+
+        let input: Vec<f32> = (0..2000).map(|i| (i as f32 * 0.01).sin()).collect();
+        assert_eq!(process(&mut reverb, &input), input);
+    }
+
+    #[test]
+    fn tail_starts_after_the_shortest_comb_delay() {
+        let mut reverb = Reverb::new(1, 44100);
+        let output = process(&mut reverb, &impulse(4000));
+
+        let first_reflection = COMB_TUNINGS[0];
+        assert!(output[..first_reflection].iter().all(|&s| s == 0.0));
+        assert!(output[first_reflection] != 0.0);
+
+        // The tail is cleared by a reset.
+        reverb.reset();
+        assert!(process(&mut reverb, &[0.0; 4000]).iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn zero_width_gives_the_same_tail_on_both_channels() {
+        let mut reverb = Reverb::new(2, 48000);
+        let wide = process(&mut reverb, &impulse(8000));
+        assert!(wide.chunks_exact(2).any(|frame| frame[0] != frame[1]));
+
+        reverb.reset();
+        reverb.set_width(0.0);
+        let narrow = process(&mut reverb, &impulse(8000));
+        assert!(narrow.chunks_exact(2).all(|frame| frame[0] == frame[1]));
+        assert!(narrow.iter().any(|&s| s != 0.0));
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "effect has no channels"))]
+    fn zero_channels_are_rejected() {
+        let mut reverb = Reverb::new(0, 44100);
+        let mut output = [0.0f32; 4];
+        let result = reverb.process_pcm_frames(
+            &mut FramesMut::wrap(&mut output, Format::F32, 0),
+            &Frames::wrap(&[0.0f32; 4], Format::F32, 0),
+        );
+        assert!(matches!(result, Err(Error::InvalidArgs)));
+    }
+
+    #[test]
+    fn tail_energy_decays_after_an_impulse() {
+        let mut reverb = Reverb::new(1, 44100);
+        let output = process(&mut reverb, &impulse(44100 * 2));
+
+        // The energy in every 100ms after the first one, which is still building up.
+        let energies: Vec<f32> = output
+            .chunks_exact(4410)
+            .skip(1)
+            .map(|window| window.iter().map(|s| s * s).sum())
+            .collect();
+        assert!(energies.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(*energies.last().unwrap() < energies[0] * 1e-6);
+    }
+}
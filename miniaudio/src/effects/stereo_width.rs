@@ -0,0 +1,128 @@
+use super::process_as_f32;
+use crate::base::Error;
+use crate::frames::{Frames, FramesMut};
+
+/// Converts a left and right sample into a mid and side sample.
+#[inline]
+pub fn to_mid_side(left: f32, right: f32) -> (f32, f32) {
+    ((left + right) * 0.5, (left - right) * 0.5)
+}
+
+/// Converts a mid and side sample back into a left and right sample.
+#[inline]
+pub fn from_mid_side(mid: f32, side: f32) -> (f32, f32) {
+    (mid + side, mid - side)
+}
+
+/// Widens or narrows a stereo signal by scaling its side (L - R) component.
+///
+/// A width of 0 removes the side component entirely which leaves the same signal on both
+/// channels. A width of 1 leaves the signal unchanged and anything above 1 makes it wider.
+///
+/// The mono sum (L + R) is never changed by this effect, but a widened signal loses more of its
+/// perceived level when it is folded down to mono because the boosted side component cancels out.
+/// Widening also raises the peak level of each channel and can clip integer output formats if the
+/// side component is already strong.
+pub struct StereoWidth {
+    width: f32,
+    scratch: Vec<f32>,
+}
+
+impl StereoWidth {
+    pub fn new(width: f32) -> StereoWidth {
+        StereoWidth {
+            width: width.max(0.0),
+            scratch: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Sets the width. Negative widths are clamped to 0.
+    #[inline]
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.max(0.0);
+    }
+
+    /// Processes stereo frames. Both `output` and `input` must have 2 channels.
+    pub fn process_pcm_frames(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+    ) -> Result<(), Error> {
+        let width = self.width;
+
+        process_as_f32(2, &mut self.scratch, output, input, |samples| {
+            for frame in samples.chunks_exact_mut(2) {
+                let (mid, side) = to_mid_side(frame[0], frame[1]);
+                let (left, right) = from_mid_side(mid, side * width);
+                frame[0] = left;
+                frame[1] = right;
+            }
+        })
+    }
+}
+
+impl Default for StereoWidth {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::Format;
+
+    fn process(width: f32, input: &[f32]) -> Vec<f32> {
+        let mut output = vec![0.0f32; input.len()];
+        StereoWidth::new(width)
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut output[..], Format::F32, 2),
+                &Frames::wrap(input, Format::F32, 2),
+            )
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn width_scales_the_side_and_keeps_the_mono_sum() {
+        let input = [1.0f32, 0.0, 0.25, 0.75];
+
+        assert_eq!(process(1.0, &input), input);
+        assert_eq!(process(0.0, &input), [0.5, 0.5, 0.5, 0.5]);
+        let wide = process(2.0, &input);
+        assert_eq!(wide, [1.5, -0.5, 0.0, 1.0]);
+        for (frame, wide) in input.chunks_exact(2).zip(wide.chunks_exact(2)) {
+            assert_eq!(frame[0] + frame[1], wide[0] + wide[1]);
+        }
+    }
+
+    #[test]
+    fn mid_side_round_trips() {
+        let (mid, side) = to_mid_side(0.75, -0.25);
+        assert_eq!((mid, side), (0.25, 0.5));
+        assert_eq!(from_mid_side(mid, side), (0.75, -0.25));
+        assert_eq!(StereoWidth::new(-1.0).width(), 0.0);
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "output and input channels did not match effect")
+    )]
+    fn only_stereo_frames_are_accepted() {
+        let input = [0.0f32; 4];
+        let mut output = [0.0f32; 4];
+        assert!(matches!(
+            StereoWidth::default().process_pcm_frames(
+                &mut FramesMut::wrap(&mut output[..], Format::F32, 1),
+                &Frames::wrap(&input[..], Format::F32, 1),
+            ),
+            Err(Error::InvalidArgs)
+        ));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::base::{to_bool32, Error, Format};
+use crate::frames::Frames;
+use miniaudio_sys as sys;
+use std::ffi::CString;
+use std::io;
+use std::mem::MaybeUninit;
+use std::path::Path;
+
+/// The container format that an encoder writes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceFormat {
+    Wav = sys::ma_resource_format_wav as _,
+}
+
+#[repr(transparent)]
+#[derive(Clone)]
+pub struct EncoderConfig(sys::ma_encoder_config);
+
+impl EncoderConfig {
+    pub fn new(
+        resource_format: ResourceFormat,
+        format: Format,
+        channels: u32,
+        sample_rate: u32,
+    ) -> EncoderConfig {
+        EncoderConfig(unsafe {
+            sys::ma_encoder_config_init(resource_format as _, format as _, channels, sample_rate)
+        })
+    }
+
+    #[inline]
+    pub fn format(&self) -> Format {
+        Format::from_c(self.0.format)
+    }
+
+    #[inline]
+    pub fn channels(&self) -> u32 {
+        self.0.channels
+    }
+
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.0.sampleRate
+    }
+}
+
+pub trait SeekWrite: io::Write + io::Seek {}
+
+// Create a blanket implementation so that everything that implements both
+// io::Write and io::Seek also implements SeekWrite.
+impl<T> SeekWrite for T where T: io::Write + io::Seek {}
+
+/// Writes frames into an audio file. miniaudio 0.10 can only encode WAV files.
+///
+/// The header of the file is finished when the encoder is dropped, so the file is only complete
+/// after that.
+pub struct Encoder {
+    inner: Box<sys::ma_encoder>,
+    has_writer: bool,
+}
+
+impl Encoder {
+    /// Creates an `Encoder` that writes to a file. This will fail with an `InvalidFile` error if
+    /// the path is not valid utf-8.
+    pub fn from_file<P: AsRef<Path>>(file: P, config: &EncoderConfig) -> Result<Self, Error> {
+        let mut encoder = Box::new(MaybeUninit::<sys::ma_encoder>::uninit());
+        let filename = file
+            .as_ref()
+            .to_str()
+            .ok_or(Error::InvalidFile)
+            .and_then(|s| CString::new(s.to_string()).map_err(|_err| Error::InvalidFile))?;
+
+        Error::from_c_result(unsafe {
+            sys::ma_encoder_init_file(
+                filename.as_ptr() as *const _,
+                &config.0,
+                encoder.as_mut_ptr(),
+            )
+        })?;
+
+        Ok(Encoder {
+            inner: unsafe { Box::from_raw(Box::into_raw(encoder).cast()) },
+            has_writer: false,
+        })
+    }
+
+    pub fn from_write<T: 'static + SeekWrite>(
+        writer: T,
+        config: &EncoderConfig,
+    ) -> Result<Self, Error> {
+        Self::from_boxed_write(Box::new(writer), config)
+    }
+
+    pub fn from_boxed_write(
+        writer: Box<dyn SeekWrite>,
+        config: &EncoderConfig,
+    ) -> Result<Self, Error> {
+        let mut encoder = Box::new(MaybeUninit::<sys::ma_encoder>::uninit());
+        let user_data = Box::into_raw(Box::new(writer));
+
+        let result = unsafe {
+            sys::ma_encoder_init(
+                Some(encoder_write_with_writer),
+                Some(encoder_seek_with_writer),
+                user_data as *mut _,
+                &config.0,
+                encoder.as_mut_ptr(),
+            )
+        };
+
+        if let Err(err) = Error::from_c_result(result) {
+            // The encoder didn't take ownership of the writer.
+            let _writer = unsafe { Box::from_raw(user_data) };
+            return Err(err);
+        }
+
+        Ok(Encoder {
+            inner: unsafe { Box::from_raw(Box::into_raw(encoder).cast()) },
+            has_writer: true,
+        })
+    }
+
+    /// Writes frames and returns the number of frames that were written. The frames must have
+    /// the format and channel count that the encoder was created with.
+    pub fn write_pcm_frames(&mut self, frames: &Frames) -> Result<u64, Error> {
+        ma_trace_span!("Encoder::write_pcm_frames", frames = frames.frame_count());
+
+        if frames.format() != self.format() || frames.channels() != self.channels() {
+            ma_debug_panic!(
+                "frames did not match encoder (frames: {:?}/{}, encoder: {:?}/{})",
+                frames.format(),
+                frames.channels(),
+                self.format(),
+                self.channels()
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        Ok(unsafe {
+            sys::ma_encoder_write_pcm_frames(
+                &mut *self.inner,
+                frames.as_ptr() as *const _,
+                frames.frame_count() as u64,
+            )
+        })
+    }
+
+    #[inline]
+    pub fn format(&self) -> Format {
+        Format::from_c(self.inner.config.format)
+    }
+
+    #[inline]
+    pub fn channels(&self) -> u32 {
+        self.inner.config.channels
+    }
+
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.inner.config.sampleRate
+    }
+}
+
+impl Drop for Encoder {
+    fn drop(&mut self) {
+        // This finishes the file, so it has to happen while the writer is still alive.
+        unsafe { sys::ma_encoder_uninit(&mut *self.inner) };
+
+        if self.has_writer {
+            // Recreate the box and allow it to be dropped.
+            let _writer: Box<Box<dyn SeekWrite>> =
+                unsafe { Box::from_raw(self.inner.pUserData as *mut _) };
+            self.has_writer = false;
+            self.inner.pUserData = std::ptr::null_mut();
+        }
+    }
+}
+
+unsafe impl Send for Encoder {}
+
+unsafe extern "C" fn encoder_write_with_writer(
+    encoder: *mut sys::ma_encoder,
+    buffer_in: *const std::ffi::c_void,
+    bytes_to_write: usize,
+) -> usize {
+    if encoder.is_null() {
+        return 0;
+    }
+
+    let writer = &mut *((*encoder).pUserData as *mut Box<dyn SeekWrite>);
+    let buffer = std::slice::from_raw_parts(buffer_in as *const u8, bytes_to_write);
+
+    match writer.write_all(buffer) {
+        Ok(()) => bytes_to_write,
+        Err(_) => 0,
+    }
+}
+
+unsafe extern "C" fn encoder_seek_with_writer(
+    encoder: *mut sys::ma_encoder,
+    byte_offset: std::os::raw::c_int,
+    origin: sys::ma_seek_origin,
+) -> sys::ma_bool32 {
+    if encoder.is_null() {
+        return to_bool32(false);
+    }
+
+    let writer = &mut *((*encoder).pUserData as *mut Box<dyn SeekWrite>);
+    let pos = match origin {
+        sys::ma_seek_origin_start => io::SeekFrom::Start(byte_offset as _),
+        sys::ma_seek_origin_current => io::SeekFrom::Current(byte_offset as _),
+        sys::ma_seek_origin_end => io::SeekFrom::End(byte_offset as _),
+        _ => return to_bool32(false),
+    };
+
+    to_bool32(writer.seek(pos).is_ok())
+}
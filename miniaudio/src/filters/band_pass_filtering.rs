@@ -1,4 +1,5 @@
 use super::biquad_filtering::Biquad;
+use super::check_filter_order;
 use crate::base::{Error, Format, MAX_FILTER_ORDER};
 use crate::frames::{Frames, FramesMut};
 use miniaudio_sys as sys;
@@ -213,7 +214,8 @@ impl BPFConfig {
         self.0.order
     }
 
-    /// If set to 0, will be treated as a passthrough (no filtering will be applied).
+    /// If set to 0, will be treated as a passthrough (no filtering will be applied). Orders above
+    /// `max_filter_order()` are rejected by `BPF::new`.
     #[inline]
     pub fn set_order(&mut self, order: u32) {
         self.0.order = order;
@@ -225,8 +227,10 @@ impl BPFConfig {
 pub struct BPF(sys::ma_bpf);
 
 impl BPF {
+    /// Returns `Error::InvalidArgs` if the order of the config is above `max_filter_order()`.
     #[inline]
     pub fn new(config: &BPFConfig) -> Result<BPF, Error> {
+        check_filter_order(config.order())?;
         let mut bpf = std::mem::MaybeUninit::<BPF>::uninit();
         unsafe {
             Error::from_c_result(sys::ma_bpf_init(
@@ -1,4 +1,5 @@
 use super::biquad_filtering::Biquad;
+use super::check_filter_order;
 use crate::base::{Error, Format};
 use crate::frames::{Frames, FramesMut};
 use miniaudio_sys as sys;
@@ -324,6 +325,7 @@ impl HPFConfig {
         self.0.order
     }
 
+    /// Orders above `max_filter_order()` are rejected by `HPF::new`.
     #[inline]
     pub fn set_order(&mut self, order: u32) {
         self.0.order = order;
@@ -336,7 +338,9 @@ impl HPFConfig {
 pub struct HPF(sys::ma_hpf);
 
 impl HPF {
+    /// Returns `Error::InvalidArgs` if the order of the config is above `max_filter_order()`.
     pub fn new(config: &HPFConfig) -> Result<HPF, Error> {
+        check_filter_order(config.order())?;
         let mut hpf = std::mem::MaybeUninit::<HPF>::uninit();
         unsafe {
             Error::from_c_result(sys::ma_hpf_init(
@@ -1,4 +1,5 @@
 use super::biquad_filtering::Biquad;
+use super::check_filter_order;
 use crate::base::{Error, Format};
 use crate::frames::{Frames, FramesMut};
 use miniaudio_sys as sys;
@@ -323,6 +324,7 @@ impl LPFConfig {
         self.0.order
     }
 
+    /// Orders above `max_filter_order()` are rejected by `LPF::new`.
     #[inline]
     pub fn set_order(&mut self, order: u32) {
         self.0.order = order;
@@ -334,7 +336,9 @@ impl LPFConfig {
 pub struct LPF(sys::ma_lpf);
 
 impl LPF {
+    /// Returns `Error::InvalidArgs` if the order of the config is above `max_filter_order()`.
     pub fn new(config: &LPFConfig) -> Result<LPF, Error> {
+        check_filter_order(config.order())?;
         let mut lpf = std::mem::MaybeUninit::<LPF>::uninit();
         unsafe {
             Error::from_c_result(sys::ma_lpf_init(
@@ -6,3 +6,76 @@ pub mod low_pass_filtering;
 pub mod low_shelf_filter;
 pub mod notching_filter;
 pub mod peaking_eq_filter;
+
+use crate::base::{Error, MAX_FILTER_ORDER};
+
+/// The highest order that miniaudio's low-pass, high-pass and band-pass filters support. This is
+/// the same as `MAX_FILTER_ORDER`.
+#[inline]
+pub const fn max_filter_order() -> u32 {
+    MAX_FILTER_ORDER as u32
+}
+
+/// Returns `Error::InvalidArgs` for orders that miniaudio would fail to initialize a filter with.
+pub(crate) fn check_filter_order(order: u32) -> Result<(), Error> {
+    if order > max_filter_order() {
+        ma_debug_panic!(
+            "filter order is above the maximum (order: {}, max: {})",
+            order,
+            max_filter_order()
+        );
+        return Err(Error::InvalidArgs);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::band_pass_filtering::{BPFConfig, BPF};
+    use super::high_pass_filtering::{HPFConfig, HPF};
+    use super::low_pass_filtering::{LPFConfig, LPF};
+    use super::*;
+    use crate::base::Format;
+
+    // The order is checked like any other argument, which panics in debug builds and returns an
+    // error otherwise.
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "filter order is above the maximum")
+    )]
+    fn lpf_rejects_oversized_order() {
+        let mut config = LPFConfig::new(Format::F32, 1, 48000, 1000.0, 2);
+        config.set_order(max_filter_order() + 1);
+        assert!(matches!(LPF::new(&config), Err(Error::InvalidArgs)));
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "filter order is above the maximum")
+    )]
+    fn hpf_rejects_oversized_order() {
+        let mut config = HPFConfig::new(Format::F32, 1, 48000, 1000.0, 2);
+        config.set_order(max_filter_order() + 1);
+        assert!(matches!(HPF::new(&config), Err(Error::InvalidArgs)));
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "filter order is above the maximum")
+    )]
+    fn bpf_rejects_oversized_order() {
+        let mut config = BPFConfig::new(Format::F32, 1, 48000, 1000.0, 2);
+        config.set_order(max_filter_order() + 1);
+        assert!(matches!(BPF::new(&config), Err(Error::InvalidArgs)));
+    }
+
+    #[test]
+    fn max_filter_order_is_accepted() {
+        let config = LPFConfig::new(Format::F32, 1, 48000, 1000.0, max_filter_order());
+        assert!(LPF::new(&config).is_ok());
+    }
+}
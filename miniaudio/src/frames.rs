@@ -1,5 +1,6 @@
 use crate::base::*;
 use crate::conversion::conversion_fn_for;
+use std::convert::TryFrom;
 
 pub struct Frames<'s> {
     data: &'s [u8],
@@ -31,13 +32,19 @@ impl<'s> Frames<'s> {
             self.frame_count(),
             dest.frame_count()
         );
+        assert!(
+            self.channels == dest.channels,
+            "frame conversion with different channel counts (input: {}, output: {})",
+            self.channels,
+            dest.channels
+        );
 
         let convert_fn = conversion_fn_for(self.format, dest.format);
         unsafe {
             convert_fn(
                 dest.as_mut_ptr() as *mut _,
                 self.as_ptr() as *const _,
-                self.frame_count() as u64,
+                (self.frame_count() * self.channels as usize) as u64,
                 dither_mode as _,
             );
         }
@@ -52,6 +59,12 @@ impl<'s> Frames<'s> {
         self.data
     }
 
+    /// Returns the samples of these frames.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if `S` is not a sample of this buffer's format or if the data is not
+    /// aligned for `S`, which can only happen when the frames were wrapped from a byte slice.
     #[inline]
     pub fn as_samples<S: Sample>(&self) -> &[S] {
         assert!(
@@ -61,10 +74,103 @@ impl<'s> Frames<'s> {
             S::format()
         );
 
+        assert_aligned::<S>(self.data.as_ptr());
+
         let len = self.sample_count();
         unsafe { std::slice::from_raw_parts(self.data.as_ptr().cast::<S>(), len) }
     }
 
+    /// Reads a packed 24-bit sample sign extended to an `i32`. The result is in the range of a
+    /// 24-bit integer and is not scaled up to the full `i32` range.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if the format of these frames is not `S24` or if `frame` or `channel` are
+    /// out of range.
+    #[inline]
+    pub fn sample_i32(&self, frame: usize, channel: u32) -> i32 {
+        let offset = s24_offset(self.format, self.channels, frame, channel);
+        read_s24(&self.data[offset..offset + 3])
+    }
+
+    /// Reads a sample normalized to `[-1, 1]` for any format, e.g. for drawing waveforms or
+    /// metering without caring about how the frames are stored. Integer formats are divided by
+    /// their most negative value, so their largest positive value is slightly below 1.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if the format is unknown or if `frame` or `channel` are out of range.
+    #[inline]
+    pub fn sample_f32(&self, frame: usize, channel: u32) -> f32 {
+        read_sample_f32(self.data, self.format, self.channels, frame, channel)
+    }
+
+    /// Returns the range of frames that contain sound, e.g. to trim the silence at the start and
+    /// end of a recording. A frame contains sound if a sample in any channel is louder than
+    /// `threshold_db` decibels below full scale.
+    ///
+    /// The range is returned as the index of the first frame with sound and the index one past
+    /// the last one, so that it can be used to slice the frames. If all frames are silent, an
+    /// empty range of `(0, 0)` is returned.
+    pub fn find_sound_bounds(&self, threshold_db: f32) -> (usize, usize) {
+        let threshold = db_to_gain(threshold_db);
+        let has_sound = |frame: usize| {
+            (0..self.channels).any(|channel| self.sample_f32(frame, channel).abs() > threshold)
+        };
+
+        let frame_count = self.frame_count();
+        match (0..frame_count).find(|&frame| has_sound(frame)) {
+            Some(start) => {
+                let end = (start..frame_count)
+                    .rev()
+                    .find(|&frame| has_sound(frame))
+                    .unwrap_or(start);
+                (start, end + 1)
+            }
+            None => (0, 0),
+        }
+    }
+
+    /// Spreads mono frames over `map.len()` output channels with the gain of each output channel
+    /// given by `map`, e.g. to put a sound only on the center channel of a 5.1 layout. This is
+    /// simpler than a channel converter when the sound only has to be placed on some channels.
+    ///
+    /// The output has the same format as the input. Samples are scaled as `f32`, so integer
+    /// samples that a gain above 1 pushes out of range are clipped. Returns `Error::InvalidArgs`
+    /// if the frames are not mono or `map` doesn't have between 1 and `MAX_CHANNELS` entries.
+    pub fn expand_to(&self, map: &[f32]) -> Result<FramesBuffer, Error> {
+        if self.channels != 1 {
+            ma_debug_panic!(
+                "only mono frames can be expanded (channels: {})",
+                self.channels
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        if map.is_empty() || map.len() > MAX_CHANNELS {
+            ma_debug_panic!("invalid channel count for channel map ({})", map.len());
+            return Err(Error::InvalidArgs);
+        }
+
+        let frame_count = self.frame_count();
+        let mut mono = vec![0f32; frame_count];
+        self.convert(
+            &mut FramesMut::wrap(&mut mono[..], Format::F32, 1),
+            DitherMode::None,
+        );
+
+        let samples: Vec<f32> = mono
+            .iter()
+            .flat_map(|&sample| map.iter().map(move |&gain| sample * gain))
+            .collect();
+
+        let channels = map.len() as u32;
+        let mut buffer = FramesBuffer::new(self.format, channels, frame_count);
+        Frames::wrap(&samples[..], Format::F32, channels)
+            .convert(&mut buffer.frames_mut(), DitherMode::None);
+        Ok(buffer)
+    }
+
     #[inline]
     pub fn frames<'t, S: 'static + Sample>(&'t self) -> impl 't + Iterator<Item = &[S]> {
         FramesIter {
@@ -74,6 +180,29 @@ impl<'s> Frames<'s> {
         }
     }
 
+    /// Returns an iterator over the samples of one channel, e.g. the left channel of stereo
+    /// frames is channel 0 and yields every even-indexed sample. This walks the interleaved
+    /// samples without copying them.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if `channel` is out of range or if `S` doesn't match the format.
+    #[inline]
+    pub fn channel<'t, S: 'static + Sample>(
+        &'t self,
+        channel: u32,
+    ) -> impl 't + Iterator<Item = &'t S> {
+        assert!(
+            channel < self.channels,
+            "channel out of range (channel: {}, channels: {})",
+            channel,
+            self.channels
+        );
+        self.as_samples::<S>()[channel as usize..]
+            .iter()
+            .step_by(self.channels as usize)
+    }
+
     #[inline]
     pub fn byte_count(&self) -> usize {
         self.data.len()
@@ -96,10 +225,104 @@ impl<'s> Frames<'s> {
         self.format
     }
 
+    /// Copies the frames into a new buffer that owns its data, so that they can be kept after
+    /// the data they borrow from is gone. Only whole frames are copied.
+    pub fn to_owned(&self) -> FramesBuffer {
+        let mut buffer = FramesBuffer::new(self.format, self.channels, self.frame_count());
+        let byte_count = buffer.as_bytes().len();
+        buffer
+            .as_bytes_mut()
+            .copy_from_slice(&self.as_bytes()[..byte_count]);
+        buffer
+    }
+
+    /// Checks that the frames are laid out correctly for their format and channel count: the
+    /// format is known, the channel count is between 1 and `MAX_CHANNELS`, the data holds a whole
+    /// number of frames and it is aligned for the sample type of the format. This is only checked
+    /// in debug builds and does nothing in release builds, so it can be called wherever frames
+    /// are received to catch buffers that were wrapped with the wrong format or channel count.
+    ///
+    /// ### Panics
+    ///
+    /// In debug builds this panics if any of the checks fail.
+    #[inline]
+    pub fn assert_layout(&self) {
+        debug_assert_layout(
+            self.data.as_ptr(),
+            self.data.len(),
+            self.format,
+            self.channels,
+        );
+    }
+
     #[inline]
     pub fn channels(&self) -> u32 {
         self.channels
     }
+
+    /// Copies the frames into an array with one row per frame and one column per channel.
+    ///
+    /// Samples are converted to `f32` the same way as in `convert`, so integer formats are
+    /// normalized to the range [-1, 1].
+    #[cfg(feature = "ndarray")]
+    pub fn to_array2(&self) -> ndarray::Array2<f32> {
+        let mut samples = vec![0.0f32; self.sample_count()];
+        self.convert(
+            &mut FramesMut::wrap(&mut samples[..], Format::F32, self.channels),
+            DitherMode::None,
+        );
+
+        ndarray::Array2::from_shape_vec((self.frame_count(), self.channels as usize), samples)
+            .expect("sample count is frame count * channels")
+    }
+
+    /// Returns the frames as `dasp` frames without copying them. This returns `None` if `S` is
+    /// not a sample of this buffer's format or if `N` is not the channel count.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if the data is not aligned for `S`, which can only happen when the frames
+    /// were wrapped from a byte slice.
+    #[cfg(feature = "dasp")]
+    pub fn as_dasp_frames<S: Sample, const N: usize>(&self) -> Option<&[[S; N]]> {
+        if N == 0 || N != self.channels as usize || S::format() != self.format {
+            return None;
+        }
+
+        let samples = self.as_samples::<S>();
+        Some(unsafe { std::slice::from_raw_parts(samples.as_ptr().cast(), samples.len() / N) })
+    }
+
+    /// Converts the frames into `dasp` frames of any sample type. Samples are converted through
+    /// `f32`. Returns `Error::InvalidArgs` if the channel count of `F` doesn't match.
+    #[cfg(feature = "dasp")]
+    pub fn to_dasp_frames<F>(&self) -> Result<Vec<F>, Error>
+    where
+        F: dasp_frame::Frame,
+        F::Sample: dasp_sample::FromSample<f32>,
+    {
+        if F::CHANNELS != self.channels as usize {
+            ma_debug_panic!(
+                "dasp frame channels did not match (dasp: {}, frames: {})",
+                F::CHANNELS,
+                self.channels
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        let mut samples = vec![0.0f32; self.sample_count()];
+        self.convert(
+            &mut FramesMut::wrap(&mut samples[..], Format::F32, self.channels),
+            DitherMode::None,
+        );
+
+        Ok(samples
+            .chunks_exact(F::CHANNELS)
+            .map(|frame| {
+                F::from_fn(|channel| dasp_sample::FromSample::from_sample_(frame[channel]))
+            })
+            .collect())
+    }
 }
 
 pub struct FramesMut<'s> {
@@ -130,6 +353,49 @@ impl<'s> FramesMut<'s> {
         self.data.as_ptr() as *mut u8
     }
 
+    /// Multiplies the first `frame_count` frames by `gain`. Unlike miniaudio's volume functions
+    /// this saturates integer samples instead of letting them wrap around.
+    pub(crate) fn apply_gain(&mut self, frame_count: usize, gain: f32) {
+        self.apply_gain_to_range(0, frame_count, gain);
+    }
+
+    /// Multiplies `frame_count` frames starting at `first_frame` by `gain`, saturating like
+    /// `apply_gain`.
+    pub(crate) fn apply_gain_to_range(
+        &mut self,
+        first_frame: usize,
+        frame_count: usize,
+        gain: f32,
+    ) {
+        let channels = self.channels as usize;
+        let start = (first_frame * channels).min(self.sample_count());
+        let end = ((first_frame + frame_count) * channels).min(self.sample_count());
+        let sample_size = self.format.size_in_bytes();
+        let bytes = &mut self.data[(start * sample_size)..(end * sample_size)];
+
+        match self.format {
+            Format::U8 => bytes.iter_mut().for_each(|s| {
+                *s = ((*s as f32 - 128.0) * gain + 128.0) as u8;
+            }),
+            Format::S16 => bytes.chunks_exact_mut(2).for_each(|s| {
+                let sample = i16::from_ne_bytes([s[0], s[1]]);
+                s.copy_from_slice(&((sample as f32 * gain) as i16).to_ne_bytes());
+            }),
+            Format::S24 => bytes.chunks_exact_mut(3).for_each(|s| {
+                write_s24(s, (read_s24(s) as f32 * gain) as i32);
+            }),
+            Format::S32 => bytes.chunks_exact_mut(4).for_each(|s| {
+                let sample = i32::from_ne_bytes([s[0], s[1], s[2], s[3]]);
+                s.copy_from_slice(&((sample as f64 * gain as f64) as i32).to_ne_bytes());
+            }),
+            Format::F32 => bytes.chunks_exact_mut(4).for_each(|s| {
+                let sample = f32::from_ne_bytes([s[0], s[1], s[2], s[3]]);
+                s.copy_from_slice(&(sample * gain).to_ne_bytes());
+            }),
+            Format::Unknown => {}
+        }
+    }
+
     /// Convert this frames samples into another format, placing the new converted
     /// frames into `dest`.
     #[inline]
@@ -140,18 +406,30 @@ impl<'s> FramesMut<'s> {
             self.frame_count(),
             dest.frame_count()
         );
+        assert!(
+            self.channels == dest.channels,
+            "frame conversion with different channel counts (input: {}, output: {})",
+            self.channels,
+            dest.channels
+        );
 
         let convert_fn = conversion_fn_for(self.format, dest.format);
         unsafe {
             convert_fn(
                 dest.as_mut_ptr() as *mut _,
                 self.as_ptr() as *const _,
-                self.frame_count() as u64,
+                (self.frame_count() * self.channels as usize) as u64,
                 dither_mode as _,
             );
         }
     }
 
+    /// Returns the samples of these frames.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if `S` is not a sample of this buffer's format or if the data is not
+    /// aligned for `S`, which can only happen when the frames were wrapped from a byte slice.
     #[inline]
     pub fn as_samples<S: Sample>(&self) -> &[S] {
         assert!(
@@ -161,10 +439,18 @@ impl<'s> FramesMut<'s> {
             S::format()
         );
 
+        assert_aligned::<S>(self.data.as_ptr());
+
         let len = self.sample_count();
         unsafe { std::slice::from_raw_parts(self.data.as_ptr().cast::<S>(), len) }
     }
 
+    /// Returns the samples of these frames.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if `S` is not a sample of this buffer's format or if the data is not
+    /// aligned for `S`, which can only happen when the frames were wrapped from a byte slice.
     #[inline]
     pub fn as_samples_mut<S: Sample>(&mut self) -> &mut [S] {
         assert!(
@@ -174,10 +460,49 @@ impl<'s> FramesMut<'s> {
             S::format()
         );
 
+        assert_aligned::<S>(self.data.as_ptr());
+
         let len = self.sample_count();
         unsafe { std::slice::from_raw_parts_mut(self.data.as_mut_ptr().cast::<S>(), len) }
     }
 
+    /// Reads a packed 24-bit sample sign extended to an `i32`. The result is in the range of a
+    /// 24-bit integer and is not scaled up to the full `i32` range.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if the format of these frames is not `S24` or if `frame` or `channel` are
+    /// out of range.
+    #[inline]
+    pub fn sample_i32(&self, frame: usize, channel: u32) -> i32 {
+        let offset = s24_offset(self.format, self.channels, frame, channel);
+        read_s24(&self.data[offset..offset + 3])
+    }
+
+    /// Reads a sample normalized to `[-1, 1]` for any format, e.g. for drawing waveforms or
+    /// metering without caring about how the frames are stored. Integer formats are divided by
+    /// their most negative value, so their largest positive value is slightly below 1.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if the format is unknown or if `frame` or `channel` are out of range.
+    #[inline]
+    pub fn sample_f32(&self, frame: usize, channel: u32) -> f32 {
+        read_sample_f32(self.data, self.format, self.channels, frame, channel)
+    }
+
+    /// Writes a packed 24-bit sample. Values outside of the range of a 24-bit integer are clamped.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if the format of these frames is not `S24` or if `frame` or `channel` are
+    /// out of range.
+    #[inline]
+    pub fn set_sample_i32(&mut self, frame: usize, channel: u32, value: i32) {
+        let offset = s24_offset(self.format, self.channels, frame, channel);
+        write_s24(&mut self.data[offset..offset + 3], value);
+    }
+
     #[inline]
     pub fn frames<'t, S: 'static + Sample>(&'t self) -> impl 't + Iterator<Item = &[S]> {
         FramesIter {
@@ -187,6 +512,52 @@ impl<'s> FramesMut<'s> {
         }
     }
 
+    /// Returns an iterator over the samples of one channel, e.g. the left channel of stereo
+    /// frames is channel 0 and yields every even-indexed sample. This walks the interleaved
+    /// samples without copying them.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if `channel` is out of range or if `S` doesn't match the format.
+    #[inline]
+    pub fn channel<'t, S: 'static + Sample>(
+        &'t self,
+        channel: u32,
+    ) -> impl 't + Iterator<Item = &'t S> {
+        assert!(
+            channel < self.channels,
+            "channel out of range (channel: {}, channels: {})",
+            channel,
+            self.channels
+        );
+        self.as_samples::<S>()[channel as usize..]
+            .iter()
+            .step_by(self.channels as usize)
+    }
+
+    /// Returns an iterator over mutable references to the samples of one channel, e.g. to
+    /// filter one channel of interleaved frames in place.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if `channel` is out of range or if `S` doesn't match the format.
+    #[inline]
+    pub fn channel_mut<'t, S: 'static + Sample>(
+        &'t mut self,
+        channel: u32,
+    ) -> impl 't + Iterator<Item = &'t mut S> {
+        let channels = self.channels;
+        assert!(
+            channel < channels,
+            "channel out of range (channel: {}, channels: {})",
+            channel,
+            channels
+        );
+        self.as_samples_mut::<S>()[channel as usize..]
+            .iter_mut()
+            .step_by(channels as usize)
+    }
+
     #[inline]
     pub fn frames_mut<'t, S: 'static + Sample>(
         &'t mut self,
@@ -236,10 +607,445 @@ impl<'s> FramesMut<'s> {
         self.format
     }
 
+    /// Returns the frames as mutable `dasp` frames without copying them. This returns `None` if
+    /// `S` is not a sample of this buffer's format or if `N` is not the channel count.
+    ///
+    /// ### Panics
+    ///
+    /// This will panic if the data is not aligned for `S`, which can only happen when the frames
+    /// were wrapped from a byte slice.
+    #[cfg(feature = "dasp")]
+    pub fn as_dasp_frames_mut<S: Sample, const N: usize>(&mut self) -> Option<&mut [[S; N]]> {
+        if N == 0 || N != self.channels as usize || S::format() != self.format {
+            return None;
+        }
+
+        let samples = self.as_samples_mut::<S>();
+        Some(unsafe {
+            std::slice::from_raw_parts_mut(samples.as_mut_ptr().cast(), samples.len() / N)
+        })
+    }
+
+    /// Copies the frames into a new buffer that owns its data, so that they can be kept after
+    /// the data they borrow from is gone. Only whole frames are copied.
+    pub fn to_owned(&self) -> FramesBuffer {
+        let mut buffer = FramesBuffer::new(self.format, self.channels, self.frame_count());
+        let byte_count = buffer.as_bytes().len();
+        buffer
+            .as_bytes_mut()
+            .copy_from_slice(&self.as_bytes()[..byte_count]);
+        buffer
+    }
+
+    /// Checks that the frames are laid out correctly for their format and channel count: the
+    /// format is known, the channel count is between 1 and `MAX_CHANNELS`, the data holds a whole
+    /// number of frames and it is aligned for the sample type of the format. This is only checked
+    /// in debug builds and does nothing in release builds, so it can be called wherever frames
+    /// are received to catch buffers that were wrapped with the wrong format or channel count.
+    ///
+    /// ### Panics
+    ///
+    /// In debug builds this panics if any of the checks fail.
+    #[inline]
+    pub fn assert_layout(&self) {
+        debug_assert_layout(
+            self.data.as_ptr(),
+            self.data.len(),
+            self.format,
+            self.channels,
+        );
+    }
+
+    #[inline]
+    pub fn channels(&self) -> u32 {
+        self.channels
+    }
+}
+
+/// The size of one frame with the given format and channel count in bytes.
+#[inline]
+pub fn bytes_per_frame(format: Format, channels: u32) -> usize {
+    format.bytes_per_sample() * channels as usize
+}
+
+/// The number of whole frames with the given format and channel count that fit into `bytes`.
+/// Returns 0 if the format or channel count make frames 0 bytes long.
+#[inline]
+pub fn frame_count_from_bytes(bytes: &[u8], format: Format, channels: u32) -> usize {
+    bytes
+        .len()
+        .checked_div(bytes_per_frame(format, channels))
+        .unwrap_or(0)
+}
+
+/// The order of the bytes of the samples in a byte buffer.
+///
+/// miniaudio always works with samples in the native byte order of the machine, but files and
+/// network streams often use a fixed one, e.g. AIFF and many network protocols are big-endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ByteOrder {
+    #[default]
+    Native,
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    /// Returns true if samples in this byte order don't need to be swapped for miniaudio.
+    #[inline]
+    pub fn is_native(self) -> bool {
+        match self {
+            ByteOrder::Native => true,
+            ByteOrder::Little => cfg!(target_endian = "little"),
+            ByteOrder::Big => cfg!(target_endian = "big"),
+        }
+    }
+}
+
+/// Converts the samples in `bytes` between `byte_order` and the native byte order, in place.
+/// Swapping is its own inverse, so this is used on the way in as well as on the way out. Bytes
+/// at the end that don't make up a whole sample are left alone.
+pub fn swap_sample_bytes(bytes: &mut [u8], format: Format, byte_order: ByteOrder) {
+    let sample_size = format.bytes_per_sample();
+    if byte_order.is_native() || sample_size < 2 {
+        return;
+    }
+
+    for sample in bytes.chunks_exact_mut(sample_size) {
+        sample.reverse();
+    }
+}
+
+/// Converts a duration into a number of frames at the given sample rate, rounding to the nearest
+/// frame.
+#[inline]
+pub fn duration_to_frames(duration: std::time::Duration, sample_rate: u32) -> u64 {
+    (duration.as_secs_f64() * sample_rate as f64).round() as u64
+}
+
+/// Converts a number of frames at the given sample rate into a duration. Returns a zero duration
+/// if the sample rate is 0.
+#[inline]
+pub fn frames_to_duration(frames: u64, sample_rate: u32) -> std::time::Duration {
+    if sample_rate == 0 {
+        return std::time::Duration::from_secs(0);
+    }
+
+    let rate = sample_rate as u64;
+    let secs = frames / rate;
+    let nanos = ((frames % rate) * 1_000_000_000 + rate / 2) / rate;
+    std::time::Duration::from_secs(secs) + std::time::Duration::from_nanos(nanos)
+}
+
+/// A heuristic that returns true if the channels of `b` look swapped compared to `a`, e.g. the
+/// left and right channel of a stereo buffer after a conversion that was supposed to keep them.
+///
+/// Every channel of `a` is correlated with every channel of `b`. If a channel of `a` correlates
+/// clearly better with another channel of `b` than with the channel at the same position, the
+/// channels are considered swapped. This only works when the channels have different content: if
+/// they are the same (e.g. mono content in a stereo buffer) they can't be told apart and this
+/// returns false. It also returns false if the channel counts are different or there are no
+/// frames. Only the frames that are in both buffers are compared. This is meant for tests and is
+/// not fast.
+pub fn detect_channel_swap(a: &Frames, b: &Frames) -> bool {
+    let channels = a.channels();
+    if channels != b.channels() || channels < 2 {
+        return false;
+    }
+
+    let frames = a.frame_count().min(b.frame_count());
+    if frames == 0 {
+        return false;
+    }
+
+    let to_f32 = |frames_in: &Frames| {
+        let byte_count = frames * bytes_per_frame(frames_in.format(), channels);
+        let input = Frames {
+            data: &frames_in.as_bytes()[..byte_count],
+            format: frames_in.format(),
+            channels,
+        };
+        let mut samples = vec![0.0f32; frames * channels as usize];
+        input.convert(
+            &mut FramesMut::wrap(&mut samples[..], Format::F32, channels),
+            DitherMode::None,
+        );
+        samples
+    };
+    let a = to_f32(a);
+    let b = to_f32(b);
+
+    let channels = channels as usize;
+    let correlation = |x: usize, y: usize| {
+        let mut xy = 0.0f64;
+        let mut xx = 0.0f64;
+        let mut yy = 0.0f64;
+        for frame in 0..frames {
+            let sx = a[frame * channels + x] as f64;
+            let sy = b[frame * channels + y] as f64;
+            xy += sx * sy;
+            xx += sx * sx;
+            yy += sy * sy;
+        }
+        if xx == 0.0 || yy == 0.0 {
+            0.0
+        } else {
+            xy / (xx * yy).sqrt()
+        }
+    };
+
+    (0..channels).any(|x| {
+        let same = correlation(x, x);
+        (0..channels).any(|y| y != x && correlation(x, y) > same + CHANNEL_SWAP_MARGIN)
+    })
+}
+
+/// How much better another channel has to correlate for `detect_channel_swap` to report a swap.
+const CHANNEL_SWAP_MARGIN: f64 = 0.1;
+
+fn debug_assert_layout(ptr: *const u8, len: usize, format: Format, channels: u32) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+
+    assert!(format != Format::Unknown, "frames have an unknown format");
+    assert!(
+        channels >= 1 && channels as usize <= MAX_CHANNELS,
+        "frames have an invalid channel count ({})",
+        channels
+    );
+
+    let frame_size = bytes_per_frame(format, channels);
+    assert!(
+        len / frame_size * frame_size == len,
+        "frames data is not a whole number of frames ({} bytes with {} bytes per frame)",
+        len,
+        frame_size
+    );
+
+    let align = match format {
+        Format::S16 => std::mem::align_of::<i16>(),
+        Format::S32 => std::mem::align_of::<i32>(),
+        Format::F32 => std::mem::align_of::<f32>(),
+        _ => 1,
+    };
+    assert!(
+        ptr as usize & (align - 1) == 0,
+        "frames data is not aligned for {:?} samples",
+        format
+    );
+}
+
+fn assert_aligned<S>(ptr: *const u8) {
+    assert!(
+        ptr as usize & (std::mem::align_of::<S>() - 1) == 0,
+        "frames data is not aligned for the requested sample type"
+    );
+}
+
+/// Reads the sample at `frame` and `channel` of interleaved frames normalized to `[-1, 1]`.
+fn read_sample_f32(data: &[u8], format: Format, channels: u32, frame: usize, channel: u32) -> f32 {
+    assert!(
+        channel < channels,
+        "channel out of range (channels: {}, requested: {})",
+        channels,
+        channel
+    );
+
+    let size = format.size_in_bytes();
+    let offset = (frame * channels as usize + channel as usize) * size;
+    let bytes = &data[offset..(offset + size)];
+    match format {
+        Format::U8 => (bytes[0] as f32 - 128.0) / 128.0,
+        Format::S16 => i16::from_ne_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+        Format::S24 => read_s24(bytes) as f32 / 8_388_608.0,
+        Format::S32 => {
+            (i32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64 / 2_147_483_648.0)
+                as f32
+        }
+        Format::F32 => f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        Format::Unknown => panic!("cannot read samples of an unknown format"),
+    }
+}
+
+/// Returns the byte offset of a packed 24-bit sample.
+fn s24_offset(format: Format, channels: u32, frame: usize, channel: u32) -> usize {
+    assert!(
+        format == Format::S24,
+        "format mismatch (frames: {:?}, requested: {:?})",
+        format,
+        Format::S24
+    );
+    assert!(
+        channel < channels,
+        "channel out of range (channels: {}, requested: {})",
+        channels,
+        channel
+    );
+
+    (frame * channels as usize + channel as usize) * 3
+}
+
+#[inline]
+pub(crate) fn read_s24(bytes: &[u8]) -> i32 {
+    i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8
+}
+
+#[inline]
+pub(crate) fn write_s24(bytes: &mut [u8], value: i32) {
+    let value = value.clamp(-8_388_608, 8_388_607);
+    bytes.copy_from_slice(&value.to_le_bytes()[..3]);
+}
+
+/// An owned buffer of frames.
+///
+/// The samples are stored with an alignment of 4 bytes so that the buffer can be viewed as samples
+/// of any format.
+#[derive(Clone)]
+pub struct FramesBuffer {
+    data: Vec<u32>,
+    byte_count: usize,
+    format: Format,
+    channels: u32,
+}
+
+impl FramesBuffer {
+    /// Creates a buffer containing `frame_count` frames of silence.
+    pub fn new(format: Format, channels: u32, frame_count: usize) -> FramesBuffer {
+        let byte_count = frame_count * channels as usize * format.size_in_bytes();
+        let silence = if format == Format::U8 { 0x80808080 } else { 0 };
+
+        FramesBuffer {
+            data: vec![silence; byte_count.div_ceil(4)],
+            byte_count,
+            format,
+            channels,
+        }
+    }
+
+    #[inline]
+    pub fn frames(&self) -> Frames<'_> {
+        Frames::wrap::<u8>(self.as_bytes(), self.format, self.channels)
+    }
+
+    #[inline]
+    pub fn frames_mut(&mut self) -> FramesMut<'_> {
+        let (format, channels) = (self.format, self.channels);
+        FramesMut::wrap::<u8>(self.as_bytes_mut(), format, channels)
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.data.as_ptr().cast::<u8>(), self.byte_count) }
+    }
+
+    #[inline]
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            std::slice::from_raw_parts_mut(self.data.as_mut_ptr().cast::<u8>(), self.byte_count)
+        }
+    }
+
+    /// Returns the number of frames contained.
+    #[inline]
+    pub fn frame_count(&self) -> usize {
+        self.frames().frame_count()
+    }
+
+    #[inline]
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
     #[inline]
     pub fn channels(&self) -> u32 {
         self.channels
     }
+
+    /// Creates a buffer from an array with one row per frame and one column per channel.
+    ///
+    /// The samples are expected to be normalized to the range [-1, 1] and are converted to
+    /// `format` the same way as in `Frames::convert`. Any memory layout is accepted, rows are
+    /// always read as frames.
+    #[cfg(feature = "ndarray")]
+    pub fn from_array2<A>(
+        array: &ndarray::ArrayBase<A, ndarray::Ix2>,
+        format: Format,
+    ) -> FramesBuffer
+    where
+        A: ndarray::Data<Elem = f32>,
+    {
+        let channels = array.ncols() as u32;
+        let samples: Vec<f32> = array.iter().copied().collect();
+
+        let mut buffer = FramesBuffer::new(format, channels, array.nrows());
+        Frames::wrap(&samples[..], Format::F32, channels)
+            .convert(&mut buffer.frames_mut(), DitherMode::None);
+        buffer
+    }
+
+    /// Creates a buffer in `format` from `dasp` frames of any sample type. Samples are converted
+    /// through `f32`.
+    #[cfg(feature = "dasp")]
+    pub fn from_dasp_frames<I, F>(frames: I, format: Format) -> FramesBuffer
+    where
+        I: IntoIterator<Item = F>,
+        F: dasp_frame::Frame,
+        F::Sample: dasp_sample::ToSample<f32>,
+    {
+        let mut samples = Vec::new();
+        for frame in frames {
+            samples.extend(frame.channels().map(dasp_sample::ToSample::to_sample_));
+        }
+
+        let channels = F::CHANNELS as u32;
+        let frame_count = samples.len().checked_div(F::CHANNELS).unwrap_or(0);
+        let mut buffer = FramesBuffer::new(format, channels, frame_count);
+        Frames::wrap(&samples[..], Format::F32, channels)
+            .convert(&mut buffer.frames_mut(), DitherMode::None);
+        buffer
+    }
+}
+
+/// Checks that a slice of samples is a whole number of frames with a valid channel count.
+fn check_interleaved_len(sample_count: usize, channels: u32) -> Result<(), Error> {
+    if channels == 0 || channels as usize > MAX_CHANNELS {
+        ma_debug_panic!("invalid channel count for frames ({})", channels);
+        return Err(Error::InvalidArgs);
+    }
+
+    if sample_count / channels as usize * channels as usize != sample_count {
+        ma_debug_panic!(
+            "{} samples are not a whole number of frames with {} channels",
+            sample_count,
+            channels
+        );
+        return Err(Error::InvalidArgs);
+    }
+
+    Ok(())
+}
+
+/// Wraps interleaved samples with a channel count, using the format of the sample type. This
+/// returns `Error::InvalidArgs` if the number of samples is not a multiple of the channel count.
+impl<'s, S: Sample> TryFrom<(&'s [S], u32)> for Frames<'s> {
+    type Error = Error;
+
+    fn try_from((samples, channels): (&'s [S], u32)) -> Result<Frames<'s>, Error> {
+        check_interleaved_len(samples.len(), channels)?;
+        Ok(Frames::wrap(samples, S::format(), channels))
+    }
+}
+
+/// Wraps interleaved samples with a channel count, using the format of the sample type. This
+/// returns `Error::InvalidArgs` if the number of samples is not a multiple of the channel count.
+impl<'s, S: Sample> TryFrom<(&'s mut [S], u32)> for FramesMut<'s> {
+    type Error = Error;
+
+    fn try_from((samples, channels): (&'s mut [S], u32)) -> Result<FramesMut<'s>, Error> {
+        check_interleaved_len(samples.len(), channels)?;
+        Ok(FramesMut::wrap(samples, S::format(), channels))
+    }
 }
 
 pub struct FramesIter<'s, S: Sample> {
@@ -325,3 +1131,309 @@ impl Sample for f32 {
         Format::F32
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn s24_samples_are_sign_extended_and_clamped() {
+        let mut bytes = [0u8; 12];
+        let mut frames = FramesMut::wrap(&mut bytes[..], Format::S24, 2);
+        frames.set_sample_i32(0, 0, -1);
+        frames.set_sample_i32(0, 1, 8_388_607);
+        frames.set_sample_i32(1, 0, i32::MIN);
+        frames.set_sample_i32(1, 1, 0x0012_3456);
+
+        assert_eq!(frames.sample_i32(0, 0), -1);
+        assert_eq!(frames.sample_i32(0, 1), 8_388_607);
+        assert_eq!(frames.sample_i32(1, 0), -8_388_608);
+        assert_eq!(frames.sample_i32(1, 1), 0x0012_3456);
+
+        // The samples are packed little-endian without padding.
+        assert_eq!(&bytes[9..12], &[0x56, 0x34, 0x12]);
+        let frames = Frames::wrap(&bytes[..], Format::S24, 2);
+        assert_eq!(frames.frame_count(), 2);
+        assert_eq!(frames.sample_i32(1, 1), 0x0012_3456);
+    }
+
+    #[test]
+    #[should_panic(expected = "format mismatch")]
+    fn s24_accessors_check_the_format() {
+        let samples = [0i16; 4];
+        Frames::wrap(&samples[..], Format::S16, 2).sample_i32(0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "not aligned")]
+    fn as_samples_checks_the_alignment() {
+        let samples = [0f32; 4];
+        let frames = Frames::wrap(&samples[..], Format::F32, 1);
+        Frames::wrap(&frames.as_bytes()[1..13], Format::F32, 1).as_samples::<f32>();
+    }
+
+    #[test]
+    fn frame_sizes_follow_the_format_and_channels() {
+        assert_eq!(bytes_per_frame(Format::U8, 1), 1);
+        assert_eq!(bytes_per_frame(Format::S16, 2), 4);
+        assert_eq!(bytes_per_frame(Format::S24, 2), 6);
+        assert_eq!(bytes_per_frame(Format::F32, 6), 24);
+
+        let bytes = [0u8; 25];
+        assert_eq!(frame_count_from_bytes(&bytes, Format::S24, 2), 4);
+        assert_eq!(frame_count_from_bytes(&bytes, Format::F32, 6), 1);
+        assert_eq!(frame_count_from_bytes(&bytes, Format::Unknown, 2), 0);
+        assert_eq!(frame_count_from_bytes(&bytes, Format::S16, 0), 0);
+    }
+
+    #[test]
+    fn durations_and_frame_counts_round_trip() {
+        use std::time::Duration;
+
+        assert_eq!(duration_to_frames(Duration::from_secs(2), 48000), 96000);
+        assert_eq!(duration_to_frames(Duration::from_millis(10), 44100), 441);
+        assert_eq!(duration_to_frames(Duration::from_micros(11), 48000), 1);
+
+        assert_eq!(frames_to_duration(96000, 48000), Duration::from_secs(2));
+        assert_eq!(frames_to_duration(441, 44100), Duration::from_millis(10));
+        assert_eq!(frames_to_duration(1, 0), Duration::from_secs(0));
+
+        // Rounding to the nearest nanosecond and frame gets back to the same frame count.
+        for &frames in &[1, 7, 44099, 44101, 12_345_678] {
+            let duration = frames_to_duration(frames, 44100);
+            assert_eq!(duration_to_frames(duration, 44100), frames);
+        }
+    }
+
+    /// Stereo frames with a sine on the left channel and a ramp on the right one.
+    fn distinct_stereo(frame_count: usize) -> Vec<f32> {
+        (0..frame_count)
+            .flat_map(|i| {
+                let left = (i as f32 * 0.05).sin();
+                let right = (i % 50) as f32 / 25.0 - 1.0;
+                vec![left, right]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detect_channel_swap_finds_swapped_channels() {
+        let a = distinct_stereo(1000);
+        let swapped: Vec<f32> = a
+            .chunks_exact(2)
+            .flat_map(|frame| vec![frame[1], frame[0]])
+            .collect();
+        let mono: Vec<f32> = a.iter().step_by(2).flat_map(|&s| vec![s, s]).collect();
+
+        let frames = Frames::wrap(&a[..], Format::F32, 2);
+        assert!(!detect_channel_swap(&frames, &frames));
+        assert!(detect_channel_swap(
+            &frames,
+            &Frames::wrap(&swapped[..], Format::F32, 2)
+        ));
+
+        let mono = Frames::wrap(&mono[..], Format::F32, 2);
+        assert!(!detect_channel_swap(&mono, &mono));
+        assert!(!detect_channel_swap(
+            &frames,
+            &Frames::wrap(&a[..], Format::F32, 1)
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "frames data is not a whole number of frames")
+    )]
+    fn assert_layout_checks_for_partial_frames() {
+        let samples = [0i16; 5];
+        Frames::wrap(&samples[..], Format::S16, 2).assert_layout();
+    }
+
+    #[test]
+    fn to_owned_copies_whole_frames() {
+        let owned = {
+            let samples = [1i16, 2, 3, 4, 5];
+            Frames::wrap(&samples[..], Format::S16, 2).to_owned()
+        };
+        assert_eq!(owned.format(), Format::S16);
+        assert_eq!(owned.channels(), 2);
+        assert_eq!(owned.frame_count(), 2);
+        assert_eq!(owned.frames().as_samples::<i16>(), &[1, 2, 3, 4]);
+
+        let mut samples = [0.5f32, -0.5];
+        let owned = FramesMut::wrap(&mut samples[..], Format::F32, 1).to_owned();
+        assert_eq!(owned.frames().as_samples::<f32>(), &[0.5, -0.5]);
+    }
+
+    #[test]
+    fn swap_sample_bytes_reverses_each_whole_sample() {
+        let foreign = if cfg!(target_endian = "little") {
+            ByteOrder::Big
+        } else {
+            ByteOrder::Little
+        };
+        assert!(ByteOrder::Native.is_native());
+        assert!(!foreign.is_native());
+
+        let mut bytes = [1u8, 2, 3, 4, 5, 6, 7];
+        swap_sample_bytes(&mut bytes, Format::S16, ByteOrder::Native);
+        assert_eq!(bytes, [1, 2, 3, 4, 5, 6, 7]);
+        swap_sample_bytes(&mut bytes, Format::S16, foreign);
+        assert_eq!(bytes, [2, 1, 4, 3, 6, 5, 7]);
+        swap_sample_bytes(&mut bytes, Format::S16, foreign);
+        assert_eq!(bytes, [1, 2, 3, 4, 5, 6, 7]);
+
+        swap_sample_bytes(&mut bytes, Format::S24, foreign);
+        assert_eq!(bytes, [3, 2, 1, 6, 5, 4, 7]);
+        swap_sample_bytes(&mut bytes, Format::U8, foreign);
+        assert_eq!(bytes, [3, 2, 1, 6, 5, 4, 7]);
+    }
+
+    #[test]
+    fn channel_iterators_walk_one_channel() {
+        let mut samples = [0i16, 1, 10, 11, 20, 21];
+        let frames = Frames::wrap(&samples[..], Format::S16, 2);
+        assert_eq!(
+            frames.channel::<i16>(0).copied().collect::<Vec<_>>(),
+            [0, 10, 20]
+        );
+        assert_eq!(
+            frames.channel::<i16>(1).copied().collect::<Vec<_>>(),
+            [1, 11, 21]
+        );
+
+        let mut frames = FramesMut::wrap(&mut samples[..], Format::S16, 2);
+        frames
+            .channel_mut::<i16>(1)
+            .for_each(|sample| *sample *= -1);
+        assert_eq!(
+            frames.channel::<i16>(0).copied().collect::<Vec<_>>(),
+            [0, 10, 20]
+        );
+        assert_eq!(samples, [0, -1, 10, -11, 20, -21]);
+    }
+
+    #[test]
+    #[should_panic(expected = "channel out of range")]
+    fn channel_iterators_check_the_channel() {
+        let samples = [0f32; 4];
+        let _ = Frames::wrap(&samples[..], Format::F32, 2).channel::<f32>(2);
+    }
+
+    #[test]
+    fn sample_f32_is_normalized_for_every_format() {
+        let u8_samples = [0u8, 128, 255];
+        let frames = Frames::wrap(&u8_samples[..], Format::U8, 1);
+        assert_eq!(frames.sample_f32(0, 0), -1.0);
+        assert_eq!(frames.sample_f32(1, 0), 0.0);
+        assert_eq!(frames.sample_f32(2, 0), 127.0 / 128.0);
+
+        let s16_samples = [i16::MIN, 16384];
+        let frames = Frames::wrap(&s16_samples[..], Format::S16, 2);
+        assert_eq!(frames.sample_f32(0, 0), -1.0);
+        assert_eq!(frames.sample_f32(0, 1), 0.5);
+
+        let mut s24_bytes = [0u8; 6];
+        let mut frames = FramesMut::wrap(&mut s24_bytes[..], Format::S24, 1);
+        frames.set_sample_i32(0, 0, -8_388_608);
+        frames.set_sample_i32(1, 0, 4_194_304);
+        assert_eq!(frames.sample_f32(0, 0), -1.0);
+        assert_eq!(frames.sample_f32(1, 0), 0.5);
+
+        let s32_samples = [i32::MIN, 1 << 30];
+        let frames = Frames::wrap(&s32_samples[..], Format::S32, 1);
+        assert_eq!(frames.sample_f32(0, 0), -1.0);
+        assert_eq!(frames.sample_f32(1, 0), 0.5);
+
+        let f32_samples = [0.25f32, -2.0];
+        let frames = Frames::wrap(&f32_samples[..], Format::F32, 1);
+        assert_eq!(frames.sample_f32(0, 0), 0.25);
+        assert_eq!(frames.sample_f32(1, 0), -2.0);
+    }
+
+    #[test]
+    fn find_sound_bounds_trims_silence_on_any_channel() {
+        // -40 dB is a gain of 0.01.
+        let mut samples = [0.0f32; 20];
+        samples[7] = 0.001;
+        samples[9] = 0.5;
+        samples[14] = -0.02;
+        samples[17] = 0.005;
+        let frames = Frames::wrap(&samples[..], Format::F32, 2);
+        assert_eq!(frames.find_sound_bounds(-40.0), (4, 8));
+        assert_eq!(frames.find_sound_bounds(-70.0), (3, 9));
+        assert_eq!(frames.find_sound_bounds(0.0), (0, 0));
+
+        let silence = [0i16; 10];
+        assert_eq!(
+            Frames::wrap(&silence[..], Format::S16, 1).find_sound_bounds(-90.0),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn expand_to_places_mono_frames_on_the_mapped_channels() {
+        let mono = [0.5f32, -0.25];
+        let expanded = Frames::wrap(&mono[..], Format::F32, 1)
+            .expand_to(&[0.0, 0.0, 1.0, 0.5])
+            .unwrap();
+        assert_eq!(expanded.format(), Format::F32);
+        assert_eq!(expanded.channels(), 4);
+        assert_eq!(
+            expanded.frames().as_samples::<f32>(),
+            &[0.0, 0.0, 0.5, 0.25, 0.0, 0.0, -0.25, -0.125]
+        );
+
+        // Integer samples keep their format and are clipped.
+        let mono = [16384i16];
+        let expanded = Frames::wrap(&mono[..], Format::S16, 1)
+            .expand_to(&[1.0, 4.0])
+            .unwrap();
+        let samples = expanded.frames();
+        let samples = samples.as_samples::<i16>();
+        assert!((samples[0] - 16384).abs() <= 1);
+        assert_eq!(samples[1], 32767);
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "only mono frames can be expanded")
+    )]
+    fn expand_to_rejects_frames_that_are_not_mono() {
+        let stereo = [0.0f32; 4];
+        assert!(matches!(
+            Frames::wrap(&stereo[..], Format::F32, 2).expand_to(&[1.0, 1.0]),
+            Err(Error::InvalidArgs)
+        ));
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn stereo_frames_round_trip_through_array2() {
+        let samples = distinct_stereo(1000);
+        let array = ndarray::Array2::from_shape_vec((1000, 2), samples.clone()).unwrap();
+
+        let buffer = FramesBuffer::from_array2(&array, Format::F32);
+        assert_eq!(buffer.channels(), 2);
+        assert_eq!(buffer.frame_count(), 1000);
+        assert_eq!(buffer.frames().as_samples::<f32>(), &samples[..]);
+        assert_eq!(buffer.frames().to_array2(), array);
+
+        // Integer samples are scaled to and from the range [-1, 1].
+        let buffer = FramesBuffer::from_array2(&array, Format::S16);
+        assert_eq!(buffer.frame_count(), 1000);
+        for (&s16, &f32) in buffer.frames().as_samples::<i16>().iter().zip(&samples) {
+            assert!((s16 as f32 / 32768.0 - f32).abs() <= 1.0 / 16384.0);
+        }
+
+        let round_trip = buffer.frames().to_array2();
+        assert_eq!(round_trip.dim(), (1000, 2));
+        assert_eq!(round_trip[[0, 0]], 0.0);
+        assert!(round_trip
+            .iter()
+            .zip(array.iter())
+            .all(|(a, b)| (a - b).abs() <= 1.0 / 16384.0));
+    }
+}
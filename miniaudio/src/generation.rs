@@ -1,5 +1,5 @@
-use crate::base::{from_bool32, to_bool32, Format};
-use crate::frames::FramesMut;
+use crate::base::{from_bool32, to_bool32, DitherMode, Error, Format};
+use crate::frames::{Frames, FramesBuffer, FramesMut};
 use miniaudio_sys as sys;
 
 #[repr(C)]
@@ -323,3 +323,127 @@ impl Noise {
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SweepType {
+    /// The frequency changes by the same number of hertz every second.
+    Linear,
+    /// The frequency changes by the same number of octaves every second. This spends equal time
+    /// on every octave which is usually what you want for measuring frequency responses.
+    Logarithmic,
+}
+
+/// Generates a full scale sine sweep from `start_hz` to `end_hz` that is `duration_frames` long
+/// and has the same signal on every channel.
+///
+/// The phase is calculated from the position in the sweep instead of being accumulated, so the
+/// sweep is exact for any sample rate and duration. `InvalidArgs` is returned for 0 channels or a
+/// sample rate of 0, and for logarithmic sweeps unless both frequencies are greater than 0.
+pub fn sweep(
+    format: Format,
+    channels: u32,
+    sample_rate: u32,
+    sweep_type: SweepType,
+    start_hz: f64,
+    end_hz: f64,
+    duration_frames: u64,
+) -> Result<FramesBuffer, Error> {
+    if channels == 0 || sample_rate == 0 {
+        ma_debug_panic!(
+            "sweep needs at least one channel and a sample rate (channels: {}, sample rate: {})",
+            channels,
+            sample_rate
+        );
+        return Err(Error::InvalidArgs);
+    }
+
+    if sweep_type == SweepType::Logarithmic && (start_hz <= 0.0 || end_hz <= 0.0) {
+        ma_debug_panic!(
+            "logarithmic sweep frequencies must be greater than 0 (start: {}, end: {})",
+            start_hz,
+            end_hz
+        );
+        return Err(Error::InvalidArgs);
+    }
+
+    let duration = duration_frames as f64 / sample_rate as f64;
+    let growth = (end_hz / start_hz).ln();
+    let phase_at = |time: f64| match sweep_type {
+        SweepType::Logarithmic if growth.abs() > f64::EPSILON => {
+            start_hz * duration / growth * ((time / duration * growth).exp() - 1.0)
+        }
+        _ => start_hz * time + (end_hz - start_hz) * time * time / (2.0 * duration),
+    };
+
+    let channels_usize = channels as usize;
+    let mut samples = vec![0.0f32; duration_frames as usize * channels_usize];
+    for (index, frame) in samples.chunks_exact_mut(channels_usize).enumerate() {
+        let phase = phase_at(index as f64 / sample_rate as f64);
+        let sample = (std::f64::consts::TAU * phase.fract()).sin() as f32;
+        frame.iter_mut().for_each(|s| *s = sample);
+    }
+
+    let mut buffer = FramesBuffer::new(format, channels, duration_frames as usize);
+    Frames::wrap(&samples[..], Format::F32, channels)
+        .convert(&mut buffer.frames_mut(), DitherMode::None);
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the frequency of the first channel of `samples` over the `window` frames starting
+    /// at `start`, estimated from the number of zero crossings.
+    fn zero_crossing_hz(samples: &[f32], channels: usize, start: usize, window: usize) -> f64 {
+        let frames: Vec<f32> = samples
+            .chunks_exact(channels)
+            .skip(start)
+            .take(window + 1)
+            .map(|frame| frame[0])
+            .collect();
+        let crossings = frames
+            .windows(2)
+            .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+            .count();
+        crossings as f64 / 2.0 / (window as f64 / 48000.0)
+    }
+
+    #[test]
+    fn sweep_rises_from_the_start_to_the_end_frequency() {
+        for &sweep_type in &[SweepType::Linear, SweepType::Logarithmic] {
+            let buffer = sweep(Format::F32, 2, 48000, sweep_type, 100.0, 4000.0, 48000).unwrap();
+            assert_eq!(buffer.frame_count(), 48000);
+            let frames = buffer.frames();
+            let samples = frames.as_samples::<f32>();
+            assert!(samples.chunks_exact(2).all(|frame| frame[0] == frame[1]));
+
+            // Ten windows of 100ms, whose zero crossings only go up.
+            let rates: Vec<f64> = (0..10)
+                .map(|window| zero_crossing_hz(samples, 2, window * 4800, 4800))
+                .collect();
+            assert!(
+                rates.windows(2).all(|pair| pair[1] > pair[0]),
+                "{:?}: {:?}",
+                sweep_type,
+                rates
+            );
+
+            // 20ms at either end are close to the start and end frequency.
+            let start = zero_crossing_hz(samples, 2, 0, 960);
+            let end = zero_crossing_hz(samples, 2, 48000 - 961, 960);
+            assert!((start - 100.0).abs() < 30.0, "{:?}: {}", sweep_type, start);
+            assert!((end - 4000.0).abs() < 200.0, "{:?}: {}", sweep_type, end);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "sweep needs at least one channel")
+    )]
+    fn sweep_rejects_zero_channels() {
+        let result = sweep(Format::F32, 0, 48000, SweepType::Linear, 100.0, 1000.0, 100);
+        assert!(matches!(result, Err(Error::InvalidArgs)));
+    }
+}
@@ -18,6 +18,15 @@ macro_rules! ma_debug_panic {
     };
 }
 
+/// Enters a `tracing` span at the trace level for the rest of the enclosing block. This expands
+/// to nothing when the `tracing` feature is disabled.
+macro_rules! ma_trace_span {
+    ($($Arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!($($Arg)*).entered();
+    };
+}
+
 /// This macro will execute a success block if a result is a MA_SUCCESS block
 /// and return the value of that block wrapped in a Result::Ok. If $Result is an error this will
 /// return an Error enum wrapped in a Result::Err.
@@ -41,27 +50,40 @@ macro_rules! impl_from_c {
     };
 }
 
+mod analysis;
 mod base;
 mod channel_conv;
 mod conversion;
 mod data_conv;
+mod data_source;
 mod decoder;
 mod device_io;
+mod effects;
+mod encoder;
 mod filters;
 mod frames;
 mod generation;
 mod lock;
+mod mixing;
 mod resampling;
 mod ring_buffers;
+mod sinc;
+mod transcoding;
 
+pub use analysis::*;
 pub use base::*;
 pub use channel_conv::*;
 pub use conversion::*;
 pub use data_conv::*;
+pub use data_source::*;
 pub use decoder::*;
 pub use device_io::*;
+pub use effects::*;
+pub use encoder::*;
 pub use filters::*;
 pub use frames::*;
 pub use generation::*;
+pub use mixing::*;
 pub use resampling::*;
 pub use ring_buffers::*;
+pub use transcoding::*;
@@ -0,0 +1,333 @@
+use crate::base::{DitherMode, Error, Format};
+use crate::decoder::Decoder;
+use crate::frames::{Frames, FramesMut};
+use crate::generation::{Noise, Waveform};
+
+/// Something that can produce frames for a `Mixer`.
+///
+/// The mixer always asks for `f32` frames with the mixer's channel count.
+pub trait MixerSource {
+    /// Reads up to `output.frame_count()` frames into `output` and returns the number of frames
+    /// that were read. Returning less than the requested number of frames means that the source
+    /// has ended.
+    fn read_pcm_frames(&mut self, output: &mut FramesMut) -> u64;
+}
+
+impl MixerSource for Waveform {
+    fn read_pcm_frames(&mut self, output: &mut FramesMut) -> u64 {
+        Waveform::read_pcm_frames(self, output)
+    }
+}
+
+impl MixerSource for Noise {
+    fn read_pcm_frames(&mut self, output: &mut FramesMut) -> u64 {
+        Noise::read_pcm_frames(self, output)
+    }
+}
+
+/// The decoder must be configured to output `f32` with the mixer's channel count.
+impl MixerSource for Decoder {
+    fn read_pcm_frames(&mut self, output: &mut FramesMut) -> u64 {
+        (**self).read_pcm_frames(output)
+    }
+}
+
+/// Identifies a source that was added to a `Mixer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MixerSourceId(u64);
+
+struct MixerSlot {
+    id: MixerSourceId,
+    source: Box<dyn MixerSource>,
+    gain: f32,
+    pan: f32,
+}
+
+/// Sums the output of several sources into a single buffer without needing a full audio graph.
+///
+/// Mixing is done in `f32` and the result is converted into the format of the output buffer. For
+/// integer formats the conversion saturates so loud mixes will clip instead of wrapping around.
+/// For `f32` output the mix is written as is and may go above 1.0.
+pub struct Mixer {
+    channels: u32,
+    next_id: u64,
+    slots: Vec<MixerSlot>,
+    read_buffer: Vec<f32>,
+    mix_buffer: Vec<f32>,
+}
+
+impl Mixer {
+    pub fn new(channels: u32) -> Mixer {
+        Mixer {
+            channels,
+            next_id: 0,
+            slots: Vec::new(),
+            read_buffer: Vec::new(),
+            mix_buffer: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn channels(&self) -> u32 {
+        self.channels
+    }
+
+    /// Returns the number of sources in the mixer.
+    #[inline]
+    pub fn source_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Adds a source with a gain of 1.0 and centered pan.
+    pub fn add_source(&mut self, source: Box<dyn MixerSource>) -> MixerSourceId {
+        let id = MixerSourceId(self.next_id);
+        self.next_id += 1;
+        self.slots.push(MixerSlot {
+            id,
+            source,
+            gain: 1.0,
+            pan: 0.0,
+        });
+        id
+    }
+
+    /// Removes a source from the mixer, returning it if it was found.
+    pub fn remove_source(&mut self, id: MixerSourceId) -> Option<Box<dyn MixerSource>> {
+        let index = self.slots.iter().position(|slot| slot.id == id)?;
+        Some(self.slots.remove(index).source)
+    }
+
+    /// Sets the linear gain that is applied to a source.
+    pub fn set_source_gain(&mut self, id: MixerSourceId, gain: f32) -> Result<(), Error> {
+        self.slot_mut(id).map(|slot| slot.gain = gain)
+    }
+
+    /// Sets the pan of a source between -1 (left) and 1 (right). Panning attenuates the opposite
+    /// channel, so it is only supported by stereo mixers and `Error::InvalidArgs` is returned for
+    /// any other channel count.
+    pub fn set_source_pan(&mut self, id: MixerSourceId, pan: f32) -> Result<(), Error> {
+        if self.channels != 2 {
+            ma_debug_panic!(
+                "pan is only supported by stereo mixers (channels: {})",
+                self.channels
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        self.slot_mut(id)
+            .map(|slot| slot.pan = pan.clamp(-1.0, 1.0))
+    }
+
+    fn slot_mut(&mut self, id: MixerSourceId) -> Result<&mut MixerSlot, Error> {
+        self.slots
+            .iter_mut()
+            .find(|slot| slot.id == id)
+            .ok_or(Error::DoesNotExist)
+    }
+
+    /// Reads from every source and mixes the results into `output`, overwriting its contents.
+    ///
+    /// Returns the largest number of frames that any source produced. Frames that no source
+    /// produced are silent. Sources that produce fewer frames than `output` can hold have ended
+    /// and are removed from the mixer after their last frames were mixed, so their ids are no
+    /// longer valid afterwards.
+    pub fn mix_into(&mut self, output: &mut FramesMut) -> Result<u64, Error> {
+        if output.channels() != self.channels {
+            ma_debug_panic!(
+                "output channels did not match mixer (output: {}, mixer: {})",
+                output.channels(),
+                self.channels
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        let channels = self.channels;
+        let sample_count = output.sample_count();
+
+        self.read_buffer.resize(sample_count, 0.0);
+        self.mix_buffer.clear();
+        self.mix_buffer.resize(sample_count, 0.0);
+
+        let frame_count = output.frame_count() as u64;
+        let read_buffer = &mut self.read_buffer;
+        let mix_buffer = &mut self.mix_buffer;
+        let mut frames_mixed = 0;
+        self.slots.retain_mut(|slot| {
+            let frames_read = slot.source.read_pcm_frames(&mut FramesMut::wrap(
+                &mut read_buffer[..],
+                Format::F32,
+                channels,
+            ));
+            frames_mixed = frames_mixed.max(frames_read);
+
+            // Only stereo mixers have a pan, so every other channel count uses the gain as is.
+            let (left_gain, right_gain) = if channels == 2 {
+                (
+                    slot.gain * (1.0 - slot.pan).min(1.0),
+                    slot.gain * (1.0 + slot.pan).min(1.0),
+                )
+            } else {
+                (slot.gain, slot.gain)
+            };
+
+            let samples_read = frames_read as usize * channels as usize;
+            for (index, (mixed, &sample)) in mix_buffer
+                .iter_mut()
+                .zip(read_buffer[..samples_read].iter())
+                .enumerate()
+            {
+                let gain = if index % 2 == 0 {
+                    left_gain
+                } else {
+                    right_gain
+                };
+                *mixed += sample * gain;
+            }
+
+            frames_read == frame_count
+        });
+
+        Frames::wrap(&self.mix_buffer[..], Format::F32, channels).convert(output, DitherMode::None);
+
+        Ok(frames_mixed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Produces `remaining` frames of a constant value and then ends.
+    struct Constant {
+        value: f32,
+        remaining: usize,
+    }
+
+    impl MixerSource for Constant {
+        fn read_pcm_frames(&mut self, output: &mut FramesMut) -> u64 {
+            let channels = output.channels() as usize;
+            let frames = output.frame_count().min(self.remaining);
+            output.as_samples_mut::<f32>()[..(frames * channels)]
+                .iter_mut()
+                .for_each(|sample| *sample = self.value);
+            self.remaining -= frames;
+            frames as u64
+        }
+    }
+
+    #[test]
+    fn ended_sources_are_removed() {
+        let mut mixer = Mixer::new(1);
+        let short = mixer.add_source(Box::new(Constant {
+            value: 0.25,
+            remaining: 6,
+        }));
+        mixer.add_source(Box::new(Constant {
+            value: 0.5,
+            remaining: usize::MAX,
+        }));
+
+        let mut output = [0f32; 4];
+        let frames = mixer.mix_into(&mut FramesMut::wrap(&mut output[..], Format::F32, 1));
+        assert_eq!(frames, Ok(4));
+        assert_eq!(output, [0.75; 4]);
+        assert_eq!(mixer.source_count(), 2);
+
+        let frames = mixer.mix_into(&mut FramesMut::wrap(&mut output[..], Format::F32, 1));
+        assert_eq!(frames, Ok(4));
+        assert_eq!(output, [0.75, 0.75, 0.5, 0.5]);
+        assert_eq!(mixer.source_count(), 1);
+        assert!(mixer.remove_source(short).is_none());
+    }
+
+    /// The magnitude of the `frequency` component of one channel of `samples`, normalized so that
+    /// a sine with an amplitude of 1 at that frequency gives about 1.
+    fn tone_magnitude(samples: &[f32], channels: usize, channel: usize, frequency: f64) -> f64 {
+        let step = 2.0 * std::f64::consts::PI * frequency / 48000.0;
+        let (re, im, count) = samples.iter().skip(channel).step_by(channels).fold(
+            (0.0, 0.0, 0),
+            |(re, im, i), &s| {
+                let phase = step * i as f64;
+                (
+                    re + s as f64 * phase.cos(),
+                    im - s as f64 * phase.sin(),
+                    i + 1,
+                )
+            },
+        );
+        2.0 * (re * re + im * im).sqrt() / count as f64
+    }
+
+    /// Produces a sine with an amplitude of 1 at 48kHz on every channel and never ends.
+    struct Sine {
+        frequency: f64,
+        frame: u64,
+    }
+
+    impl MixerSource for Sine {
+        fn read_pcm_frames(&mut self, output: &mut FramesMut) -> u64 {
+            let channels = output.channels() as usize;
+            let step = 2.0 * std::f64::consts::PI * self.frequency / 48000.0;
+            for frame in output.as_samples_mut::<f32>().chunks_exact_mut(channels) {
+                let sample = (step * self.frame as f64).sin() as f32;
+                frame.iter_mut().for_each(|s| *s = sample);
+                self.frame += 1;
+            }
+            output.frame_count() as u64
+        }
+    }
+
+    fn sine(frequency: f64) -> Box<dyn MixerSource> {
+        Box::new(Sine {
+            frequency,
+            frame: 0,
+        })
+    }
+
+    #[test]
+    fn sources_are_mixed_with_their_gains() {
+        let mut mixer = Mixer::new(1);
+        let low = mixer.add_source(sine(440.0));
+        let high = mixer.add_source(sine(3000.0));
+        mixer.set_source_gain(low, 0.5).unwrap();
+        mixer.set_source_gain(high, 0.25).unwrap();
+
+        // 100ms, which is a whole number of periods of both tones.
+        let mut output = vec![0f32; 4800];
+        let frames = mixer.mix_into(&mut FramesMut::wrap(&mut output[..], Format::F32, 1));
+        assert_eq!(frames, Ok(4800));
+
+        assert!((tone_magnitude(&output, 1, 0, 440.0) - 0.5).abs() < 0.01);
+        assert!((tone_magnitude(&output, 1, 0, 3000.0) - 0.25).abs() < 0.01);
+        assert!(tone_magnitude(&output, 1, 0, 1000.0) < 0.01);
+    }
+
+    #[test]
+    fn panned_sources_only_reach_their_side() {
+        let mut mixer = Mixer::new(2);
+        let left = mixer.add_source(sine(440.0));
+        let right = mixer.add_source(sine(3000.0));
+        mixer.set_source_pan(left, -1.0).unwrap();
+        mixer.set_source_pan(right, 1.0).unwrap();
+
+        let mut output = vec![0f32; 2 * 4800];
+        let frames = mixer.mix_into(&mut FramesMut::wrap(&mut output[..], Format::F32, 2));
+        assert_eq!(frames, Ok(4800));
+
+        assert!((tone_magnitude(&output, 2, 0, 440.0) - 1.0).abs() < 0.01);
+        assert!(tone_magnitude(&output, 2, 0, 3000.0) < 0.01);
+        assert!(tone_magnitude(&output, 2, 1, 440.0) < 0.01);
+        assert!((tone_magnitude(&output, 2, 1, 3000.0) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "pan is only supported by stereo mixers")
+    )]
+    fn pan_is_rejected_by_mixers_that_are_not_stereo() {
+        let mut mixer = Mixer::new(1);
+        let id = mixer.add_source(sine(440.0));
+        assert_eq!(mixer.set_source_pan(id, 0.5), Err(Error::InvalidArgs));
+    }
+}
@@ -1,21 +1,230 @@
-use crate::base::{Error, Format};
-use crate::frames::{Frames, FramesMut};
+use crate::base::{DitherMode, Error, Format, MAX_CHANNELS, MAX_FILTER_ORDER};
+use crate::device_io::{DeviceType, RawDevice};
+use crate::effects::FadeCurve;
+use crate::filters::check_filter_order;
+use crate::filters::low_pass_filtering::{LPFConfig, LPF};
+use crate::frames::{
+    bytes_per_frame, swap_sample_bytes, ByteOrder, Frames, FramesBuffer, FramesMut, Sample,
+};
+use crate::ring_buffers::RingBufferSend;
+use crate::sinc::SincResampler;
 use miniaudio_sys as sys;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Checks that the frames passed into a resampler have the format and channel count of the
+/// resampler's config. This catches frames that were wrapped with a different channel count than
+/// the one the resampler was created with, which would otherwise make miniaudio read or write past
+/// the end of the buffers.
+fn check_frames_match_config(
+    format: Format,
+    channels: u32,
+    output: &FramesMut,
+    input: &Frames,
+) -> Result<(), Error> {
+    if output.format() != format || input.format() != format {
+        ma_debug_panic!(
+            "output and input format did not match resampler config (output: {:?}, input: {:?}, config: {:?})",
+            output.format(),
+            input.format(),
+            format
+        );
+        return Err(Error::InvalidArgs);
+    }
+
+    if output.channels() != channels || input.channels() != channels {
+        ma_debug_panic!(
+            "output and input channels did not match resampler config (output: {}, input: {}, config: {})",
+            output.channels(),
+            input.channels(),
+            channels
+        );
+        return Err(Error::InvalidArgs);
+    }
+
+    Ok(())
+}
+
+/// The discriminant of `ResampleAlgorithmType::Sinc`. miniaudio does not know about this value, so
+/// it is never stored in a miniaudio config.
+const SINC_RESAMPLE_ALGORITHM: sys::ma_resample_algorithm = 0x100;
+
+/// The default window length of the sinc resampler in input frames.
+pub const DEFAULT_SINC_WINDOW_LENGTH: u32 = 32;
+
+/// The default beta of the Kaiser window of the sinc resampler.
+pub const DEFAULT_SINC_BETA: f64 = 8.6;
+
+/// The number of input frames that `Resampler::resample_buffer` processes between checks for
+/// cancellation.
+pub const RESAMPLE_BUFFER_CHUNK_FRAMES: usize = 4096;
+
+/// The smallest increase in progress that is reported by `Resampler::resample_buffer_with_progress`
+/// and `StreamingTranscoder`, which limits how often their progress callbacks are called.
+pub const PROGRESS_REPORT_STEP: f32 = 0.01;
+
+/// Throttles calls to a progress callback to steps of `PROGRESS_REPORT_STEP`.
+pub(crate) struct ProgressReporter<P: FnMut(f32)> {
+    on_progress: P,
+    last: Option<f32>,
+}
+
+impl<P: FnMut(f32)> ProgressReporter<P> {
+    pub(crate) fn new(on_progress: P) -> ProgressReporter<P> {
+        ProgressReporter {
+            on_progress,
+            last: None,
+        }
+    }
+
+    /// Reports `fraction` if it is at least `PROGRESS_REPORT_STEP` above the last report.
+    pub(crate) fn report(&mut self, fraction: f32) {
+        let fraction = fraction.clamp(0.0, 1.0);
+        let due = match self.last {
+            Some(last) => fraction >= last + PROGRESS_REPORT_STEP,
+            None => true,
+        };
+        if due {
+            self.last = Some(fraction);
+            (self.on_progress)(fraction);
+        }
+    }
+
+    /// Reports completion, unless that was reported already.
+    pub(crate) fn finish(&mut self) {
+        if self.last != Some(1.0) {
+            self.last = Some(1.0);
+            (self.on_progress)(1.0);
+        }
+    }
+}
+
+/// The size in bytes of each of the buffers that frames are converted into when the backend uses
+/// a different format than the resampler.
+const CONVERSION_CHUNK_BYTES: usize = 8192;
+
+/// Quantizes the `f32` output of a dithered `s16` or `u8` resampler.
+///
+/// miniaudio's own dither is not centered on the sample: its random number generator overflows
+/// into negative numbers, so it adds up to 3 LSB below the sample and only 1 LSB above it. This
+/// adds rectangular noise of ±0.5 LSB or triangular noise of ±1 LSB and rounds to the nearest
+/// step instead, so dithered samples stay within 1 LSB (1.5 LSB with triangular noise) of the
+/// exact result.
+struct OutputDither {
+    mode: DitherMode,
+    state: u32,
+}
+
+impl OutputDither {
+    fn new(mode: DitherMode, seed: u64) -> OutputDither {
+        OutputDither {
+            mode,
+            state: (seed as u32) ^ ((seed >> 32) as u32) ^ 0x9e37_79b9,
+        }
+    }
+
+    /// Returns a random number in [0, 1).
+    fn next(&mut self) -> f32 {
+        self.state = self
+            .state
+            .wrapping_mul(1_664_525)
+            .wrapping_add(1_013_904_223);
+        (self.state >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Returns the noise to add to a sample in LSB.
+    fn noise(&mut self) -> f32 {
+        match self.mode {
+            DitherMode::None => 0.0,
+            DitherMode::Rectangle => self.next() - 0.5,
+            DitherMode::Triangle => self.next() - self.next(),
+        }
+    }
+
+    /// Writes `input` into `output` in `format`, which is `S16` or `U8`.
+    fn quantize(&mut self, input: &[f32], output: &mut [u8], format: Format) {
+        match format {
+            Format::S16 => {
+                for (sample, dest) in input.iter().zip(output.chunks_exact_mut(2)) {
+                    let value = (sample * 32768.0 + self.noise()).round();
+                    dest.copy_from_slice(&(value.clamp(-32768.0, 32767.0) as i16).to_ne_bytes());
+                }
+            }
+            Format::U8 => {
+                for (sample, dest) in input.iter().zip(output.iter_mut()) {
+                    let value = (sample * 128.0 + 128.0 + self.noise()).round();
+                    *dest = value.clamp(0.0, 255.0) as u8;
+                }
+            }
+            _ => unreachable!("only s16 and u8 output is dithered"),
+        }
+    }
+}
+
+/// A sample type that `Resampler` can process, which is `u8`, `i16`, `i32` and `f32`.
+///
+/// `Frames` carry their format and channel count at runtime, so `process_pcm_frames` can only
+/// reject frames that don't match the config with `Error::InvalidArgs`. `Resampler::process_frames`
+/// takes typed frames instead, and uses this trait to turn passing a sample type that no
+/// resampler supports into a compile error. The channel count of the frames is checked at compile
+/// time as well: frames with no channels or more than `MAX_CHANNELS` fail to build. Whether the
+/// types match the format and channel count that the resampler was created with is only known at
+/// runtime, so that is still checked.
+///
+/// Implementing this for another `Sample` type compiles but makes `process_frames` return
+/// `Error::InvalidArgs`, since the resampler rejects every other format.
+pub trait ResamplerSample: Sample {}
+
+impl ResamplerSample for u8 {}
+impl ResamplerSample for i16 {}
+impl ResamplerSample for i32 {}
+impl ResamplerSample for f32 {}
+
+/// Checks a channel count that is known at compile time. Evaluating `VALID` fails to build for
+/// channel counts that miniaudio doesn't support.
+struct ChannelCount<const N: usize>;
+
+impl<const N: usize> ChannelCount<N> {
+    const VALID: () = assert!(
+        N >= 1 && N <= MAX_CHANNELS,
+        "frames must have between 1 and MAX_CHANNELS channels"
+    );
+}
 
 #[repr(C)]
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ResampleAlgorithmType {
     Linear = sys::ma_resample_algorithm_linear as _,
     Speex = sys::ma_resample_algorithm_speex as _,
+    Sinc = SINC_RESAMPLE_ALGORITHM as _,
 }
 impl_from_c!(ResampleAlgorithmType, sys::ma_resample_algorithm);
 
+impl ResampleAlgorithmType {
+    /// Returns true if this algorithm was compiled into this build. The Speex resampler is only
+    /// available when the `ma-enable-speex` feature is enabled.
+    #[inline]
+    pub fn is_available(self) -> bool {
+        match self {
+            ResampleAlgorithmType::Linear => true,
+            ResampleAlgorithmType::Speex => cfg!(feature = "ma-enable-speex"),
+            ResampleAlgorithmType::Sinc => true,
+        }
+    }
+}
+
 /// The choice of resampling algorithm depends on your situation and requirements.
 /// The linear resampler is the most efficient and has the least amount of latency,
 /// but at the expense of poorer quality. The Speex resampler is higher quality,
 /// but slower with more latency. It also performs several heap allocations internally
-/// for memory management.
-#[derive(Clone, Copy, PartialEq)]
+/// for memory management. The Speex resampler has to be enabled with the `ma-enable-speex`
+/// feature.
+///
+/// The sinc resampler sits in between the two. It is implemented in Rust instead of miniaudio, so
+/// it can only be used with a `Resampler` and not with a data converter or a device. Its latency
+/// is half of the window length in input frames and it never allocates after it was created.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ResampleAlgorithm {
     Linear {
         lpf_order: u32,
@@ -25,6 +234,16 @@ pub enum ResampleAlgorithm {
     Speex {
         quality: u32,
     },
+
+    /// A windowed-sinc resampler using a Kaiser window. `window_length` is the number of input
+    /// frames that are used for each output frame and must be an even number between 2 and 256.
+    /// Longer windows have a steeper low-pass filter at the cost of latency and processing time.
+    /// `beta` is the shape of the Kaiser window, higher values give more stopband attenuation but
+    /// a wider transition band.
+    Sinc {
+        window_length: u32,
+        beta: f64,
+    },
 }
 
 impl ResampleAlgorithm {
@@ -32,8 +251,16 @@ impl ResampleAlgorithm {
         match *self {
             ResampleAlgorithm::Linear { .. } => ResampleAlgorithmType::Linear,
             ResampleAlgorithm::Speex { .. } => ResampleAlgorithmType::Speex,
+            ResampleAlgorithm::Sinc { .. } => ResampleAlgorithmType::Sinc,
         }
     }
+
+    /// Returns true if this algorithm was compiled into this build. The Speex resampler is only
+    /// available when the `ma-enable-speex` feature is enabled.
+    #[inline]
+    pub fn is_available(&self) -> bool {
+        self.algorithm_type().is_available()
+    }
 }
 
 #[repr(transparent)]
@@ -102,18 +329,48 @@ impl LinearResamplerConfig {
         self.0.lpfOrder
     }
 
+    /// Orders above `max_filter_order()` are rejected by `LinearResampler::new`.
     #[inline]
     pub fn set_lpf_order(&mut self, order: u32) {
         self.0.lpfOrder = order;
     }
+
+    /// Sets the highest low-pass filter order whose latency fits into `max_latency_frames` input
+    /// frames, so that the quality can be picked by the latency that is acceptable rather than
+    /// by trying out orders.
+    ///
+    /// miniaudio reports the input latency of the linear resampler as one frame for the
+    /// interpolation plus one frame per order of the filter (each second order section delays by
+    /// two frames and a first order section by one), so the order is `max_latency_frames - 1`,
+    /// limited to `max_filter_order()`. The output latency is the input latency scaled by the
+    /// ratio of the sample rates.
+    ///
+    /// Returns the order that was set and its input latency respectively. Returns
+    /// `Error::InvalidArgs` if the budget is 0, because the interpolation always needs a frame.
+    pub fn set_latency_budget_frames(
+        &mut self,
+        max_latency_frames: u32,
+    ) -> Result<(u32, u64), Error> {
+        if max_latency_frames == 0 {
+            ma_debug_panic!("the linear resampler needs a latency budget of at least one frame");
+            return Err(Error::InvalidArgs);
+        }
+
+        let order = (max_latency_frames - 1).min(MAX_FILTER_ORDER as u32);
+        self.set_lpf_order(order);
+        Ok((order, 1 + order as u64))
+    }
 }
 
 #[repr(transparent)]
 pub struct LinearResampler(sys::ma_linear_resampler);
 
 impl LinearResampler {
+    /// Returns `Error::InvalidArgs` if the low-pass filter order of the config is above
+    /// `max_filter_order()`.
     #[inline]
     pub fn new(config: &LinearResamplerConfig) -> Result<LinearResampler, Error> {
+        check_filter_order(config.lpf_order())?;
         let mut lr = std::mem::MaybeUninit::<LinearResampler>::uninit();
         unsafe {
             Error::from_c_result(sys::ma_linear_resampler_init(
@@ -142,14 +399,30 @@ impl LinearResampler {
     //
     /// Converts the given input data.
     ///
-    /// Returns the number of input frames that were consumed during processing and the number of
-    /// output frames that were written to the output buffer respectively.
+    /// The resampler only changes the sample rate, so both `output` and `input` must have the
+    /// format and channel count that the resampler was configured with.
+    ///
+    /// Returns the number of output frames that were written to the output buffer and the number
+    /// of input frames that were consumed during processing respectively.
     #[inline]
     pub fn process_pcm_frames(
         &mut self,
         output: &mut FramesMut,
         input: &Frames,
     ) -> Result<(u64, u64), Error> {
+        ma_trace_span!(
+            "LinearResampler::process_pcm_frames",
+            output_frames = output.frame_count(),
+            input_frames = input.frame_count(),
+        );
+
+        check_frames_match_config(
+            self.config().format(),
+            self.config().channels(),
+            output,
+            input,
+        )?;
+
         let mut output_frames = output.frame_count() as u64;
         let mut input_frames = input.frame_count() as u64;
 
@@ -258,9 +531,196 @@ impl Clone for LinearResampler {
     }
 }
 
-#[repr(transparent)]
+/// Manages one mono `LinearResampler` per channel so that each channel can have its own low-pass
+/// filter settings. miniaudio applies a single LPF configuration to every channel, which is not
+/// always what you want (e.g. filtering audio channels while bypassing a control or LFE channel
+/// by setting its LPF order to 0).
+///
+/// This is more expensive than a single `LinearResampler`. Each call has to deinterleave the input
+/// into a scratch buffer, run a separate resampler for every channel and then reinterleave the
+/// results into the output, so on top of the per-channel call overhead there are two extra copies
+/// of the data. The scratch buffers grow to fit the largest buffers passed in and are then reused.
+pub struct MultiChannelLinearResampler {
+    resamplers: Vec<LinearResampler>,
+    format: Format,
+    input_scratch: Vec<u8>,
+    output_scratch: Vec<u8>,
+}
+
+impl MultiChannelLinearResampler {
+    /// Creates a resampler for `config.channels()` channels. Every channel starts out with the LPF
+    /// settings of `config`.
+    pub fn new(config: &LinearResamplerConfig) -> Result<MultiChannelLinearResampler, Error> {
+        let mut channel_config = config.clone();
+        channel_config.0.channels = 1;
+
+        let resamplers = (0..config.channels())
+            .map(|_| LinearResampler::new(&channel_config))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(MultiChannelLinearResampler {
+            resamplers,
+            format: config.format(),
+            input_scratch: Vec::new(),
+            output_scratch: Vec::new(),
+        })
+    }
+
+    #[inline]
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    #[inline]
+    pub fn channels(&self) -> u32 {
+        self.resamplers.len() as u32
+    }
+
+    /// Returns the mono resampler used for a single channel.
+    #[inline]
+    pub fn channel(&self, channel: u32) -> &LinearResampler {
+        &self.resamplers[channel as usize]
+    }
+
+    /// Changes the LPF settings of a single channel. An order of 0 disables the LPF for that
+    /// channel.
+    ///
+    /// This reinitializes the resampler for that channel which resets its internal state, so it
+    /// should be done before processing begins.
+    pub fn set_channel_lpf(
+        &mut self,
+        channel: u32,
+        order: u32,
+        nyquist_factor: f64,
+    ) -> Result<(), Error> {
+        if channel >= self.channels() {
+            ma_debug_panic!(
+                "channel out of range (channel: {}, channels: {})",
+                channel,
+                self.channels()
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        let resampler = &mut self.resamplers[channel as usize];
+        let mut config = resampler.config().clone();
+        config.set_lpf_order(order);
+        config.set_lpf_nyquist_factor(nyquist_factor);
+        *resampler = LinearResampler::new(&config)?;
+        Ok(())
+    }
+
+    /// Sets the input and output sample rate of every channel.
+    pub fn set_rate(&mut self, sample_rate_in: u32, sample_rate_out: u32) -> Result<(), Error> {
+        for resampler in self.resamplers.iter_mut() {
+            resampler.set_rate(sample_rate_in, sample_rate_out)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the input and output sample rate of every channel as a ratio.
+    ///
+    /// The ratio is in/out.
+    pub fn set_rate_ratio(&mut self, ratio_in_out: f32) -> Result<(), Error> {
+        for resampler in self.resamplers.iter_mut() {
+            resampler.set_rate_ratio(ratio_in_out)?;
+        }
+        Ok(())
+    }
+
+    /// Converts the given interleaved input data.
+    ///
+    /// Like `LinearResampler::process_pcm_frames` this returns the number of output frames that
+    /// were written to the output buffer and the number of input frames that were consumed during
+    /// processing respectively.
+    pub fn process_pcm_frames(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+    ) -> Result<(u64, u64), Error> {
+        ma_trace_span!(
+            "MultiChannelLinearResampler::process_pcm_frames",
+            output_frames = output.frame_count(),
+            input_frames = input.frame_count(),
+        );
+
+        let channels = self.channels();
+        check_frames_match_config(self.format, channels, output, input)?;
+
+        let sample_size = self.format.size_in_bytes();
+        let frame_size = sample_size * channels as usize;
+        let input_frames = input.frame_count();
+        let output_frames = output.frame_count();
+
+        let MultiChannelLinearResampler {
+            resamplers,
+            format,
+            input_scratch,
+            output_scratch,
+        } = self;
+
+        input_scratch.resize(input_frames * sample_size, 0);
+        output_scratch.resize(output_frames * sample_size, 0);
+
+        let mut consumed = 0;
+        let mut produced = 0;
+
+        for (channel, resampler) in resamplers.iter_mut().enumerate() {
+            let channel_offset = channel * sample_size;
+
+            for (frame, dest) in input_scratch.chunks_exact_mut(sample_size).enumerate() {
+                let src = frame * frame_size + channel_offset;
+                dest.copy_from_slice(&input.as_bytes()[src..(src + sample_size)]);
+            }
+
+            let (channel_produced, channel_consumed) = resampler.process_pcm_frames(
+                &mut FramesMut::wrap::<u8>(output_scratch, *format, 1),
+                &Frames::wrap::<u8>(input_scratch, *format, 1),
+            )?;
+
+            // Every channel runs at the same rate so they should always advance together.
+            debug_assert!(
+                channel == 0 || (channel_consumed, channel_produced) == (consumed, produced)
+            );
+            consumed = channel_consumed;
+            produced = channel_produced;
+
+            let output_bytes = output.as_bytes_mut();
+            for (frame, src) in output_scratch
+                .chunks_exact(sample_size)
+                .take(channel_produced as usize)
+                .enumerate()
+            {
+                let dest = frame * frame_size + channel_offset;
+                output_bytes[dest..(dest + sample_size)].copy_from_slice(src);
+            }
+        }
+
+        Ok((produced, consumed))
+    }
+}
+
+/// The settings of the sinc resampler, which miniaudio's config has no room for.
+#[derive(Clone, Copy)]
+struct SincConfig {
+    window_length: u32,
+    beta: f64,
+}
+
+impl Default for SincConfig {
+    fn default() -> SincConfig {
+        SincConfig {
+            window_length: DEFAULT_SINC_WINDOW_LENGTH,
+            beta: DEFAULT_SINC_BETA,
+        }
+    }
+}
+
+/// The miniaudio config is only used for the linear and Speex algorithms. When the sinc algorithm
+/// is selected its settings are kept in the `SincConfig` instead and the miniaudio config is left
+/// as it was.
 #[derive(Clone)]
-pub struct ResamplerConfig(sys::ma_resampler_config);
+pub struct ResamplerConfig(sys::ma_resampler_config, Option<SincConfig>, DitherMode);
 
 impl ResamplerConfig {
     pub fn new(
@@ -270,15 +730,73 @@ impl ResamplerConfig {
         sample_rate_out: u32,
         algorithm: ResampleAlgorithmType,
     ) -> ResamplerConfig {
-        ResamplerConfig(unsafe {
-            sys::ma_resampler_config_init(
-                format as _,
-                channels,
-                sample_rate_in,
-                sample_rate_out,
-                algorithm as _,
-            )
-        })
+        let (ma_algorithm, sinc) = match algorithm {
+            ResampleAlgorithmType::Sinc => (
+                sys::ma_resample_algorithm_linear,
+                Some(SincConfig::default()),
+            ),
+            _ => (algorithm as _, None),
+        };
+
+        ResamplerConfig(
+            unsafe {
+                sys::ma_resampler_config_init(
+                    format as _,
+                    channels,
+                    sample_rate_in,
+                    sample_rate_out,
+                    ma_algorithm,
+                )
+            },
+            sinc,
+            DitherMode::None,
+        )
+    }
+
+    /// Returns the config that the backend is created with. `s32` frames are always resampled as
+    /// `f32`, `u8` and `s16` frames are resampled as `f32` when they are dithered on output, and
+    /// `u8` frames are resampled as `s16` otherwise.
+    fn backend_config(&self) -> ResamplerConfig {
+        let mut config = self.clone();
+        match config.format() {
+            Format::S32 => config.0.format = Format::F32 as _,
+            Format::U8 | Format::S16 if config.output_dither() != DitherMode::None => {
+                config.0.format = Format::F32 as _;
+            }
+            Format::U8 => config.0.format = Format::S16 as _,
+            _ => {}
+        }
+        config
+    }
+
+    /// Creates a config for resampling audio at `sample_rate_in` for playback on `device`.
+    ///
+    /// The output sample rate, format and channel count are the ones of the frames that the
+    /// device asks for in its data callback, which may be different from what was requested when
+    /// the device was created. The linear algorithm is used, which can be changed with
+    /// `set_algorithm`. Returns `Error::InvalidArgs` if the device has no playback side. The
+    /// resampler doesn't support `s24`, so for devices using it a data converter is needed
+    /// instead.
+    pub fn for_device(sample_rate_in: u32, device: &RawDevice) -> Result<ResamplerConfig, Error> {
+        match device.device_type() {
+            DeviceType::Playback | DeviceType::Duplex => {}
+            device_type => {
+                ma_debug_panic!(
+                    "can't resample for a device without playback (device type: {:?})",
+                    device_type
+                );
+                return Err(Error::InvalidArgs);
+            }
+        }
+
+        let playback = device.playback();
+        Ok(ResamplerConfig::new(
+            playback.format(),
+            playback.channels(),
+            sample_rate_in,
+            device.sample_rate(),
+            ResampleAlgorithmType::Linear,
+        ))
     }
 
     #[inline]
@@ -311,6 +829,22 @@ impl ResamplerConfig {
         self.0.sampleRateOut = sample_rate;
     }
 
+    /// The dither that is applied when the resampled frames are written in an integer format.
+    #[inline]
+    pub fn output_dither(&self) -> DitherMode {
+        self.2
+    }
+
+    /// Sets the dither that is applied when the resampled frames are written in an integer
+    /// format. With anything other than `DitherMode::None`, `s16` and `u8` frames are resampled as
+    /// `f32` instead of truncating the fractional results of the filter, and are rounded to the
+    /// nearest step after adding the noise, so rectangular dither stays within 1 LSB of the exact
+    /// result and triangular dither within 1.5 LSB. This has no effect on `f32` frames.
+    #[inline]
+    pub fn set_output_dither(&mut self, dither_mode: DitherMode) {
+        self.2 = dither_mode;
+    }
+
     pub fn set_algorithm(&mut self, algo: ResampleAlgorithm) {
         match algo {
             ResampleAlgorithm::Linear {
@@ -320,157 +854,4244 @@ impl ResamplerConfig {
                 self.0.algorithm = sys::ma_resample_algorithm_linear;
                 self.0.linear.lpfOrder = lpf_order;
                 self.0.linear.lpfNyquistFactor = lpf_nyquist_factor;
+                self.1 = None;
             }
 
             ResampleAlgorithm::Speex { quality } => {
                 self.0.algorithm = sys::ma_resample_algorithm_speex;
                 self.0.speex.quality = quality as _;
+                self.1 = None;
+            }
+
+            ResampleAlgorithm::Sinc {
+                window_length,
+                beta,
+            } => {
+                self.1 = Some(SincConfig {
+                    window_length,
+                    beta,
+                });
             }
         }
     }
 
     pub fn algorithm(&self) -> ResampleAlgorithm {
-        match self.0.algorithm {
-            sys::ma_resample_algorithm_linear => ResampleAlgorithm::Linear {
-                lpf_order: self.0.linear.lpfOrder,
-                lpf_nyquist_factor: self.0.linear.lpfNyquistFactor,
-            },
+        if let Some(sinc) = self.1 {
+            return ResampleAlgorithm::Sinc {
+                window_length: sinc.window_length,
+                beta: sinc.beta,
+            };
+        }
 
+        // Only the linear and Speex algorithms are ever stored in the miniaudio config.
+        match self.0.algorithm {
             sys::ma_resample_algorithm_speex => ResampleAlgorithm::Speex {
                 quality: self.0.speex.quality as _,
             },
 
-            _ => unreachable!(),
+            _ => ResampleAlgorithm::Linear {
+                lpf_order: self.0.linear.lpfOrder,
+                lpf_nyquist_factor: self.0.linear.lpfNyquistFactor,
+            },
         }
     }
-}
 
-#[repr(transparent)]
-pub struct Resampler(sys::ma_resampler);
+    /// Checks the config for settings that work but will probably degrade the quality of the
+    /// output. Settings that can't work at all (e.g. a sample rate of 0) are reported as errors by
+    /// `Resampler::new` instead.
+    pub fn validate(&self) -> Vec<ConfigWarning> {
+        let mut warnings = Vec::new();
+        let downsampling = self.sample_rate_out() < self.sample_rate_in();
 
-impl Resampler {
-    pub fn new(config: &ResamplerConfig) -> Result<Resampler, Error> {
-        let mut resampler = std::mem::MaybeUninit::<Resampler>::uninit();
-        unsafe {
-            sys::ma_resampler_init(&config.0, resampler.as_mut_ptr() as *mut _);
-            Ok(resampler.assume_init())
+        if self.sample_rate_in() == self.sample_rate_out() {
+            warnings.push(ConfigWarning::SameSampleRate);
         }
-    }
 
-    #[inline]
-    pub fn config(&self) -> &ResamplerConfig {
-        unsafe { &*(&self.0.config as *const sys::ma_resampler_config as *const ResamplerConfig) }
-    }
+        match self.algorithm() {
+            ResampleAlgorithm::Linear {
+                lpf_order,
+                lpf_nyquist_factor,
+            } => {
+                if downsampling && lpf_order == 0 {
+                    warnings.push(ConfigWarning::NoLpfWhileDownsampling);
+                } else if lpf_order > 0 && lpf_nyquist_factor > 1.0 {
+                    warnings.push(ConfigWarning::LpfCutoffAboveNyquist { lpf_nyquist_factor });
+                }
+            }
 
-    // FIXME this API actually allows passing null for input or output and does this:
-    //
-    //      You can pass in NULL for the input buffer in which case it will be treated
-    //      as an infinitely large buffer of zeros. The output buffer can also be NULL,
-    //      in which case the processing will be treated as seek.
-    //
-    // I don't have a really good way to represent this right now, so I don't support it :P.
-    //
-    /// Converts the given input data.
-    ///
-    /// Returns the number of input frames that were consumed during processing and the number of
-    /// output frames that were written to the output buffer respectively.
-    #[inline]
-    pub fn process_pcm_frames(
-        &mut self,
-        output: &mut FramesMut,
-        input: &Frames,
-    ) -> Result<(u64, u64), Error> {
-        if output.format() != input.format() {
-            ma_debug_panic!(
-                "output and input format did not match (output: {:?}, input: {:?}",
-                output.format(),
-                input.format()
-            );
-            return Err(Error::InvalidArgs);
+            ResampleAlgorithm::Speex { quality } => {
+                if quality == 0 {
+                    warnings.push(ConfigWarning::SpeexQualityZero);
+                }
+            }
+
+            ResampleAlgorithm::Sinc { window_length, .. } => {
+                if window_length < MIN_RECOMMENDED_SINC_WINDOW_LENGTH {
+                    warnings.push(ConfigWarning::ShortSincWindow { window_length });
+                }
+            }
         }
 
-        let mut output_frames = output.frame_count() as u64;
-        let mut input_frames = input.frame_count() as u64;
+        warnings
+    }
+}
 
-        Error::from_c_result(unsafe {
-            sys::ma_resampler_process_pcm_frames(
-                &mut self.0,
-                input.as_ptr() as *const _,
-                &mut input_frames,
-                output.as_mut_ptr() as *mut _,
-                &mut output_frames,
-            )
-        })?;
+/// Access to the low-pass filter settings of a resampler config, so that generic code can
+/// configure the filter of either config type.
+///
+/// For a `ResamplerConfig` these are the settings of the linear resampler and have no effect when
+/// another algorithm is used.
+pub trait HasLpf {
+    fn lpf_order(&self) -> u32;
+    fn set_lpf_order(&mut self, order: u32);
+    fn lpf_nyquist_factor(&self) -> f64;
+    fn set_lpf_nyquist_factor(&mut self, factor: f64);
+}
 
-        Ok((input_frames, output_frames))
+impl HasLpf for LinearResamplerConfig {
+    #[inline]
+    fn lpf_order(&self) -> u32 {
+        LinearResamplerConfig::lpf_order(self)
     }
 
-    /// Sets the input and output sample rate.
     #[inline]
-    pub fn set_rate(&mut self, sample_rate_in: u32, sample_rate_out: u32) -> Result<(), Error> {
-        Error::from_c_result(unsafe {
-            sys::ma_resampler_set_rate(&mut self.0, sample_rate_in, sample_rate_out)
-        })
+    fn set_lpf_order(&mut self, order: u32) {
+        LinearResamplerConfig::set_lpf_order(self, order)
     }
 
-    /// Sets the input and output sample rate as a ratio.
-    ///
-    /// The ratio is in/out.
     #[inline]
-    pub fn set_rate_ratio(&mut self, ratio_in_out: f32) -> Result<(), Error> {
-        Error::from_c_result(unsafe { sys::ma_resampler_set_rate_ratio(&mut self.0, ratio_in_out) })
+    fn lpf_nyquist_factor(&self) -> f64 {
+        LinearResamplerConfig::lpf_nyquist_factor(self)
     }
 
-    /// Calculates the number of whole input frames that would need to be read from the client in
-    /// order to output the specified number of output frames.
-    ///
-    /// The returned value does not include cached input frames. It only returns the number of
-    /// extra frames that would need to be read from the input buffer in order to output the
-    /// specified number of output frames.
     #[inline]
-    pub fn required_input_frame_count(&self, output_frame_count: u64) -> u64 {
-        unsafe {
-            sys::ma_resampler_get_required_input_frame_count(
-                &self.0 as *const _ as *mut _,
-                output_frame_count,
-            )
-        }
+    fn set_lpf_nyquist_factor(&mut self, factor: f64) {
+        LinearResamplerConfig::set_lpf_nyquist_factor(self, factor)
     }
+}
 
-    /// Calculates the number of whole output frames that would be output after fully reading and
-    /// consuming the specified number of input frames.
+impl HasLpf for ResamplerConfig {
     #[inline]
-    pub fn expected_output_frame_count(&self, input_frame_count: u64) -> u64 {
-        unsafe {
-            sys::ma_linear_resampler_get_expected_output_frame_count(
-                &self.0 as *const _ as *mut _,
-                input_frame_count,
-            )
-        }
+    fn lpf_order(&self) -> u32 {
+        self.0.linear.lpfOrder
     }
+
     #[inline]
+    fn set_lpf_order(&mut self, order: u32) {
+        self.0.linear.lpfOrder = order;
+    }
 
-    /// Retrieves the latency introduced by the resampler in input frames.
-    pub fn input_latency(&mut self) -> u64 {
-        unsafe { sys::ma_linear_resampler_get_input_latency(&self.0 as *const _ as *mut _) }
+    #[inline]
+    fn lpf_nyquist_factor(&self) -> f64 {
+        self.0.linear.lpfNyquistFactor
     }
 
-    /// Retrieves the latency introduced by the resampler in output frames.
     #[inline]
-    pub fn output_latency(&mut self) -> u64 {
-        unsafe { sys::ma_linear_resampler_get_output_latency(&self.0 as *const _ as *mut _) }
+    fn set_lpf_nyquist_factor(&mut self, factor: f64) {
+        self.0.linear.lpfNyquistFactor = factor;
     }
 }
 
-impl Clone for Resampler {
-    fn clone(&self) -> Self {
-        // This should not fail if the resampler was properly initialized.
-        Self::new(self.config()).expect("failed to clone resampler")
+/// A problem with a `ResamplerConfig` that doesn't prevent a resampler from being created but
+/// will probably make it sound worse than intended. See `ResamplerConfig::validate`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigWarning {
+    /// The linear resampler is downsampling without a low-pass filter, so frequencies above the
+    /// new Nyquist frequency will alias.
+    NoLpfWhileDownsampling,
+
+    /// The cutoff of the linear resampler's low-pass filter is above the Nyquist frequency, so it
+    /// doesn't prevent aliasing.
+    LpfCutoffAboveNyquist { lpf_nyquist_factor: f64 },
+
+    /// Speex quality 0 is barely better than the linear resampler while still being slower and
+    /// allocating.
+    SpeexQualityZero,
+
+    /// Sinc windows this short have a very wide transition band, which dulls the high end.
+    ShortSincWindow { window_length: u32 },
+
+    /// The input and output sample rates are the same, so the resampler only adds latency.
+    SameSampleRate,
+}
+
+impl std::fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            ConfigWarning::NoLpfWhileDownsampling => {
+                write!(f, "the low-pass filter is disabled while downsampling, which may cause aliasing")
+            }
+            ConfigWarning::LpfCutoffAboveNyquist { lpf_nyquist_factor } => write!(
+                f,
+                "the low-pass filter cutoff is above the Nyquist frequency (factor {}), which may cause aliasing",
+                lpf_nyquist_factor
+            ),
+            ConfigWarning::SpeexQualityZero => {
+                write!(f, "Speex quality 0 is effectively linear resampling")
+            }
+            ConfigWarning::ShortSincWindow { window_length } => write!(
+                f,
+                "a sinc window of {} frames gives a soft low-pass filter that dulls high frequencies",
+                window_length
+            ),
+            ConfigWarning::SameSampleRate => write!(
+                f,
+                "the input and output sample rates are the same, so resampling only adds latency"
+            ),
+        }
     }
 }
 
-impl Drop for Resampler {
-    fn drop(&mut self) {
-        unsafe { sys::ma_resampler_uninit(&mut self.0) };
+/// Sinc windows shorter than this get a `ConfigWarning::ShortSincWindow`.
+const MIN_RECOMMENDED_SINC_WINDOW_LENGTH: u32 = 8;
+
+// The miniaudio resampler is a lot larger than the sinc resampler, but boxing it would add an
+// allocation to every linear resampler.
+#[allow(clippy::large_enum_variant)]
+enum ResamplerBackend {
+    Miniaudio(sys::ma_resampler),
+    Sinc(SincResampler),
+}
+
+pub struct Resampler {
+    config: ResamplerConfig,
+    backend: ResamplerBackend,
+    /// The number of input frames consumed since the resampler was created or reset.
+    input_frames_consumed: u64,
+    /// Set while fading out of the previous rate after `set_rate_crossfaded`.
+    rate_crossfade: Option<Box<RateCrossfade>>,
+    /// The number of frames of silence that `finish` has fed in to drain the resampler.
+    tail_frames_fed: u64,
+    /// The input that `read` pulled but that wasn't consumed yet.
+    read_input: ReadInput,
+}
+
+/// The number of bytes of input that `Resampler::read` pulls at a time.
+const READ_CHUNK_BYTES: usize = 4096;
+
+/// Input that `Resampler::read` pulled from its callback, of which the frames from `start` to
+/// `end` weren't consumed yet.
+#[derive(Default)]
+struct ReadInput {
+    buffer: Vec<u8>,
+    start: usize,
+    end: usize,
+}
+
+/// The state of a crossfade from the previous rate started by `Resampler::set_rate_crossfaded`.
+struct RateCrossfade {
+    /// A copy of the resampler that keeps running at the previous rate.
+    outgoing: Resampler,
+    fade_frames: u64,
+    curve: FadeCurve,
+    /// The number of output frames of the fade that were already produced.
+    position: u64,
+    /// Holds the output of `outgoing` before it is mixed into the output.
+    buffer: FramesBuffer,
+    /// The input that was already consumed at the new rate but not yet by `outgoing`, which
+    /// consumes the input at a different pace.
+    input: Vec<u8>,
+}
+
+/// The result of `Resampler::process_ex`, which says which side stopped the resampler.
+///
+/// When neither `consumed` nor `produced` advanced, at least one of the flags is set, so a
+/// streaming loop can tell whether to read more input or drain the output instead of calling the
+/// resampler again with the same buffers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessOutcome {
+    /// The number of input frames that were consumed.
+    pub consumed: u64,
+    /// The number of output frames that were written.
+    pub produced: u64,
+    /// All of the input was consumed, so more input is needed to produce more output.
+    pub needs_more_input: bool,
+    /// The whole output buffer was written, so it has to be drained before more can be produced.
+    pub output_full: bool,
+}
+
+impl Resampler {
+    /// Creates a new resampler. This returns `Error::NoBackend` if the config uses an algorithm
+    /// that was not compiled into this build (see `ResampleAlgorithm::is_available`).
+    ///
+    /// The Speex resampler allocates its state on the heap, so with the Speex algorithm this
+    /// returns `Error::OutOfMemory` if that allocation fails. On constrained systems the linear
+    /// resampler, which doesn't allocate, can be used as a fallback in that case.
+    pub fn new(config: &ResamplerConfig) -> Result<Resampler, Error> {
+        Resampler::with_sinc_kernel(config, None)
+    }
+
+    /// Creates a resampler with the config of `plan`. Resamplers using the sinc algorithm share
+    /// the kernel of the plan instead of computing their own, until their rate is changed.
+    pub fn from_plan(plan: &ResamplePlan) -> Result<Resampler, Error> {
+        Resampler::with_sinc_kernel(&plan.config, plan.sinc_kernel.clone())
+    }
+
+    /// Creates a resampler, using `sinc_kernel` instead of computing one if the config uses the
+    /// sinc algorithm.
+    fn with_sinc_kernel(
+        config: &ResamplerConfig,
+        sinc_kernel: Option<Arc<Vec<f32>>>,
+    ) -> Result<Resampler, Error> {
+        if !config.algorithm().is_available() {
+            return Err(Error::NoBackend);
+        }
+        if let ResampleAlgorithm::Linear { lpf_order, .. } = config.algorithm() {
+            check_filter_order(lpf_order)?;
+        }
+
+        let backend_config = config.backend_config();
+        if let ResampleAlgorithm::Sinc {
+            window_length,
+            beta,
+        } = config.algorithm()
+        {
+            let sinc = SincResampler::new(
+                backend_config.format(),
+                config.channels(),
+                config.sample_rate_in(),
+                config.sample_rate_out(),
+                window_length,
+                beta,
+                sinc_kernel,
+            )?;
+            return Ok(Resampler {
+                config: config.clone(),
+                backend: ResamplerBackend::Sinc(sinc),
+                input_frames_consumed: 0,
+                rate_crossfade: None,
+                tail_frames_fed: 0,
+                read_input: ReadInput::default(),
+            });
+        }
+
+        let mut resampler = std::mem::MaybeUninit::<sys::ma_resampler>::uninit();
+        unsafe {
+            Error::from_c_result(sys::ma_resampler_init(
+                &backend_config.0,
+                resampler.as_mut_ptr(),
+            ))?;
+            Ok(Resampler {
+                config: config.clone(),
+                backend: ResamplerBackend::Miniaudio(resampler.assume_init()),
+                input_frames_consumed: 0,
+                rate_crossfade: None,
+                tail_frames_fed: 0,
+                read_input: ReadInput::default(),
+            })
+        }
+    }
+
+    /// Reinitializes this resampler in place with a new config.
+    ///
+    /// A resampler never changes the format or channel count of the frames it processes, so when
+    /// the properties of the stream change (e.g. a new track with a different channel count) the
+    /// resampler has to be reinitialized with a config that matches the new stream. Any frames
+    /// cached by the resampler are discarded. If initialization fails the resampler is left as it
+    /// was.
+    pub fn reinit(&mut self, config: &ResamplerConfig) -> Result<(), Error> {
+        if !config.algorithm().is_available() {
+            return Err(Error::NoBackend);
+        }
+        if let ResampleAlgorithm::Linear { lpf_order, .. } = config.algorithm() {
+            check_filter_order(lpf_order)?;
+        }
+
+        if config.algorithm().algorithm_type() == ResampleAlgorithmType::Sinc {
+            *self = Resampler::new(config)?;
+            return Ok(());
+        }
+
+        let mut resampler = std::mem::MaybeUninit::<sys::ma_resampler>::uninit();
+        unsafe {
+            Error::from_c_result(sys::ma_resampler_init(
+                &config.backend_config().0,
+                resampler.as_mut_ptr(),
+            ))?;
+            *self = Resampler {
+                config: config.clone(),
+                backend: ResamplerBackend::Miniaudio(resampler.assume_init()),
+                input_frames_consumed: 0,
+                rate_crossfade: None,
+                tail_frames_fed: 0,
+                read_input: ReadInput::default(),
+            };
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn config(&self) -> &ResamplerConfig {
+        &self.config
+    }
+
+    /// Sets the dither that is applied when writing `s16` or `u8` output (see
+    /// `ResamplerConfig::set_output_dither`). If this changes the format that the frames are
+    /// resampled in, the resampler is reinitialized, which discards any cached frames like
+    /// `reinit` does.
+    pub fn set_output_dither(&mut self, dither_mode: DitherMode) -> Result<(), Error> {
+        let mut config = self.config.clone();
+        config.set_output_dither(dither_mode);
+        if config.backend_config().format() == self.config.backend_config().format() {
+            self.config = config;
+            return Ok(());
+        }
+        self.reinit(&config)
+    }
+
+    #[inline]
+    pub fn output_dither(&self) -> DitherMode {
+        self.config.output_dither()
+    }
+
+    /// Clears the frames cached by the resampler and its filter state so that it can be used for
+    /// a new stream with the same config. Unlike `reinit` this does not allocate.
+    pub fn reset(&mut self) {
+        self.input_frames_consumed = 0;
+        self.rate_crossfade = None;
+        self.tail_frames_fed = 0;
+        self.read_input.start = 0;
+        self.read_input.end = 0;
+        let raw = match &mut self.backend {
+            ResamplerBackend::Miniaudio(raw) => raw,
+            ResamplerBackend::Sinc(sinc) => return sinc.reset(),
+        };
+
+        match self.config.algorithm().algorithm_type() {
+            ResampleAlgorithmType::Linear => unsafe {
+                let linear_config = raw.state.linear.config;
+                sys::ma_linear_resampler_init(&linear_config, &mut raw.state.linear);
+            },
+
+            ResampleAlgorithmType::Speex => {
+                #[cfg(feature = "ma-enable-speex")]
+                unsafe {
+                    sys::ma_speex_resampler_reset_mem(raw.state.speex.pSpeexResamplerState);
+                }
+                #[cfg(not(feature = "ma-enable-speex"))]
+                let _ = raw;
+            }
+
+            ResampleAlgorithmType::Sinc => unreachable!(),
+        }
+    }
+
+    // FIXME this API actually allows passing null for input or output and does this:
+    //
+    //      You can pass in NULL for the input buffer in which case it will be treated
+    //      as an infinitely large buffer of zeros. The output buffer can also be NULL,
+    //      in which case the processing will be treated as seek.
+    //
+    // I don't have a really good way to represent this right now, so I don't support it :P.
+    //
+    /// Converts the given input data.
+    ///
+    /// The resampler only changes the sample rate, so both `output` and `input` must have the
+    /// format and channel count that the resampler was configured with.
+    ///
+    /// miniaudio can only resample `s16` and `f32`, so `u8` frames are converted to `s16` and back
+    /// in chunks. This removes the bias of 128 before resampling, so it isn't filtered like a DC
+    /// offset and silence stays at 128. `s32` frames are converted to `f32` the same way, which
+    /// keeps 24 bits of precision. When an output dither is set, `s16` and `u8` frames are
+    /// converted to `f32` instead and dithered when they are converted back.
+    ///
+    /// Returns the number of input frames that were consumed during processing and the number of
+    /// output frames that were written to the output buffer respectively.
+    #[inline]
+    pub fn process_pcm_frames(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+    ) -> Result<(u64, u64), Error> {
+        ma_trace_span!(
+            "Resampler::process_pcm_frames",
+            output_frames = output.frame_count(),
+            input_frames = input.frame_count(),
+        );
+
+        if output.format() != input.format() {
+            ma_debug_panic!(
+                "output and input format did not match (output: {:?}, input: {:?}",
+                output.format(),
+                input.format()
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        check_frames_match_config(
+            self.config().format(),
+            self.config().channels(),
+            output,
+            input,
+        )?;
+
+        if self.config.backend_config().format() != self.config.format() {
+            return self.process_converted(output, input);
+        }
+        self.process_unchecked(output, input)
+    }
+
+    /// Converts the frames to the format of the backend and back through buffers on the stack.
+    fn process_converted(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+    ) -> Result<(u64, u64), Error> {
+        let format = self.config.format();
+        let backend_format = self.config.backend_config().format();
+        let channels = self.config.channels();
+        let frame_size = bytes_per_frame(format, channels);
+        let backend_frame_size = bytes_per_frame(backend_format, channels);
+        let chunk_frames = CONVERSION_CHUNK_BYTES / backend_frame_size;
+
+        // The buffers are stored as f32 so that they are aligned for every backend format.
+        let mut input_storage = [0f32; CONVERSION_CHUNK_BYTES / 4];
+        let mut output_storage = [0f32; CONVERSION_CHUNK_BYTES / 4];
+        let mut input_chunk = FramesMut::wrap(&mut input_storage[..], backend_format, channels);
+        let mut output_chunk = FramesMut::wrap(&mut output_storage[..], backend_format, channels);
+        let mut dither = match self.config.output_dither() {
+            DitherMode::None => None,
+            mode => Some(OutputDither::new(mode, self.input_frames_consumed)),
+        };
+
+        let input_frames = input.frame_count();
+        let output_frames = output.frame_count();
+        let mut consumed = 0;
+        let mut produced = 0;
+        while produced < output_frames {
+            let input_count = (input_frames - consumed).min(chunk_frames);
+            let output_count = (output_frames - produced).min(chunk_frames);
+
+            Frames::wrap::<u8>(
+                &input.as_bytes()[(consumed * frame_size)..((consumed + input_count) * frame_size)],
+                format,
+                channels,
+            )
+            .convert(
+                &mut FramesMut::wrap::<u8>(
+                    &mut input_chunk.as_bytes_mut()[..(input_count * backend_frame_size)],
+                    backend_format,
+                    channels,
+                ),
+                DitherMode::None,
+            );
+
+            let (used, written) = self.process_unchecked(
+                &mut FramesMut::wrap::<u8>(
+                    &mut output_chunk.as_bytes_mut()[..(output_count * backend_frame_size)],
+                    backend_format,
+                    channels,
+                ),
+                &Frames::wrap::<u8>(
+                    &input_chunk.as_bytes()[..(input_count * backend_frame_size)],
+                    backend_format,
+                    channels,
+                ),
+            )?;
+            let (used, written) = (used as usize, written as usize);
+
+            let output_bytes = &mut output.as_bytes_mut()
+                [(produced * frame_size)..((produced + written) * frame_size)];
+            match dither {
+                Some(ref mut dither) => dither.quantize(
+                    &output_chunk.as_samples::<f32>()[..(written * channels as usize)],
+                    output_bytes,
+                    format,
+                ),
+                None => Frames::wrap::<u8>(
+                    &output_chunk.as_bytes()[..(written * backend_frame_size)],
+                    backend_format,
+                    channels,
+                )
+                .convert(
+                    &mut FramesMut::wrap::<u8>(output_bytes, format, channels),
+                    DitherMode::None,
+                ),
+            }
+
+            consumed += used;
+            produced += written;
+            if used == 0 && written == 0 {
+                break;
+            }
+        }
+
+        Ok((consumed as u64, produced as u64))
+    }
+
+    /// Runs the backend and the rate crossfade on frames in the format of the backend.
+    fn process_unchecked(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+    ) -> Result<(u64, u64), Error> {
+        let (input_frames, output_frames) = self.process_backend(output, input)?;
+
+        // If this is ever violated the resampler has read or written past the end of a buffer,
+        // so fail loudly in debug builds instead of corrupting memory quietly.
+        debug_assert!(
+            input_frames <= input.frame_count() as u64,
+            "resampler consumed more input frames than it was given (consumed: {}, input: {})",
+            input_frames,
+            input.frame_count()
+        );
+        debug_assert!(
+            output_frames <= output.frame_count() as u64,
+            "resampler produced more output frames than fit in the output (produced: {}, output: {})",
+            output_frames,
+            output.frame_count()
+        );
+
+        if self.rate_crossfade.is_some() {
+            self.mix_rate_crossfade(output, input, input_frames, output_frames)?;
+        }
+        Ok((input_frames, output_frames))
+    }
+
+    /// Runs the backend without any checks.
+    fn process_backend(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+    ) -> Result<(u64, u64), Error> {
+        let raw = match &mut self.backend {
+            ResamplerBackend::Miniaudio(raw) => raw,
+            ResamplerBackend::Sinc(sinc) => {
+                let (input_frames, output_frames) = sinc.process_pcm_frames(output, input);
+                self.input_frames_consumed += input_frames;
+                return Ok((input_frames, output_frames));
+            }
+        };
+
+        let mut output_frames = output.frame_count() as u64;
+        let mut input_frames = input.frame_count() as u64;
+
+        Error::from_c_result(unsafe {
+            sys::ma_resampler_process_pcm_frames(
+                raw,
+                input.as_ptr() as *const _,
+                &mut input_frames,
+                output.as_mut_ptr() as *mut _,
+                &mut output_frames,
+            )
+        })?;
+
+        self.input_frames_consumed += input_frames;
+        Ok((input_frames, output_frames))
+    }
+
+    /// Runs the resampler that is still at the previous rate over the same input and crossfades
+    /// its output into the first `output_frames` frames of `output`. `input_frames` is the number
+    /// of frames of `input` that were consumed at the new rate.
+    fn mix_rate_crossfade(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+        input_frames: u64,
+        output_frames: u64,
+    ) -> Result<(), Error> {
+        // The position in the input stream of the start of `input`.
+        let input_start = self.input_frames_consumed - input_frames;
+        let crossfade = match self.rate_crossfade.as_mut() {
+            Some(crossfade) => crossfade,
+            None => return Ok(()),
+        };
+
+        let format = output.format();
+        let channels = output.channels();
+        let frame_size = bytes_per_frame(format, channels);
+        let frames = output_frames
+            .min(crossfade.fade_frames - crossfade.position)
+            .min(crossfade.buffer.frame_count() as u64) as usize;
+
+        // The outgoing resampler first catches up on the input that it is behind on, and only
+        // then continues in `input`, skipping whatever it already consumed ahead of the new rate.
+        let mut outgoing_frames = 0;
+        if !crossfade.input.is_empty() {
+            let (used, written) = crossfade.outgoing.process_backend(
+                &mut FramesMut::wrap(
+                    &mut crossfade.buffer.as_bytes_mut()[..(frames * frame_size)],
+                    format,
+                    channels,
+                ),
+                &Frames::wrap::<u8>(&crossfade.input, format, channels),
+            )?;
+            crossfade.input.drain(..(used as usize * frame_size));
+            outgoing_frames += written as usize;
+        }
+        if crossfade.input.is_empty() && outgoing_frames < frames {
+            let skip = ((crossfade.outgoing.input_frames_consumed - input_start) as usize)
+                .min(input.frame_count());
+            let (_, written) = crossfade.outgoing.process_backend(
+                &mut FramesMut::wrap(
+                    &mut crossfade.buffer.as_bytes_mut()
+                        [(outgoing_frames * frame_size)..(frames * frame_size)],
+                    format,
+                    channels,
+                ),
+                &Frames::wrap::<u8>(&input.as_bytes()[(skip * frame_size)..], format, channels),
+            )?;
+            outgoing_frames += written as usize;
+        }
+
+        // Keep the input that the new rate consumed and the outgoing resampler didn't get to yet.
+        let outgoing_position = crossfade.outgoing.input_frames_consumed;
+        let input_end = input_start + input_frames;
+        if outgoing_position < input_end {
+            let from = (outgoing_position.max(input_start) - input_start) as usize;
+            crossfade.input.extend_from_slice(
+                &input.as_bytes()[(from * frame_size)..(input_frames as usize * frame_size)],
+            );
+        }
+
+        let channels = channels as usize;
+        let sample_count = outgoing_frames * channels;
+        let fade_frames = crossfade.fade_frames as f32;
+        let position = crossfade.position as usize;
+        let curve = crossfade.curve;
+        let gains = |sample: usize| {
+            curve.crossfade_gains((position + sample / channels) as f32 / fade_frames)
+        };
+
+        match format {
+            Format::S16 => {
+                let outgoing_buffer = crossfade.buffer.frames();
+                let outgoing = &outgoing_buffer.as_samples::<i16>()[..sample_count];
+                for (index, (dest, &old)) in output.as_samples_mut::<i16>()[..sample_count]
+                    .iter_mut()
+                    .zip(outgoing)
+                    .enumerate()
+                {
+                    let (old_gain, new_gain) = gains(index);
+                    let mixed = old as f32 * old_gain + *dest as f32 * new_gain;
+                    *dest = mixed.round().clamp(-32768.0, 32767.0) as i16;
+                }
+            }
+
+            _ => {
+                let outgoing_buffer = crossfade.buffer.frames();
+                let outgoing = &outgoing_buffer.as_samples::<f32>()[..sample_count];
+                for (index, (dest, &old)) in output.as_samples_mut::<f32>()[..sample_count]
+                    .iter_mut()
+                    .zip(outgoing)
+                    .enumerate()
+                {
+                    let (old_gain, new_gain) = gains(index);
+                    *dest = old * old_gain + *dest * new_gain;
+                }
+            }
+        }
+
+        crossfade.position += frames as u64;
+        if crossfade.position >= crossfade.fade_frames {
+            self.rate_crossfade = None;
+        }
+
+        Ok(())
+    }
+
+    /// Fills `output` by pulling input from `fill_input` as needed, and returns the number of
+    /// output frames that were written.
+    ///
+    /// `fill_input` is given a buffer with the format and channel count of the resampler and must
+    /// return the number of frames that it wrote into it. Returning 0 signals the end of the
+    /// input, after which any output that can still be produced from frames cached by the
+    /// resampler is written and reading stops. The callback is asked for about the number of
+    /// input frames required to produce the remaining output. Input that was pulled but not
+    /// consumed by the time `output` is full is kept and used first by the next call, so splitting
+    /// a read into several smaller ones gives the same output. The buffer for the pulled input is
+    /// allocated by the first call.
+    pub fn read<F>(&mut self, output: &mut FramesMut, fill_input: F) -> Result<u64, Error>
+    where
+        F: FnMut(&mut FramesMut) -> u64,
+    {
+        let format = self.config().format();
+        let channels = self.config().channels();
+        if output.format() != format || output.channels() != channels {
+            ma_debug_panic!(
+                "output did not match resampler (output: {:?}/{}, resampler: {:?}/{})",
+                output.format(),
+                output.channels(),
+                format,
+                channels
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        let bytes_per_frame = bytes_per_frame(format, channels);
+        let mut pending = std::mem::take(&mut self.read_input);
+        let chunk_frames = READ_CHUNK_BYTES / bytes_per_frame;
+        pending.buffer.resize(chunk_frames * bytes_per_frame, 0);
+
+        let result = self.read_pending(output, &mut pending, fill_input);
+        self.read_input = pending;
+        result
+    }
+
+    /// The loop of `read`, which consumes `pending` before pulling more input into it.
+    fn read_pending<F>(
+        &mut self,
+        output: &mut FramesMut,
+        pending: &mut ReadInput,
+        mut fill_input: F,
+    ) -> Result<u64, Error>
+    where
+        F: FnMut(&mut FramesMut) -> u64,
+    {
+        let format = self.config().format();
+        let channels = self.config().channels();
+        let bytes_per_frame = bytes_per_frame(format, channels);
+        let chunk_frames = pending.buffer.len() / bytes_per_frame;
+
+        let output_frames = output.frame_count();
+        let mut produced = 0;
+        let mut at_end = false;
+
+        while produced < output_frames {
+            if pending.start == pending.end && !at_end {
+                let remaining = (output_frames - produced) as u64;
+                let required = self.required_input_frame_count(remaining) as usize;
+                let requested = required.clamp(1, chunk_frames);
+
+                let mut input = FramesMut::wrap::<u8>(
+                    &mut pending.buffer[..requested * bytes_per_frame],
+                    format,
+                    channels,
+                );
+                let filled = (fill_input(&mut input) as usize).min(requested);
+
+                pending.start = 0;
+                pending.end = filled;
+                at_end = filled == 0;
+            }
+
+            let input = Frames::wrap::<u8>(
+                &pending.buffer[pending.start * bytes_per_frame..pending.end * bytes_per_frame],
+                format,
+                channels,
+            );
+            let mut output = FramesMut::wrap::<u8>(
+                &mut output.as_bytes_mut()[produced * bytes_per_frame..],
+                format,
+                channels,
+            );
+
+            let (consumed, written) = self.process_pcm_frames(&mut output, &input)?;
+            pending.start += consumed as usize;
+            produced += written as usize;
+
+            // Stop once the cached frames have been drained after the end of the input, or if
+            // the resampler can't make any progress with the input it was given.
+            if written == 0 && (at_end || consumed == 0) {
+                break;
+            }
+        }
+
+        Ok(produced as u64)
+    }
+
+    /// Fills the whole output buffer of a device from inside its data callback, pulling input
+    /// from `source` like `read` does.
+    ///
+    /// The resampler writes straight into `device_output`, so there is no intermediate output
+    /// buffer or copy per period and nothing is allocated after the first call, which allocates
+    /// the buffer for the pulled input. If `source` runs out before the buffer
+    /// is full the rest of it is filled with silence. Returns the number of resampled frames that
+    /// were written, not counting the silence. See the `playback-resampled-decoder` example.
+    pub fn fill_device_buffer<F>(
+        &mut self,
+        device_output: &mut FramesMut,
+        source: F,
+    ) -> Result<u64, Error>
+    where
+        F: FnMut(&mut FramesMut) -> u64,
+    {
+        let produced = self.read(device_output, source)?;
+
+        let frame_size = bytes_per_frame(device_output.format(), device_output.channels());
+        let silence = if device_output.format() == Format::U8 {
+            0x80
+        } else {
+            0
+        };
+        device_output.as_bytes_mut()[(produced as usize * frame_size)..]
+            .iter_mut()
+            .for_each(|byte| *byte = silence);
+        Ok(produced)
+    }
+
+    /// Resamples a loop so that the result is exactly `target_output_frames` long, which keeps it
+    /// seamless when it is played back in a loop.
+    ///
+    /// The resampler is reset and the loop is resampled as if it was already looping: it is
+    /// preceded by its own end and followed by its own start, so the interpolation across the loop
+    /// point is the same as anywhere else and the latency of the resampler is compensated for.
+    /// The result is then stretched to the target length with linear interpolation.
+    ///
+    /// Stretching changes the pitch by the ratio between the natural length of the resampled loop
+    /// (`input.frame_count() * sample_rate_out / sample_rate_in`) and the target. Rounding the
+    /// natural length to the nearest frame is off by half a frame at most, which is a pitch error
+    /// below one cent for loops longer than about 900 frames. The extra interpolation also
+    /// slightly attenuates the highest frequencies.
+    pub fn process_loop(
+        &mut self,
+        input: &Frames,
+        target_output_frames: u64,
+    ) -> Result<FramesBuffer, Error> {
+        let format = self.config().format();
+        let channels = self.config().channels();
+        if input.format() != format || input.channels() != channels {
+            ma_debug_panic!(
+                "input did not match resampler (input: {:?}/{}, resampler: {:?}/{})",
+                input.format(),
+                input.channels(),
+                format,
+                channels
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        let target_frames = target_output_frames as usize;
+        let loop_frames = input.frame_count();
+        if target_frames == 0 {
+            return Ok(FramesBuffer::new(format, channels, 0));
+        }
+        if loop_frames == 0 {
+            return Err(Error::InvalidArgs);
+        }
+
+        self.reset();
+        let latency = self.input_latency() as usize;
+
+        // The loop with its own end in front of it and its own start after it. For very short
+        // loops these wrap around several times.
+        let bytes_per_frame = bytes_per_frame(format, channels);
+        let mut looped = FramesBuffer::new(format, channels, loop_frames + 2 * latency);
+        let offset = loop_frames - latency % loop_frames;
+        for (frame, dest) in looped
+            .as_bytes_mut()
+            .chunks_exact_mut(bytes_per_frame)
+            .enumerate()
+        {
+            let src = (frame + offset) % loop_frames * bytes_per_frame;
+            dest.copy_from_slice(&input.as_bytes()[src..(src + bytes_per_frame)]);
+        }
+
+        let expected = self.expected_output_frame_count(looped.frame_count() as u64) as usize;
+        let mut resampled = FramesBuffer::new(format, channels, expected + 1);
+        let mut consumed = 0;
+        let mut produced = 0;
+        while consumed < looped.frame_count() && produced < resampled.frame_count() {
+            let (input_frames, output_frames) = self.process_pcm_frames(
+                &mut FramesMut::wrap::<u8>(
+                    &mut resampled.as_bytes_mut()[produced * bytes_per_frame..],
+                    format,
+                    channels,
+                ),
+                &Frames::wrap::<u8>(
+                    &looped.as_bytes()[consumed * bytes_per_frame..],
+                    format,
+                    channels,
+                ),
+            )?;
+            if input_frames == 0 && output_frames == 0 {
+                break;
+            }
+            consumed += input_frames as usize;
+            produced += output_frames as usize;
+        }
+
+        let mut resampled_f32 = FramesBuffer::new(Format::F32, channels, produced);
+        Frames::wrap::<u8>(
+            &resampled.as_bytes()[..produced * bytes_per_frame],
+            format,
+            channels,
+        )
+        .convert(&mut resampled_f32.frames_mut(), DitherMode::None);
+
+        // Output frame `j` of the resampler lines up with input frame `j / ratio - latency` of
+        // the looped buffer, which is `2 * latency` frames ahead of the start of the loop.
+        let ratio = self.config().sample_rate_out() as f64 / self.config().sample_rate_in() as f64;
+        let frames_per_target = loop_frames as f64 / target_frames as f64;
+        let channel_count = channels as usize;
+        let source = resampled_f32.frames();
+        let source = source.as_samples::<f32>();
+        let last_frame = produced.saturating_sub(1);
+
+        let mut output_f32 = FramesBuffer::new(Format::F32, channels, target_frames);
+        let mut output_frames = output_f32.frames_mut();
+        for (frame, dest) in output_frames
+            .as_samples_mut::<f32>()
+            .chunks_exact_mut(channel_count)
+            .enumerate()
+        {
+            let position = (frame as f64 * frames_per_target + 2.0 * latency as f64) * ratio;
+            let index = (position.floor() as usize).min(last_frame);
+            let next = (index + 1).min(last_frame);
+            let fraction = (position - index as f64).clamp(0.0, 1.0) as f32;
+
+            for (channel, sample) in dest.iter_mut().enumerate() {
+                let a = source
+                    .get(index * channel_count + channel)
+                    .copied()
+                    .unwrap_or(0.0);
+                let b = source
+                    .get(next * channel_count + channel)
+                    .copied()
+                    .unwrap_or(0.0);
+                *sample = a + (b - a) * fraction;
+            }
+        }
+
+        let mut output = FramesBuffer::new(format, channels, target_frames);
+        output_f32
+            .frames()
+            .convert(&mut output.frames_mut(), DitherMode::None);
+        Ok(output)
+    }
+
+    /// Drains the frames that are still cached in the resampler at the end of a stream into
+    /// `output`, by feeding it `input_latency()` frames of silence.
+    ///
+    /// Returns the number of frames that were written and whether the resampler has been drained
+    /// completely respectively. If `output` was too small to hold the whole tail this returns false
+    /// and can be called again to get the rest, so it can be called in a loop until it returns
+    /// true. After that it keeps returning no frames; `reset` the resampler to start a new stream.
+    pub fn finish(&mut self, output: &mut FramesMut) -> Result<(u64, bool), Error> {
+        let format = self.config().format();
+        let channels = self.config().channels();
+        if output.format() != format || output.channels() != channels {
+            ma_debug_panic!(
+                "output did not match resampler (output: {:?}/{}, resampler: {:?}/{})",
+                output.format(),
+                output.channels(),
+                format,
+                channels
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        let bytes_per_frame = bytes_per_frame(format, channels);
+        let silence_byte = if format == Format::U8 { 0x80 } else { 0 };
+        let silence = [silence_byte; 4096];
+        let chunk_frames = (silence.len() / bytes_per_frame) as u64;
+        let latency = self.input_latency();
+
+        let mut produced = 0;
+        while self.tail_frames_fed < latency && produced < output.frame_count() {
+            let frames = (latency - self.tail_frames_fed).min(chunk_frames) as usize;
+            let (input_frames, output_frames) = self.process_pcm_frames(
+                &mut FramesMut::wrap::<u8>(
+                    &mut output.as_bytes_mut()[produced * bytes_per_frame..],
+                    format,
+                    channels,
+                ),
+                &Frames::wrap::<u8>(&silence[..(frames * bytes_per_frame)], format, channels),
+            )?;
+            if input_frames == 0 && output_frames == 0 {
+                break;
+            }
+            self.tail_frames_fed += input_frames;
+            produced += output_frames as usize;
+        }
+
+        Ok((produced as u64, self.tail_frames_fed >= latency))
+    }
+
+    /// Resamples all of `input` in chunks and returns the output, followed by the frames that are
+    /// still cached in the resampler at the end of the input.
+    ///
+    /// The end of the input is flushed out by feeding the resampler `input_latency()` frames of
+    /// silence after it, so the output is `expected_output_frame_count` of the input plus the
+    /// latency long and starts with the latency of the resampler. Long conversions can be
+    /// cancelled with `should_continue`, which is called before each chunk of
+    /// `RESAMPLE_BUFFER_CHUNK_FRAMES` input frames. When it returns false this stops and returns
+    /// `Error::Cancelled`; to cancel from another thread, pass a closure that loads an
+    /// `AtomicBool`. The resampler is left in the middle of the stream in that case, so `reset`
+    /// it before using it again.
+    pub fn resample_buffer<F>(
+        &mut self,
+        input: &Frames,
+        should_continue: F,
+    ) -> Result<FramesBuffer, Error>
+    where
+        F: FnMut() -> bool,
+    {
+        self.resample_buffer_with_progress(input, should_continue, |_| {})
+    }
+
+    /// Like `resample_buffer`, but also reports the fraction of the input that was consumed to
+    /// `on_progress`, e.g. for a progress bar.
+    ///
+    /// The progress is checked after every chunk of `RESAMPLE_BUFFER_CHUNK_FRAMES` input frames
+    /// but only reported when it has grown by at least `PROGRESS_REPORT_STEP` since the last
+    /// report, so the callback is called about a hundred times at most no matter how long the
+    /// input is. The reported values never decrease and the last one is exactly 1.0 once the
+    /// whole input has been resampled.
+    pub fn resample_buffer_with_progress<F, P>(
+        &mut self,
+        input: &Frames,
+        mut should_continue: F,
+        on_progress: P,
+    ) -> Result<FramesBuffer, Error>
+    where
+        F: FnMut() -> bool,
+        P: FnMut(f32),
+    {
+        let format = self.config().format();
+        let channels = self.config().channels();
+        if input.format() != format || input.channels() != channels {
+            ma_debug_panic!(
+                "input did not match resampler (input: {:?}/{}, resampler: {:?}/{})",
+                input.format(),
+                input.channels(),
+                format,
+                channels
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        let bytes_per_frame = bytes_per_frame(format, channels);
+        let silence = FramesBuffer::new(format, channels, self.input_latency() as usize);
+        let input_frames = input.frame_count() + silence.frame_count();
+        let expected = self.expected_output_frame_count(input_frames as u64) as usize;
+        let mut output = FramesBuffer::new(format, channels, expected + 1);
+        let mut progress = ProgressReporter::new(on_progress);
+
+        let mut consumed = 0;
+        let mut produced = 0;
+        while consumed < input_frames && produced < output.frame_count() {
+            if !should_continue() {
+                return Err(Error::Cancelled);
+            }
+
+            // Take the next chunk from the input, or from the silence once it has run out.
+            let (source, start) = if consumed < input.frame_count() {
+                (input.as_bytes(), consumed)
+            } else {
+                (silence.as_bytes(), consumed - input.frame_count())
+            };
+            let end = (start + RESAMPLE_BUFFER_CHUNK_FRAMES).min(source.len() / bytes_per_frame);
+
+            let (input_used, output_written) = self.process_pcm_frames(
+                &mut FramesMut::wrap::<u8>(
+                    &mut output.as_bytes_mut()[produced * bytes_per_frame..],
+                    format,
+                    channels,
+                ),
+                &Frames::wrap::<u8>(
+                    &source[start * bytes_per_frame..end * bytes_per_frame],
+                    format,
+                    channels,
+                ),
+            )?;
+            if input_used == 0 && output_written == 0 {
+                break;
+            }
+            consumed += input_used as usize;
+            produced += output_written as usize;
+            progress.report(consumed as f32 / input_frames as f32);
+        }
+        progress.finish();
+
+        Ok(Frames::wrap::<u8>(
+            &output.as_bytes()[..produced * bytes_per_frame],
+            format,
+            channels,
+        )
+        .to_owned())
+    }
+
+    /// Converts interleaved frames of `N` channels, which must match the format and channel count
+    /// of the config. See `ResamplerSample` for what is checked at compile time.
+    ///
+    /// Returns the number of input frames that were consumed and the number of output frames that
+    /// were written respectively.
+    pub fn process_frames<S: ResamplerSample, const N: usize>(
+        &mut self,
+        output: &mut [[S; N]],
+        input: &[[S; N]],
+    ) -> Result<(u64, u64), Error> {
+        #[allow(clippy::let_unit_value)]
+        let _valid = ChannelCount::<N>::VALID;
+
+        // `[[S; N]]` has the same layout as a `[S]` with `N` times as many samples.
+        let output = unsafe {
+            std::slice::from_raw_parts_mut(output.as_mut_ptr().cast::<S>(), output.len() * N)
+        };
+        let input =
+            unsafe { std::slice::from_raw_parts(input.as_ptr().cast::<S>(), input.len() * N) };
+
+        self.process_pcm_frames(
+            &mut FramesMut::wrap(output, S::format(), N as u32),
+            &Frames::wrap(input, S::format(), N as u32),
+        )
+    }
+
+    /// Converts the first `input_frames` frames of `input` into at most the first `output_frames`
+    /// frames of `output`, leaving the rest of both buffers untouched. This is the same as calling
+    /// `process_pcm_frames` with sub-slices of the buffers.
+    ///
+    /// Returns `Error::InvalidArgs` if either count is larger than its buffer. Returns the number
+    /// of input frames that were consumed and the number of output frames that were written
+    /// respectively.
+    pub fn process_pcm_frames_partial(
+        &mut self,
+        output: &mut FramesMut,
+        output_frames: u64,
+        input: &Frames,
+        input_frames: u64,
+    ) -> Result<(u64, u64), Error> {
+        if output_frames > output.frame_count() as u64 || input_frames > input.frame_count() as u64
+        {
+            ma_debug_panic!(
+                "frame counts are larger than the buffers (output: {}/{}, input: {}/{})",
+                output_frames,
+                output.frame_count(),
+                input_frames,
+                input.frame_count()
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        let output_bytes =
+            output_frames as usize * bytes_per_frame(output.format(), output.channels());
+        let input_bytes = input_frames as usize * bytes_per_frame(input.format(), input.channels());
+        let (output_format, output_channels) = (output.format(), output.channels());
+        self.process_pcm_frames(
+            &mut FramesMut::wrap::<u8>(
+                &mut output.as_bytes_mut()[..output_bytes],
+                output_format,
+                output_channels,
+            ),
+            &Frames::wrap::<u8>(
+                &input.as_bytes()[..input_bytes],
+                input.format(),
+                input.channels(),
+            ),
+        )
+    }
+
+    /// Converts the given input data like `process_pcm_frames` and also reports whether the input
+    /// ran out or the output filled up (see `ProcessOutcome`). Both can be true at the same time.
+    pub fn process_ex(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+    ) -> Result<ProcessOutcome, Error> {
+        let (consumed, produced) = self.process_pcm_frames(output, input)?;
+        Ok(ProcessOutcome {
+            consumed,
+            produced,
+            needs_more_input: consumed == input.frame_count() as u64,
+            output_full: produced == output.frame_count() as u64,
+        })
+    }
+
+    /// Converts the given input data and multiplies the output frames that were written by `gain`.
+    ///
+    /// The gain is applied after resampling and only to the part of `output` that was written to,
+    /// which saves a second pass over the whole buffer. Integer samples saturate instead of
+    /// wrapping around when the gain pushes them out of range.
+    ///
+    /// Returns the number of input frames that were consumed during processing and the number of
+    /// output frames that were written to the output buffer respectively.
+    pub fn process_pcm_frames_with_gain(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+        gain: f32,
+    ) -> Result<(u64, u64), Error> {
+        let (input_frames, output_frames) = self.process_pcm_frames(output, input)?;
+        if gain != 1.0 {
+            output.apply_gain(output_frames as usize, gain);
+        }
+        Ok((input_frames, output_frames))
+    }
+
+    /// Resamples `input` straight into the writable part of a ring buffer of samples, so that a
+    /// producer thread doesn't need an intermediate buffer.
+    ///
+    /// `S` must be the sample type of the resampler's format. When the writable part of the ring
+    /// wraps around the end of its buffer the output is written in two parts. If the ring doesn't
+    /// have room for all of the output, only as much output as fits is produced and only the
+    /// input that was needed for it is consumed, so the rest of the input should be passed in
+    /// again once the consumer has caught up. Only whole frames are ever written.
+    ///
+    /// Returns the number of input frames that were consumed and the number of output frames
+    /// that were written to the ring respectively.
+    pub fn process_into_ring<S: Sample + Clone>(
+        &mut self,
+        ring: &RingBufferSend<S>,
+        input: &Frames,
+    ) -> Result<(u64, u64), Error> {
+        let format = self.config().format();
+        let channels = self.config().channels();
+        if S::format() != format || input.format() != format || input.channels() != channels {
+            ma_debug_panic!(
+                "ring or input did not match resampler (ring: {:?}, input: {:?}/{}, resampler: {:?}/{})",
+                S::format(),
+                input.format(),
+                input.channels(),
+                format,
+                channels
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        let bytes_per_frame = bytes_per_frame(format, channels);
+        let channel_count = channels as usize;
+        let mut consumed = 0;
+        let mut produced = 0;
+
+        // The second write is for the part of the writable region after the ring wraps around.
+        for _ in 0..2 {
+            let remaining = &input.as_bytes()[consumed as usize * bytes_per_frame..];
+            let remaining_frames = (remaining.len() / bytes_per_frame) as u64;
+            let wanted = self.expected_output_frame_count(remaining_frames) as usize;
+            if wanted == 0 {
+                break;
+            }
+
+            let mut result = Ok((0, 0));
+            ring.write_partial_with(wanted * channel_count, |dest| {
+                let whole_frames = dest.len() / channel_count * channel_count;
+                result = self.process_pcm_frames(
+                    &mut FramesMut::wrap(&mut dest[..whole_frames], format, channels),
+                    &Frames::wrap::<u8>(remaining, format, channels),
+                );
+                result.map_or(0, |(_, written)| written as usize * channel_count)
+            });
+
+            let (input_frames, output_frames) = result?;
+            consumed += input_frames;
+            produced += output_frames;
+            if output_frames == 0 {
+                break;
+            }
+        }
+
+        Ok((consumed, produced))
+    }
+
+    /// Sets the input and output sample rate.
+    #[inline]
+    pub fn set_rate(&mut self, sample_rate_in: u32, sample_rate_out: u32) -> Result<(), Error> {
+        match &mut self.backend {
+            ResamplerBackend::Miniaudio(raw) => Error::from_c_result(unsafe {
+                sys::ma_resampler_set_rate(raw, sample_rate_in, sample_rate_out)
+            })?,
+            ResamplerBackend::Sinc(sinc) => sinc.set_rate(sample_rate_in, sample_rate_out)?,
+        }
+
+        self.config.set_sample_rate_in(sample_rate_in);
+        self.config.set_sample_rate_out(sample_rate_out);
+        Ok(())
+    }
+
+    /// Sets the input and output sample rate as a ratio.
+    ///
+    /// The ratio is in/out.
+    #[inline]
+    pub fn set_rate_ratio(&mut self, ratio_in_out: f32) -> Result<(), Error> {
+        match &mut self.backend {
+            ResamplerBackend::Miniaudio(raw) => {
+                Error::from_c_result(unsafe {
+                    sys::ma_resampler_set_rate_ratio(raw, ratio_in_out)
+                })?;
+                self.config.0.sampleRateIn = raw.config.sampleRateIn;
+                self.config.0.sampleRateOut = raw.config.sampleRateOut;
+                Ok(())
+            }
+
+            // This uses the same precision as miniaudio.
+            ResamplerBackend::Sinc(_) => {
+                let denominator = 1000;
+                let numerator = (ratio_in_out * denominator as f32) as u32;
+                if numerator == 0 {
+                    return Err(Error::InvalidArgs);
+                }
+                self.set_rate(numerator, denominator)
+            }
+        }
+    }
+
+    /// Sets the input and output sample rate like `set_rate`, but crossfades from the output at
+    /// the previous rate to the output at the new rate over the next `fade_frames` output frames.
+    /// `curve` shapes the gains of both sides of the crossfade, `FadeCurve::EqualPower` keeps the
+    /// level steady and `FadeCurve::Linear` dips in the middle.
+    ///
+    /// Changing the rate moves the interpolation position, which can be heard as a click. The
+    /// crossfade masks it, which is useful when the rate follows a pitch control in a UI.
+    ///
+    /// During the fade a copy of the resampler keeps running at the previous rate over the same
+    /// input, so processing costs about twice as much until the fade is done, and a buffer of
+    /// `fade_frames` output frames is allocated by this call. The copy keeps its own position in
+    /// the input: the input that it falls behind on is buffered, and the input that it consumed
+    /// ahead of the new rate is skipped when it is passed in again. When the previous rate consumes
+    /// the input faster, the copy can only run as far ahead as the input that is passed in, so
+    /// pass in more input than the new rate needs to keep it from coming up short. Calling this
+    /// again during a fade starts a new fade from the rate that was set most recently. Passing 0
+    /// for `fade_frames` is the same as calling `set_rate`.
+    ///
+    /// The state of the Speex resampler can't be copied, so this returns
+    /// `Error::InvalidOperation` for it.
+    pub fn set_rate_crossfaded(
+        &mut self,
+        sample_rate_in: u32,
+        sample_rate_out: u32,
+        fade_frames: u64,
+        curve: FadeCurve,
+    ) -> Result<(), Error> {
+        if fade_frames == 0 {
+            self.rate_crossfade = None;
+            return self.set_rate(sample_rate_in, sample_rate_out);
+        }
+
+        let backend = match &self.backend {
+            ResamplerBackend::Miniaudio(raw) => {
+                if self.config.algorithm().algorithm_type() != ResampleAlgorithmType::Linear {
+                    ma_debug_panic!("the Speex resampler can't be crossfaded");
+                    return Err(Error::InvalidOperation);
+                }
+                // The linear resampler doesn't own anything, so its state can just be copied.
+                ResamplerBackend::Miniaudio(*raw)
+            }
+            ResamplerBackend::Sinc(sinc) => ResamplerBackend::Sinc(sinc.clone()),
+        };
+
+        let outgoing = Resampler {
+            config: self.config.clone(),
+            backend,
+            input_frames_consumed: self.input_frames_consumed,
+            rate_crossfade: None,
+            tail_frames_fed: 0,
+            read_input: ReadInput::default(),
+        };
+
+        self.set_rate(sample_rate_in, sample_rate_out)?;
+        self.rate_crossfade = Some(Box::new(RateCrossfade {
+            outgoing,
+            fade_frames,
+            curve,
+            position: 0,
+            buffer: FramesBuffer::new(
+                self.config.backend_config().format(),
+                self.config.channels(),
+                fade_frames as usize,
+            ),
+            input: Vec::new(),
+        }));
+        Ok(())
+    }
+
+    /// Calculates the number of whole input frames that would need to be read from the client in
+    /// order to output the specified number of output frames.
+    ///
+    /// The returned value does not include cached input frames. It only returns the number of
+    /// extra frames that would need to be read from the input buffer in order to output the
+    /// specified number of output frames.
+    #[inline]
+    pub fn required_input_frame_count(&self, output_frame_count: u64) -> u64 {
+        match &self.backend {
+            ResamplerBackend::Miniaudio(raw) => unsafe {
+                sys::ma_resampler_get_required_input_frame_count(
+                    raw as *const _ as *mut _,
+                    output_frame_count,
+                )
+            },
+            ResamplerBackend::Sinc(sinc) => sinc.required_input_frame_count(output_frame_count),
+        }
+    }
+
+    /// Calculates the number of whole output frames that would be output after fully reading and
+    /// consuming the specified number of input frames.
+    #[inline]
+    pub fn expected_output_frame_count(&self, input_frame_count: u64) -> u64 {
+        match &self.backend {
+            ResamplerBackend::Miniaudio(raw) => unsafe {
+                sys::ma_resampler_get_expected_output_frame_count(
+                    raw as *const _ as *mut _,
+                    input_frame_count,
+                )
+            },
+            ResamplerBackend::Sinc(sinc) => sinc.expected_output_frame_count(input_frame_count),
+        }
+    }
+
+    /// Retrieves the latency introduced by the resampler in input frames.
+    #[inline]
+    pub fn input_latency(&mut self) -> u64 {
+        match &self.backend {
+            ResamplerBackend::Miniaudio(raw) => unsafe {
+                sys::ma_resampler_get_input_latency(raw as *const _ as *mut _)
+            },
+            ResamplerBackend::Sinc(sinc) => sinc.input_latency(),
+        }
+    }
+
+    /// Retrieves the latency introduced by the resampler in output frames.
+    ///
+    /// This is the fixed latency of the filters and doesn't depend on how much input was pushed
+    /// through so far, see `remaining_output_frames` for the frames that are actually held.
+    #[inline]
+    pub fn output_latency(&mut self) -> u64 {
+        match &self.backend {
+            ResamplerBackend::Miniaudio(raw) => unsafe {
+                sys::ma_resampler_get_output_latency(raw as *const _ as *mut _)
+            },
+            ResamplerBackend::Sinc(sinc) => sinc.output_latency(),
+        }
+    }
+
+    /// Returns the position in the input stream, in input frames since the resampler was
+    /// created or reset, that the next output frame will be interpolated at.
+    ///
+    /// This is the number of input frames that were consumed minus the ones that are still cached
+    /// for interpolation, plus the fractional position between two input frames. It starts out
+    /// negative because the first output frames are interpolated from the silence that the
+    /// resampler starts with. The position does not include the delay of the low-pass filter, so
+    /// the audio that is heard at the next output frame is from `input_latency()` frames earlier
+    /// than this for the sinc resampler and the filtered linear resampler. For the Speex resampler
+    /// miniaudio doesn't expose the interpolation state, so the position is estimated as the
+    /// consumed frames minus the input latency.
+    pub fn source_position(&self) -> f64 {
+        let consumed = self.input_frames_consumed as f64;
+        match &self.backend {
+            ResamplerBackend::Miniaudio(raw) => {
+                match self.config.algorithm().algorithm_type() {
+                    // x0 and x1 hold the last two input frames and the next output frame is
+                    // `inTimeInt + inTimeFrac / sampleRateOut` frames after x0.
+                    ResampleAlgorithmType::Linear => unsafe {
+                        let linear = &raw.state.linear;
+                        consumed - 2.0
+                            + linear.inTimeInt as f64
+                            + linear.inTimeFrac as f64 / linear.config.sampleRateOut as f64
+                    },
+                    _ => {
+                        let latency = unsafe {
+                            sys::ma_resampler_get_input_latency(raw as *const _ as *mut _)
+                        };
+                        consumed - latency as f64
+                    }
+                }
+            }
+            ResamplerBackend::Sinc(sinc) => consumed + sinc.position_offset(),
+        }
+    }
+
+    /// Returns the number of output frames that are still held inside of the resampler.
+    ///
+    /// These are the frames of the input that was already consumed which are still in the
+    /// interpolation and low-pass filter history and have not been written to an output buffer
+    /// yet. This is 0 before any input was consumed and grows to the output latency of the
+    /// resampler once its history is filled. The frames only come out once more input is pushed
+    /// through, so to drain them at the end of a stream feed the resampler `input_latency()` frames
+    /// of silence. Adding `RawDevice::current_latency_frames` to this gives the total amount of
+    /// audio that is still in flight when playback of a resampled stream is stopped.
+    pub fn remaining_output_frames(&self) -> u64 {
+        let consumed = self.input_frames_consumed as f64;
+
+        // `source_position` is where the next output frame is interpolated, so everything that was
+        // consumed after it is still held, plus the delay of the low-pass filter of the linear
+        // resampler, which the position doesn't include. The Speex position is already estimated
+        // from the full latency.
+        let filter_delay = match &self.backend {
+            ResamplerBackend::Miniaudio(raw) => match self.config.algorithm().algorithm_type() {
+                ResampleAlgorithmType::Linear => unsafe {
+                    sys::ma_resampler_get_input_latency(raw as *const _ as *mut _).saturating_sub(1)
+                        as f64
+                },
+                _ => 0.0,
+            },
+            ResamplerBackend::Sinc(_) => 0.0,
+        };
+        let held_input = (consumed - self.source_position() + filter_delay).clamp(0.0, consumed);
+
+        let ratio = self.config.sample_rate_out() as f64 / self.config.sample_rate_in() as f64;
+        (held_input * ratio).round() as u64
+    }
+}
+
+/// Resamples `input` from `sample_rate` to `intermediate_rate` and back, and returns a buffer with
+/// exactly as many frames as `input`, e.g. to process audio at a lower rate and then overwrite the
+/// original in place.
+///
+/// The delay of both resamplers is removed so that the output lines up with the input, and the
+/// end is padded with silence if the resamplers came up short. The alignment is exact for the
+/// sinc resampler. The low-pass filter of the linear resampler isn't linear phase and delays the
+/// audio by less than its reported latency, so its output can come out a few frames early. The round trip is lossy: when
+/// `intermediate_rate` is lower than `sample_rate` everything above its Nyquist frequency is
+/// removed, and each pass adds the interpolation error of `algorithm`, which is audible for the
+/// linear resampler without a low-pass filter. The first and last few frames are also affected by
+/// the silence that the resamplers start and end with.
+pub fn resample_roundtrip_to_length(
+    input: &Frames,
+    sample_rate: u32,
+    intermediate_rate: u32,
+    algorithm: ResampleAlgorithm,
+) -> Result<FramesBuffer, Error> {
+    let format = input.format();
+    let channels = input.channels();
+
+    let mut config = ResamplerConfig::new(
+        format,
+        channels,
+        sample_rate,
+        intermediate_rate,
+        algorithm.algorithm_type(),
+    );
+    config.set_algorithm(algorithm);
+    let mut down = Resampler::new(&config)?;
+    config.set_sample_rate_in(intermediate_rate);
+    config.set_sample_rate_out(sample_rate);
+    let mut up = Resampler::new(&config)?;
+
+    // The latency of the second pass is in intermediate frames, which become longer or shorter by
+    // the ratio. Both are added up before rounding because the output latencies that miniaudio
+    // reports are rounded down to whole frames.
+    let delay = (down.input_latency() as f64
+        + up.input_latency() as f64 * sample_rate as f64 / intermediate_rate as f64)
+        .round() as u64;
+
+    let intermediate = down.resample_buffer(input, || true)?;
+    let roundtrip = up.resample_buffer(&intermediate.frames(), || true)?;
+
+    let bytes_per_frame = bytes_per_frame(format, channels);
+    let start = (delay as usize).min(roundtrip.frame_count()) * bytes_per_frame;
+    let end = (start + input.byte_count()).min(roundtrip.as_bytes().len());
+
+    let mut output = FramesBuffer::new(format, channels, input.frame_count());
+    output.as_bytes_mut()[..(end - start)].copy_from_slice(&roundtrip.as_bytes()[start..end]);
+    Ok(output)
+}
+
+impl Clone for Resampler {
+    fn clone(&self) -> Self {
+        // This should not fail if the resampler was properly initialized.
+        Self::new(self.config()).expect("failed to clone resampler")
+    }
+}
+
+impl Drop for Resampler {
+    fn drop(&mut self) {
+        if let ResamplerBackend::Miniaudio(raw) = &mut self.backend {
+            unsafe { sys::ma_resampler_uninit(raw) };
+        }
+    }
+}
+
+// SAFETY: `ma_resampler` is only `!Send` because of the raw pointer to the Speex state. That
+// state is allocated for this resampler alone and is freed in `drop`, nothing else points to it,
+// and neither it nor the linear resampler's inline state is tied to the thread that created it.
+// `Resampler` isn't `Sync`, so it's only ever used from one thread at a time. The boxed
+// crossfade state owns its outgoing `Resampler`, which is `Send` for the same reason, and
+// otherwise only holds plain buffers, so moving the box moves all of its state with it.
+unsafe impl Send for Resampler {}
+
+/// Lends out resamplers with the same config so that they can be reused, e.g. by a server that
+/// converts many short clips. This mostly helps with the Speex resampler which allocates when it
+/// is initialized.
+///
+/// Resamplers are returned to the pool and reset when the `PooledResampler` that is lending them
+/// out is dropped. At most `max_size` idle resamplers are kept and any others are dropped. The
+/// idle resamplers are kept behind a mutex, so a pool can be shared by several worker threads.
+pub struct ResamplerPool {
+    config: ResamplerConfig,
+    max_size: usize,
+    idle: Mutex<Vec<Resampler>>,
+}
+
+impl ResamplerPool {
+    pub fn new(config: &ResamplerConfig, max_size: usize) -> ResamplerPool {
+        ResamplerPool {
+            config: config.clone(),
+            max_size,
+            idle: Mutex::new(Vec::with_capacity(max_size)),
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> &ResamplerConfig {
+        &self.config
+    }
+
+    #[inline]
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Returns the number of resamplers that are currently waiting in the pool.
+    #[inline]
+    pub fn idle_count(&self) -> usize {
+        self.idle().len()
+    }
+
+    /// Lends out an idle resampler, or creates a new one if there are none.
+    pub fn acquire(&self) -> Result<PooledResampler<'_>, Error> {
+        let idle = self.idle().pop();
+        let resampler = match idle {
+            Some(resampler) => resampler,
+            None => Resampler::new(&self.config)?,
+        };
+
+        Ok(PooledResampler {
+            pool: self,
+            resampler: Some(resampler),
+        })
+    }
+
+    /// Locks the idle resamplers. A panic while they are locked can't leave the list half
+    /// changed, so a poisoned lock is used anyway.
+    fn idle(&self) -> MutexGuard<'_, Vec<Resampler>> {
+        self.idle.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A resampler that was lent out by a `ResamplerPool`.
+pub struct PooledResampler<'p> {
+    pool: &'p ResamplerPool,
+    resampler: Option<Resampler>,
+}
+
+impl std::ops::Deref for PooledResampler<'_> {
+    type Target = Resampler;
+
+    fn deref(&self) -> &Resampler {
+        self.resampler
+            .as_ref()
+            .expect("pooled resampler was already returned")
+    }
+}
+
+impl std::ops::DerefMut for PooledResampler<'_> {
+    fn deref_mut(&mut self) -> &mut Resampler {
+        self.resampler
+            .as_mut()
+            .expect("pooled resampler was already returned")
+    }
+}
+
+impl Drop for PooledResampler<'_> {
+    fn drop(&mut self) {
+        if let Some(mut resampler) = self.resampler.take() {
+            let mut idle = self.pool.idle();
+            if idle.len() < self.pool.max_size {
+                resampler.reset();
+                idle.push(resampler);
+            }
+        }
+    }
+}
+
+/// The parts of a resampler that can be computed once and shared by many resamplers with the same
+/// config, e.g. by a batch server that converts thousands of clips between the same two rates.
+///
+/// Only the kernel of the sinc resampler can be shared. The Speex resampler computes its filter
+/// tables inside of miniaudio, so Speex and linear resamplers created from a plan are the same as
+/// ones created with `Resampler::new` and a plan for them holds nothing but the config. A plan is
+/// read-only, so it is `Send` and `Sync` and can be shared between threads, e.g. in an `Arc`.
+#[derive(Clone)]
+pub struct ResamplePlan {
+    config: ResamplerConfig,
+    sinc_kernel: Option<Arc<Vec<f32>>>,
+}
+
+impl ResamplePlan {
+    /// Creates a plan for `config`. For the sinc algorithm this computes the kernel, so it fails
+    /// with the same errors as `Resampler::new`. For the other algorithms only the availability of
+    /// the algorithm is checked here and the rest of the config is checked by
+    /// `Resampler::from_plan`.
+    pub fn new(config: &ResamplerConfig) -> Result<ResamplePlan, Error> {
+        if !config.algorithm().is_available() {
+            return Err(Error::NoBackend);
+        }
+
+        let sinc_kernel = if let ResampleAlgorithm::Sinc {
+            window_length,
+            beta,
+        } = config.algorithm()
+        {
+            let sinc = SincResampler::new(
+                config.backend_config().format(),
+                config.channels(),
+                config.sample_rate_in(),
+                config.sample_rate_out(),
+                window_length,
+                beta,
+                None,
+            )?;
+            Some(sinc.kernel().clone())
+        } else {
+            None
+        };
+
+        Ok(ResamplePlan {
+            config: config.clone(),
+            sinc_kernel,
+        })
+    }
+
+    #[inline]
+    pub fn config(&self) -> &ResamplerConfig {
+        &self.config
+    }
+}
+
+/// Two resamplers that are fed from the same input, e.g. to produce a 48kHz stream for playback
+/// and a 16kHz stream for speech recognition from the same source.
+///
+/// Both resamplers always consume the same number of input frames so they stay in sync.
+pub struct ResamplerTee {
+    first: Resampler,
+    second: Resampler,
+}
+
+impl ResamplerTee {
+    /// Creates a tee from two configs. Both configs must have the same format, channel count and
+    /// input sample rate.
+    pub fn new(first: &ResamplerConfig, second: &ResamplerConfig) -> Result<ResamplerTee, Error> {
+        if first.format() != second.format()
+            || first.channels() != second.channels()
+            || first.sample_rate_in() != second.sample_rate_in()
+        {
+            ma_debug_panic!("resampler tee configs did not have the same input properties");
+            return Err(Error::InvalidArgs);
+        }
+
+        Ok(ResamplerTee {
+            first: Resampler::new(first)?,
+            second: Resampler::new(second)?,
+        })
+    }
+
+    #[inline]
+    pub fn first(&self) -> &Resampler {
+        &self.first
+    }
+
+    #[inline]
+    pub fn second(&self) -> &Resampler {
+        &self.second
+    }
+
+    /// Converts the given input data into both outputs.
+    ///
+    /// Only as much input is consumed as both outputs have room for, so the output that would
+    /// fill up first limits how much of the input is used.
+    ///
+    /// Returns the number of input frames that were consumed and the number of output frames that
+    /// were written to `first_output` and `second_output` respectively.
+    pub fn process_pcm_frames(
+        &mut self,
+        first_output: &mut FramesMut,
+        second_output: &mut FramesMut,
+        input: &Frames,
+    ) -> Result<(u64, (u64, u64)), Error> {
+        let input_frames = (input.frame_count() as u64)
+            .min(
+                self.first
+                    .required_input_frame_count(first_output.frame_count() as u64),
+            )
+            .min(
+                self.second
+                    .required_input_frame_count(second_output.frame_count() as u64),
+            );
+
+        let bytes_per_frame = bytes_per_frame(input.format(), input.channels());
+        let input = Frames::wrap::<u8>(
+            &input.as_bytes()[..input_frames as usize * bytes_per_frame],
+            input.format(),
+            input.channels(),
+        );
+
+        let (first_consumed, first_written) =
+            self.first.process_pcm_frames(first_output, &input)?;
+        let (second_consumed, second_written) =
+            self.second.process_pcm_frames(second_output, &input)?;
+        debug_assert_eq!(
+            first_consumed, second_consumed,
+            "resamplers in tee consumed a different number of frames"
+        );
+
+        Ok((
+            first_consumed.min(second_consumed),
+            (first_written, second_written),
+        ))
+    }
+}
+
+/// A `Resampler` that keeps track of presentation timestamps, e.g. to keep audio aligned with
+/// subtitles or a transcript after its sample rate was converted.
+///
+/// Every chunk of input is tagged with the timestamp of its first frame. The timestamp of the
+/// first output frame of each call is interpolated from the position in the input that the
+/// resampler is at (see `Resampler::source_position`) and the timestamp of the chunk that position
+/// falls into, so it follows rate changes and stays exact over long streams. Input frames that
+/// were not consumed can be passed again with the timestamp of their first frame, or tagged with
+/// the same timestamp as before, since only the position of the earliest untouched frame matters.
+pub struct TimedResampler {
+    resampler: Resampler,
+    /// The input frame index (as counted by the resampler) and timestamp of the start of each
+    /// chunk that output can still be interpolated from, oldest first.
+    tags: VecDeque<(u64, Duration)>,
+    /// The sample rate that the input is played back at. This only changes with `set_rate`, not
+    /// when the ratio is adjusted to compensate for drift.
+    sample_rate_in: u32,
+}
+
+impl TimedResampler {
+    pub fn new(config: &ResamplerConfig) -> Result<TimedResampler, Error> {
+        Ok(TimedResampler {
+            resampler: Resampler::new(config)?,
+            tags: VecDeque::new(),
+            sample_rate_in: config.sample_rate_in(),
+        })
+    }
+
+    #[inline]
+    pub fn resampler(&self) -> &Resampler {
+        &self.resampler
+    }
+
+    /// Changes the sample rates. Timestamps of output frames that follow are interpolated with
+    /// the new ratio.
+    pub fn set_rate(&mut self, sample_rate_in: u32, sample_rate_out: u32) -> Result<(), Error> {
+        self.resampler.set_rate(sample_rate_in, sample_rate_out)?;
+        self.sample_rate_in = sample_rate_in;
+        Ok(())
+    }
+
+    /// Changes the ratio without changing the sample rate that the input is played back at,
+    /// e.g. to compensate for clock drift. Timestamps still advance with the input's sample rate,
+    /// so they stay aligned with the input while the output speeds up or slows down.
+    pub fn set_rate_ratio(&mut self, ratio_in_out: f32) -> Result<(), Error> {
+        self.resampler.set_rate_ratio(ratio_in_out)
+    }
+
+    /// Clears the resampler and all timestamps so that it can be used for a new stream.
+    pub fn reset(&mut self) {
+        self.resampler.reset();
+        self.tags.clear();
+    }
+
+    /// Converts the given input data, where `timestamp` is the presentation timestamp of the first
+    /// frame of `input`.
+    ///
+    /// Returns the number of input frames that were consumed, the number of output frames that
+    /// were written and the timestamp of the first output frame respectively. The first output
+    /// frames of a stream are interpolated from the silence that the resampler starts with, so
+    /// their timestamps are extrapolated back from the first chunk and clamped to zero. Like
+    /// `source_position` the timestamp doesn't include the delay of the resampler's low-pass
+    /// filter.
+    pub fn process_pcm_frames(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+        timestamp: Duration,
+    ) -> Result<(u64, u64, Duration), Error> {
+        let start = self.resampler.input_frames_consumed;
+        while self
+            .tags
+            .back()
+            .is_some_and(|&(tag_start, _)| tag_start >= start)
+        {
+            self.tags.pop_back();
+        }
+        self.tags.push_back((start, timestamp));
+
+        let output_timestamp = self.timestamp_at(self.resampler.source_position());
+        let (consumed, produced) = self.resampler.process_pcm_frames(output, input)?;
+
+        // Tags before the one that the next output frame falls into are not needed anymore.
+        let position = self.resampler.source_position();
+        while self.tags.len() > 1 && (self.tags[1].0 as f64) <= position {
+            self.tags.pop_front();
+        }
+
+        Ok((consumed, produced, output_timestamp))
+    }
+
+    /// Interpolates the timestamp of a position in the input stream.
+    fn timestamp_at(&self, position: f64) -> Duration {
+        let (tag_start, tag_timestamp) = self
+            .tags
+            .iter()
+            .rev()
+            .find(|&&(tag_start, _)| tag_start as f64 <= position)
+            .or_else(|| self.tags.front())
+            .copied()
+            .unwrap_or((0, Duration::ZERO));
+
+        let offset = (position - tag_start as f64) / self.sample_rate_in as f64;
+        Duration::from_secs_f64((tag_timestamp.as_secs_f64() + offset).max(0.0))
+    }
+}
+
+/// The number of input frames that `TwoStageResampler` filters and decimates at a time.
+const TWO_STAGE_CHUNK_FRAMES: usize = 1024;
+
+/// A resampler for large downsampling ratios (e.g. 192kHz to 8kHz) that first decimates the input
+/// by an integer factor and then resamples the rest of the way with a regular `Resampler`.
+///
+/// The decimation stage low-pass filters the input below the Nyquist frequency of the output
+/// sample rate and then keeps every Nth frame. This is much cheaper than running the fractional
+/// resampler at the full input rate and, because the anti-aliasing filter runs at the input rate,
+/// it removes far more of the content that would otherwise alias back into the output when using
+/// the linear resampler on its own.
+///
+/// The decimation factor is chosen automatically as the largest factor that divides the input
+/// sample rate and keeps the intermediate sample rate at or above the output sample rate. When
+/// there is no such factor above 1 (e.g. when upsampling) this behaves like a plain `Resampler`.
+/// Like the linear resampler and the low-pass filter only `F32` and `S16` are supported.
+pub struct TwoStageResampler {
+    decimation: u32,
+    lpf: Option<LPF>,
+    resampler: Resampler,
+    phase: u32,
+    filtered: FramesBuffer,
+    pending: FramesBuffer,
+    pending_start: usize,
+    pending_end: usize,
+}
+
+impl TwoStageResampler {
+    pub fn new(config: &ResamplerConfig) -> Result<TwoStageResampler, Error> {
+        let sample_rate_in = config.sample_rate_in();
+        let sample_rate_out = config.sample_rate_out();
+
+        let max_decimation = sample_rate_in
+            .checked_div(sample_rate_out)
+            .unwrap_or(1)
+            .max(1);
+        let decimation = (1..=max_decimation)
+            .rev()
+            .find(|&factor| sample_rate_in / factor * factor == sample_rate_in)
+            .unwrap_or(1);
+
+        let lpf = if decimation > 1 {
+            Some(LPF::new(&LPFConfig::new(
+                config.format(),
+                config.channels(),
+                sample_rate_in,
+                sample_rate_out as f64 * 0.45,
+                MAX_FILTER_ORDER as u32,
+            ))?)
+        } else {
+            None
+        };
+
+        let mut resampler_config = config.clone();
+        resampler_config.set_sample_rate_in(sample_rate_in / decimation);
+
+        let (format, channels) = (config.format(), config.channels());
+        Ok(TwoStageResampler {
+            decimation,
+            lpf,
+            resampler: Resampler::new(&resampler_config)?,
+            phase: 0,
+            filtered: FramesBuffer::new(format, channels, TWO_STAGE_CHUNK_FRAMES),
+            pending: FramesBuffer::new(format, channels, TWO_STAGE_CHUNK_FRAMES),
+            pending_start: 0,
+            pending_end: 0,
+        })
+    }
+
+    /// The factor that the input is decimated by before it is resampled.
+    #[inline]
+    pub fn decimation(&self) -> u32 {
+        self.decimation
+    }
+
+    /// The resampler that converts the decimated frames to the output sample rate.
+    #[inline]
+    pub fn resampler(&self) -> &Resampler {
+        &self.resampler
+    }
+
+    /// Converts the given input data.
+    ///
+    /// Both `output` and `input` must have the format and channel count that the resampler was
+    /// configured with.
+    ///
+    /// Returns the number of input frames that were consumed during processing and the number of
+    /// output frames that were written to the output buffer respectively.
+    pub fn process_pcm_frames(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+    ) -> Result<(u64, u64), Error> {
+        let format = self.resampler.config().format();
+        let channels = self.resampler.config().channels();
+        check_frames_match_config(format, channels, output, input)?;
+
+        let bytes_per_frame = bytes_per_frame(format, channels);
+        let output_frames = output.frame_count();
+        let input_frames = input.frame_count();
+        let mut consumed = 0;
+        let mut produced = 0;
+
+        loop {
+            if self.pending_start == self.pending_end && consumed < input_frames {
+                let remaining = (output_frames - produced) as u64;
+                let required = self.resampler.required_input_frame_count(remaining) as usize;
+                let take = (required * self.decimation as usize)
+                    .clamp(1, TWO_STAGE_CHUNK_FRAMES)
+                    .min(input_frames - consumed);
+
+                let chunk = Frames::wrap::<u8>(
+                    &input.as_bytes()
+                        [consumed * bytes_per_frame..(consumed + take) * bytes_per_frame],
+                    format,
+                    channels,
+                );
+                self.decimate(&chunk, bytes_per_frame)?;
+                consumed += take;
+            }
+
+            let pending = self.pending.frames();
+            let pending = Frames::wrap::<u8>(
+                &pending.as_bytes()
+                    [self.pending_start * bytes_per_frame..self.pending_end * bytes_per_frame],
+                format,
+                channels,
+            );
+            let mut output = FramesMut::wrap::<u8>(
+                &mut output.as_bytes_mut()[produced * bytes_per_frame..],
+                format,
+                channels,
+            );
+            let (resampler_consumed, written) =
+                self.resampler.process_pcm_frames(&mut output, &pending)?;
+            self.pending_start += resampler_consumed as usize;
+            produced += written as usize;
+
+            let input_left = consumed < input_frames || self.pending_start < self.pending_end;
+            if produced == output_frames || !input_left || (written == 0 && resampler_consumed == 0)
+            {
+                break;
+            }
+        }
+
+        Ok((consumed as u64, produced as u64))
+    }
+
+    /// Filters and decimates `chunk` into the pending buffer, which must be empty.
+    fn decimate(&mut self, chunk: &Frames, bytes_per_frame: usize) -> Result<(), Error> {
+        let frame_count = chunk.frame_count();
+        let filtered_bytes = &mut self.filtered.as_bytes_mut()[..frame_count * bytes_per_frame];
+
+        match self.lpf {
+            Some(ref mut lpf) => lpf.process_pcm_frames(
+                &mut FramesMut::wrap::<u8>(filtered_bytes, chunk.format(), chunk.channels()),
+                chunk,
+            )?,
+            None => filtered_bytes.copy_from_slice(chunk.as_bytes()),
+        }
+
+        let filtered = self.filtered.as_bytes();
+        let pending = self.pending.as_bytes_mut();
+        let mut kept = 0;
+        for frame in filtered[..frame_count * bytes_per_frame].chunks_exact(bytes_per_frame) {
+            if self.phase == 0 {
+                pending[kept * bytes_per_frame..(kept + 1) * bytes_per_frame]
+                    .copy_from_slice(frame);
+                kept += 1;
+                self.phase = self.decimation - 1;
+            } else {
+                self.phase -= 1;
+            }
+        }
+
+        self.pending_start = 0;
+        self.pending_end = kept;
+        Ok(())
+    }
+}
+
+/// An object safe interface over the different resampler types that operates on raw bytes. This
+/// allows resamplers with different algorithms to be stored together, e.g. as a
+/// `Vec<Box<dyn DynamicResample>>`.
+///
+/// The bytes are interpreted using the format and channel count that the resampler was configured
+/// with.
+pub trait DynamicResample {
+    /// Converts the given input bytes, placing the results in `output`. The samples of both
+    /// `input` and `output` are in `byte_order`. Input that is not in the native byte order is
+    /// copied so that it can be swapped, which allocates.
+    ///
+    /// Returns the number of input frames that were consumed during processing and the number of
+    /// output frames that were written to the output buffer respectively.
+    fn process_bytes(
+        &mut self,
+        output: &mut [u8],
+        input: &[u8],
+        byte_order: ByteOrder,
+    ) -> Result<(u64, u64), Error>;
+
+    /// Sets the input and output sample rate as a ratio.
+    ///
+    /// The ratio is in/out.
+    fn set_rate_ratio(&mut self, ratio_in_out: f32) -> Result<(), Error>;
+}
+
+/// Swaps `input` into the native byte order if needed, processes it and swaps the frames that were
+/// written to `output` back into `byte_order`.
+fn process_bytes_in_order<F>(
+    format: Format,
+    channels: u32,
+    output: &mut [u8],
+    input: &[u8],
+    byte_order: ByteOrder,
+    process: F,
+) -> Result<(u64, u64), Error>
+where
+    F: FnOnce(&mut FramesMut, &Frames) -> Result<(u64, u64), Error>,
+{
+    let swapped;
+    let input = if byte_order.is_native() {
+        input
+    } else {
+        swapped = {
+            let mut swapped = input.to_vec();
+            swap_sample_bytes(&mut swapped, format, byte_order);
+            swapped
+        };
+        &swapped[..]
+    };
+
+    let (consumed, produced) = process(
+        &mut FramesMut::wrap::<u8>(output, format, channels),
+        &Frames::wrap::<u8>(input, format, channels),
+    )?;
+
+    let written = produced as usize * bytes_per_frame(format, channels);
+    swap_sample_bytes(&mut output[..written], format, byte_order);
+    Ok((consumed, produced))
+}
+
+impl DynamicResample for LinearResampler {
+    fn process_bytes(
+        &mut self,
+        output: &mut [u8],
+        input: &[u8],
+        byte_order: ByteOrder,
+    ) -> Result<(u64, u64), Error> {
+        let format = self.config().format();
+        let channels = self.config().channels();
+        process_bytes_in_order(
+            format,
+            channels,
+            output,
+            input,
+            byte_order,
+            |output, input| {
+                let (output_frames, input_frames) = self.process_pcm_frames(output, input)?;
+                Ok((input_frames, output_frames))
+            },
+        )
+    }
+
+    fn set_rate_ratio(&mut self, ratio_in_out: f32) -> Result<(), Error> {
+        LinearResampler::set_rate_ratio(self, ratio_in_out)
+    }
+}
+
+impl DynamicResample for MultiChannelLinearResampler {
+    fn process_bytes(
+        &mut self,
+        output: &mut [u8],
+        input: &[u8],
+        byte_order: ByteOrder,
+    ) -> Result<(u64, u64), Error> {
+        let format = self.format();
+        let channels = self.channels();
+        process_bytes_in_order(
+            format,
+            channels,
+            output,
+            input,
+            byte_order,
+            |output, input| {
+                let (output_frames, input_frames) = self.process_pcm_frames(output, input)?;
+                Ok((input_frames, output_frames))
+            },
+        )
+    }
+
+    fn set_rate_ratio(&mut self, ratio_in_out: f32) -> Result<(), Error> {
+        MultiChannelLinearResampler::set_rate_ratio(self, ratio_in_out)
+    }
+}
+
+impl DynamicResample for Resampler {
+    fn process_bytes(
+        &mut self,
+        output: &mut [u8],
+        input: &[u8],
+        byte_order: ByteOrder,
+    ) -> Result<(u64, u64), Error> {
+        let format = self.config().format();
+        let channels = self.config().channels();
+        process_bytes_in_order(
+            format,
+            channels,
+            output,
+            input,
+            byte_order,
+            |output, input| self.process_pcm_frames(output, input),
+        )
+    }
+
+    fn set_rate_ratio(&mut self, ratio_in_out: f32) -> Result<(), Error> {
+        Resampler::set_rate_ratio(self, ratio_in_out)
+    }
+}
+
+/// What a `ResamplingReader` does when its inner reader returns bytes that end in the middle of
+/// a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnderflowPolicy {
+    /// The bytes of the partial frame are kept until the rest of the frame is read. This is the
+    /// right choice for sockets and pipes, which can split the stream at any byte. A partial
+    /// frame at the end of the stream can never be completed and is dropped.
+    #[default]
+    BufferPartial,
+
+    /// The partial frame is completed with zeros right away. This is only correct for sources
+    /// where a partial frame means the stream was truncated, as any bytes that arrive later will
+    /// be misaligned.
+    ZeroFill,
+
+    /// The read returns an error of kind `InvalidData`. The bytes of the partial frame are kept,
+    /// so reading again continues as if `BufferPartial` was used. At the end of the stream the
+    /// partial frame is dropped and the error is of kind `UnexpectedEof` instead. It is only
+    /// returned once, and the reads after it return the rest of the resampled stream.
+    Error,
+}
+
+/// The number of bytes requested from the inner reader of a `ResamplingReader` at a time.
+const READER_CHUNK_BYTES: usize = 4096;
+
+/// Adapts a reader of raw interleaved PCM bytes into a reader of resampled PCM bytes.
+///
+/// The bytes must be in the format and channel count of the resampler's config and are returned
+/// in the same format and channel count at the output sample rate. When the inner reader reaches
+/// its end, the frames that are still cached by the resampler are pushed out with silence so that
+/// the end of the stream isn't cut off.
+pub struct ResamplingReader<R> {
+    inner: R,
+    resampler: Resampler,
+    underflow_policy: UnderflowPolicy,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    output_start: usize,
+    at_end: bool,
+}
+
+impl<R: std::io::Read> ResamplingReader<R> {
+    pub fn new(inner: R, config: &ResamplerConfig) -> Result<ResamplingReader<R>, Error> {
+        Ok(ResamplingReader {
+            inner,
+            resampler: Resampler::new(config)?,
+            underflow_policy: UnderflowPolicy::default(),
+            input: Vec::new(),
+            output: Vec::new(),
+            output_start: 0,
+            at_end: false,
+        })
+    }
+
+    #[inline]
+    pub fn underflow_policy(&self) -> UnderflowPolicy {
+        self.underflow_policy
+    }
+
+    #[inline]
+    pub fn set_underflow_policy(&mut self, policy: UnderflowPolicy) {
+        self.underflow_policy = policy;
+    }
+
+    #[inline]
+    pub fn resampler(&self) -> &Resampler {
+        &self.resampler
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the inner reader. Any bytes that were read from it but not returned yet are lost.
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads the next chunk from the inner reader and resamples all of the whole frames that are
+    /// buffered.
+    fn fill(&mut self) -> std::io::Result<()> {
+        let bytes_per_frame = bytes_per_frame(
+            self.resampler.config().format(),
+            self.resampler.config().channels(),
+        );
+
+        let start = self.input.len();
+        self.input.resize(start + READER_CHUNK_BYTES, 0);
+        let read = match self.inner.read(&mut self.input[start..]) {
+            Ok(read) => read,
+            Err(err) => {
+                self.input.truncate(start);
+                return Err(err);
+            }
+        };
+        self.input.truncate(start + read);
+
+        let mut eof_error = None;
+        let partial = self.input.len() % bytes_per_frame;
+        if partial != 0 {
+            match self.underflow_policy {
+                UnderflowPolicy::BufferPartial if read == 0 => {
+                    self.input.truncate(self.input.len() - partial);
+                }
+                UnderflowPolicy::BufferPartial => {}
+                UnderflowPolicy::ZeroFill => {
+                    self.input
+                        .resize(self.input.len() + bytes_per_frame - partial, 0);
+                }
+                UnderflowPolicy::Error if read == 0 => {
+                    self.input.truncate(self.input.len() - partial);
+                    eof_error = Some(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "stream ended in a partial frame",
+                    ));
+                }
+                UnderflowPolicy::Error => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "read ended in a partial frame",
+                    ));
+                }
+            }
+        }
+
+        if read == 0 {
+            // Push silence through so that the frames cached by the resampler come out.
+            let latency = self.resampler.input_latency() as usize;
+            self.input
+                .resize(self.input.len() + latency * bytes_per_frame, 0);
+            self.at_end = true;
+        }
+
+        self.resample(bytes_per_frame)?;
+        eof_error.map_or(Ok(()), Err)
+    }
+
+    fn resample(&mut self, bytes_per_frame: usize) -> std::io::Result<()> {
+        let format = self.resampler.config().format();
+        let channels = self.resampler.config().channels();
+        let input_frames = self.input.len() / bytes_per_frame;
+        if input_frames == 0 {
+            return Ok(());
+        }
+
+        self.output.drain(..self.output_start);
+        self.output_start = 0;
+
+        let mut consumed = 0;
+        while consumed < input_frames {
+            let remaining = (input_frames - consumed) as u64;
+            let expected = self.resampler.expected_output_frame_count(remaining) as usize + 1;
+            let output_start = self.output.len();
+            self.output
+                .resize(output_start + expected * bytes_per_frame, 0);
+
+            let (input_used, output_written) = self
+                .resampler
+                .process_pcm_frames(
+                    &mut FramesMut::wrap::<u8>(&mut self.output[output_start..], format, channels),
+                    &Frames::wrap::<u8>(
+                        &self.input[consumed * bytes_per_frame..input_frames * bytes_per_frame],
+                        format,
+                        channels,
+                    ),
+                )
+                .map_err(|err| std::io::Error::other(err.to_string()))?;
+
+            self.output
+                .truncate(output_start + output_written as usize * bytes_per_frame);
+            consumed += input_used as usize;
+
+            if input_used == 0 && output_written == 0 {
+                break;
+            }
+        }
+
+        self.input.drain(..consumed * bytes_per_frame);
+        Ok(())
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for ResamplingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while self.output_start == self.output.len() {
+            if self.at_end {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+
+        let available = &self.output[self.output_start..];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.output_start += count;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(rate_in: u32, rate_out: u32, algorithm: ResampleAlgorithm) -> ResamplerConfig {
+        let mut config = ResamplerConfig::new(
+            Format::F32,
+            1,
+            rate_in,
+            rate_out,
+            algorithm.algorithm_type(),
+        );
+        config.set_algorithm(algorithm);
+        config
+    }
+
+    /// Pushes `frame_count` frames of a constant signal through the resampler in one go and
+    /// returns the number of output frames that were written.
+    fn push(resampler: &mut Resampler, frame_count: usize) -> u64 {
+        let input = vec![0.5f32; frame_count];
+        let mut output = vec![0.0f32; frame_count * 4 + 64];
+        let (consumed, produced) = resampler
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut output, Format::F32, 1),
+                &Frames::wrap(&input, Format::F32, 1),
+            )
+            .unwrap();
+        assert_eq!(consumed, frame_count as u64);
+        produced
+    }
+
+    #[test]
+    fn sinc_settings_are_kept_out_of_the_miniaudio_config() {
+        let sinc = ResampleAlgorithm::Sinc {
+            window_length: 64,
+            beta: 6.0,
+        };
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 2,
+            lpf_nyquist_factor: 1.0,
+        };
+
+        let mut config = config(48000, 44100, linear);
+        config.set_algorithm(sinc);
+        assert_eq!(config.algorithm(), sinc);
+        assert_eq!(config.0.algorithm, sys::ma_resample_algorithm_linear);
+        config.set_algorithm(linear);
+        assert_eq!(config.algorithm(), linear);
+
+        let config =
+            ResamplerConfig::new(Format::F32, 1, 48000, 44100, ResampleAlgorithmType::Sinc);
+        assert_eq!(
+            config.algorithm(),
+            ResampleAlgorithm::Sinc {
+                window_length: DEFAULT_SINC_WINDOW_LENGTH,
+                beta: DEFAULT_SINC_BETA,
+            }
+        );
+    }
+
+    /// Resamples a full scale sine and returns the RMS of the output, leaving out the frames at
+    /// both ends that are affected by the silence the resampler starts and ends with.
+    fn sine_rms(algorithm: ResampleAlgorithm, rate_in: u32, rate_out: u32, frequency: f64) -> f64 {
+        let input: Vec<f32> = (0..9600)
+            .map(|i| (std::f64::consts::TAU * frequency * i as f64 / rate_in as f64).sin() as f32)
+            .collect();
+        let mut output = vec![0.0f32; input.len()];
+        let mut resampler = Resampler::new(&config(rate_in, rate_out, algorithm)).unwrap();
+        let (_, produced) = resampler
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut output, Format::F32, 1),
+                &Frames::wrap(&input, Format::F32, 1),
+            )
+            .unwrap();
+
+        let middle = &output[256..(produced as usize - 256)];
+        (middle.iter().map(|&s| s as f64 * s as f64).sum::<f64>() / middle.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn sinc_filters_out_what_linear_aliases() {
+        let sinc = ResampleAlgorithm::Sinc {
+            window_length: 128,
+            beta: DEFAULT_SINC_BETA,
+        };
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 0,
+            lpf_nyquist_factor: 1.0,
+        };
+
+        // Both keep a tone well below the new Nyquist frequency.
+        let full_scale = std::f64::consts::FRAC_1_SQRT_2;
+        assert!((sine_rms(sinc, 48000, 24000, 1000.0) - full_scale).abs() < 0.02);
+        assert!((sine_rms(linear, 48000, 24000, 1000.0) - full_scale).abs() < 0.02);
+
+        // A tone above it is removed by sinc, while linear folds it back down to 10 kHz.
+        assert!(sine_rms(sinc, 48000, 24000, 14000.0) < 0.01);
+        assert!(sine_rms(linear, 48000, 24000, 14000.0) > 0.5);
+    }
+
+    #[test]
+    fn output_latency_does_not_depend_on_the_consumed_input() {
+        let algorithms = [
+            ResampleAlgorithm::Linear {
+                lpf_order: 4,
+                lpf_nyquist_factor: 1.0,
+            },
+            ResampleAlgorithm::Sinc {
+                window_length: 32,
+                beta: 8.0,
+            },
+        ];
+
+        for algorithm in algorithms.iter() {
+            let mut resampler = Resampler::new(&config(44100, 48000, *algorithm)).unwrap();
+            let latency = resampler.output_latency();
+            assert!(latency > 0);
+
+            push(&mut resampler, 4800);
+            assert_eq!(resampler.output_latency(), latency);
+        }
+    }
+
+    #[test]
+    fn remaining_output_frames_is_what_is_left_to_drain() {
+        let algorithms = [
+            ResampleAlgorithm::Linear {
+                lpf_order: 0,
+                lpf_nyquist_factor: 1.0,
+            },
+            ResampleAlgorithm::Linear {
+                lpf_order: 4,
+                lpf_nyquist_factor: 1.0,
+            },
+            ResampleAlgorithm::Sinc {
+                window_length: 32,
+                beta: 8.0,
+            },
+        ];
+
+        for &(rate_in, rate_out) in &[(44100, 48000), (48000, 44100), (48000, 8000)] {
+            for algorithm in algorithms.iter() {
+                let mut resampler = Resampler::new(&config(rate_in, rate_out, *algorithm)).unwrap();
+                assert_eq!(resampler.remaining_output_frames(), 0);
+
+                push(&mut resampler, 4800);
+                let remaining = resampler.remaining_output_frames();
+
+                // Feeding another `input_latency()` frames pushes the held frames out, give or take
+                // the fractional position of the interpolation.
+                let latency = resampler.input_latency() as usize;
+                let drained = push(&mut resampler, latency);
+                assert!(
+                    (drained as i64 - remaining as i64).abs() <= 2,
+                    "{:?} {} -> {}: {} remaining but {} drained",
+                    algorithm,
+                    rate_in,
+                    rate_out,
+                    remaining,
+                    drained
+                );
+            }
+        }
+    }
+
+    /// A reader that returns at most one byte per read, like a slow socket.
+    struct ByteAtATime<'a>(&'a [u8]);
+
+    impl std::io::Read for ByteAtATime<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    fn s16_stereo_bytes(frame_count: usize) -> Vec<u8> {
+        (0..frame_count * 2)
+            .flat_map(|i| ((i as i16).wrapping_mul(97)).to_ne_bytes())
+            .collect()
+    }
+
+    fn s16_reader_config() -> ResamplerConfig {
+        ResamplerConfig::new(Format::S16, 2, 44100, 48000, ResampleAlgorithmType::Linear)
+    }
+
+    #[test]
+    fn resampling_reader_output_does_not_depend_on_how_the_input_is_split() {
+        use std::io::Read;
+
+        let input = s16_stereo_bytes(1000);
+        let mut expected = Vec::new();
+        ResamplingReader::new(&input[..], &s16_reader_config())
+            .unwrap()
+            .read_to_end(&mut expected)
+            .unwrap();
+
+        let mut output = Vec::new();
+        ResamplingReader::new(ByteAtATime(&input), &s16_reader_config())
+            .unwrap()
+            .read_to_end(&mut output)
+            .unwrap();
+
+        assert!(!expected.is_empty());
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn resampling_reader_reports_a_partial_frame_at_the_end_once() {
+        use std::io::Read;
+
+        let mut input = s16_stereo_bytes(1000);
+        let mut expected = Vec::new();
+        ResamplingReader::new(&input[..], &s16_reader_config())
+            .unwrap()
+            .read_to_end(&mut expected)
+            .unwrap();
+
+        input.push(1);
+        let mut reader = ResamplingReader::new(&input[..], &s16_reader_config()).unwrap();
+        reader.set_underflow_policy(UnderflowPolicy::Error);
+
+        // The read that ends in the partial frame can't know that the stream ends there, so it
+        // fails with `InvalidData` first and the read that hits the end with `UnexpectedEof`.
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+        let mut buf = [0u8; 100];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(read) => output.extend_from_slice(&buf[..read]),
+                Err(err) => errors.push(err.kind()),
+            }
+        }
+
+        assert_eq!(
+            errors,
+            [
+                std::io::ErrorKind::InvalidData,
+                std::io::ErrorKind::UnexpectedEof
+            ]
+        );
+        assert_eq!(output, expected);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "filter order is above the maximum")
+    )]
+    fn linear_resampler_rejects_oversized_lpf_order() {
+        let mut config = LinearResamplerConfig::new(Format::F32, 1, 48000, 44100);
+        config.set_lpf_order(MAX_FILTER_ORDER as u32 + 1);
+        assert!(matches!(
+            LinearResampler::new(&config),
+            Err(Error::InvalidArgs)
+        ));
+    }
+
+    #[test]
+    fn timed_resampler_timestamps_follow_the_input_when_the_ratio_changes() {
+        let sample_rate = 48000;
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 0,
+            lpf_nyquist_factor: 1.0,
+        };
+        let mut resampler = TimedResampler::new(&config(sample_rate, sample_rate, linear)).unwrap();
+
+        let chunk = vec![0.25f32; 480];
+        let mut output = vec![0.0f32; 2048];
+        let mut last = Duration::ZERO;
+        for i in 0..20 {
+            if i == 10 {
+                resampler.set_rate_ratio(1.5).unwrap();
+            }
+
+            let position = resampler.resampler().source_position();
+            let timestamp =
+                Duration::from_secs_f64(i as f64 * chunk.len() as f64 / sample_rate as f64);
+            let (consumed, _, output_timestamp) = resampler
+                .process_pcm_frames(
+                    &mut FramesMut::wrap(&mut output, Format::F32, 1),
+                    &Frames::wrap(&chunk, Format::F32, 1),
+                    timestamp,
+                )
+                .unwrap();
+            assert_eq!(consumed, chunk.len() as u64);
+
+            // Every chunk was tagged at its position in the input, so the timestamp is just the
+            // position at the input's sample rate, whatever the ratio is.
+            let expected = position.max(0.0) / sample_rate as f64;
+            assert!(
+                (output_timestamp.as_secs_f64() - expected).abs() < 1e-6,
+                "chunk {}: {:?} but expected {}s",
+                i,
+                output_timestamp,
+                expected
+            );
+            assert!(output_timestamp >= last);
+            last = output_timestamp;
+        }
+    }
+
+    #[test]
+    fn resamplers_from_one_plan_have_the_same_output() {
+        let sinc = ResampleAlgorithm::Sinc {
+            window_length: 32,
+            beta: DEFAULT_SINC_BETA,
+        };
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 4,
+            lpf_nyquist_factor: 1.0,
+        };
+
+        let input: Vec<f32> = (0..4800).map(|i| ((i % 97) as f32 / 97.0) - 0.5).collect();
+        let resample = |resampler: &mut Resampler| {
+            let mut output = vec![0.0f32; 6000];
+            let (_, produced) = resampler
+                .process_pcm_frames(
+                    &mut FramesMut::wrap(&mut output, Format::F32, 1),
+                    &Frames::wrap(&input, Format::F32, 1),
+                )
+                .unwrap();
+            output.truncate(produced as usize);
+            output
+        };
+
+        for algorithm in [sinc, linear].iter() {
+            let config = config(44100, 48000, *algorithm);
+            let plan = ResamplePlan::new(&config).unwrap();
+            let expected = resample(&mut Resampler::new(&config).unwrap());
+            assert!(!expected.is_empty());
+
+            let mut resamplers: Vec<Resampler> = (0..16)
+                .map(|_| Resampler::from_plan(&plan).unwrap())
+                .collect();
+            for resampler in resamplers.iter_mut() {
+                assert_eq!(resample(resampler), expected, "{:?}", algorithm);
+            }
+        }
+    }
+
+    #[test]
+    fn read_pulls_the_same_output_as_pushing_the_whole_input() {
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 4,
+            lpf_nyquist_factor: 1.0,
+        };
+        let input: Vec<f32> = (0..4800).map(|i| ((i % 97) as f32 / 97.0) - 0.5).collect();
+
+        let mut expected = vec![0.0f32; 6000];
+        let (_, produced) = Resampler::new(&config(44100, 48000, linear))
+            .unwrap()
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut expected, Format::F32, 1),
+                &Frames::wrap(&input, Format::F32, 1),
+            )
+            .unwrap();
+        expected.truncate(produced as usize);
+
+        // Hand out the input in small, uneven chunks so that it takes many calls.
+        let mut resampler = Resampler::new(&config(44100, 48000, linear)).unwrap();
+        let mut position = 0;
+        let mut calls_after_end = 0;
+        let mut output = vec![0.0f32; 6000];
+        let read = resampler
+            .read(
+                &mut FramesMut::wrap(&mut output, Format::F32, 1),
+                |buffer| {
+                    let frames = (input.len() - position).min(37).min(buffer.frame_count());
+                    if frames == 0 {
+                        calls_after_end += 1;
+                    }
+                    buffer.as_samples_mut::<f32>()[..frames]
+                        .copy_from_slice(&input[position..(position + frames)]);
+                    position += frames;
+                    frames as u64
+                },
+            )
+            .unwrap();
+
+        assert_eq!(position, input.len());
+        assert_eq!(calls_after_end, 1);
+        assert_eq!(&output[..read as usize], &expected[..]);
+    }
+
+    #[test]
+    fn process_loop_has_the_exact_length_and_no_click_at_the_loop_point() {
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 4,
+            lpf_nyquist_factor: 1.0,
+        };
+        // One period of a 100 Hz sine, which loops without a discontinuity.
+        let input: Vec<f32> = (0..480)
+            .map(|i| (std::f64::consts::TAU * i as f64 / 480.0).sin() as f32)
+            .collect();
+        let input = Frames::wrap(&input, Format::F32, 1);
+        let mut resampler = Resampler::new(&config(48000, 44100, linear)).unwrap();
+
+        for &target in &[0, 1, 440, 441, 442, 1000] {
+            let output = resampler.process_loop(&input, target).unwrap();
+            assert_eq!(output.frame_count(), target as usize);
+        }
+
+        // The step across the loop point is no bigger than the steps inside the loop.
+        let output = resampler.process_loop(&input, 441).unwrap();
+        let samples = output.frames();
+        let samples = samples.as_samples::<f32>();
+        let max_step = samples
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs())
+            .fold(0.0f32, f32::max);
+        let wrap_step = (samples[0] - samples[samples.len() - 1]).abs();
+        assert!(
+            wrap_step <= max_step * 1.5,
+            "step of {} at the loop point, {} inside the loop",
+            wrap_step,
+            max_step
+        );
+
+        let empty = Frames::wrap::<f32>(&[], Format::F32, 1);
+        assert!(matches!(
+            resampler.process_loop(&empty, 100),
+            Err(Error::InvalidArgs)
+        ));
+    }
+
+    fn null_device(device_type: DeviceType) -> crate::device_io::Device {
+        use crate::device_io::{Backend, Context, Device, DeviceConfig};
+
+        let context = Context::new(&[Backend::Null], None).unwrap();
+        let mut config = DeviceConfig::new(device_type);
+        config.set_sample_rate(48000);
+        config.playback_mut().set_format(Format::S16);
+        config.playback_mut().set_channels(2);
+        config.capture_mut().set_format(Format::S16);
+        config.capture_mut().set_channels(2);
+        Device::new(Some(context), &config).unwrap()
+    }
+
+    #[test]
+    fn config_for_device_matches_its_playback_side() {
+        let device = null_device(DeviceType::Playback);
+        let config = ResamplerConfig::for_device(44100, &device).unwrap();
+        assert_eq!(config.format(), device.playback().format());
+        assert_eq!(config.channels(), device.playback().channels());
+        assert_eq!(config.sample_rate_in(), 44100);
+        assert_eq!(config.sample_rate_out(), device.sample_rate());
+        assert!(matches!(
+            config.algorithm().algorithm_type(),
+            ResampleAlgorithmType::Linear
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "can't resample for a device without playback")
+    )]
+    fn config_for_device_rejects_capture_devices() {
+        let device = null_device(DeviceType::Capture);
+        assert!(matches!(
+            ResamplerConfig::for_device(44100, &device),
+            Err(Error::InvalidArgs)
+        ));
+    }
+
+    #[test]
+    fn process_into_ring_writes_the_same_output_across_the_wrap_around() {
+        use crate::ring_buffers::ring_buffer;
+
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 4,
+            lpf_nyquist_factor: 1.0,
+        };
+        let input: Vec<f32> = (0..1000).map(|i| ((i % 97) as f32 / 97.0) - 0.5).collect();
+
+        let mut expected = vec![0.0f32; 2000];
+        let (_, produced) = Resampler::new(&config(44100, 48000, linear))
+            .unwrap()
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut expected, Format::F32, 1),
+                &Frames::wrap(&input, Format::F32, 1),
+            )
+            .unwrap();
+        expected.truncate(produced as usize);
+
+        // Move the write position close to the end of the ring so that the first write wraps.
+        // miniaudio's ring spans a single subbuffer, so this is a ring of 64 samples.
+        let (send, recv) = ring_buffer::<f32>(64, 1).unwrap();
+        assert_eq!(send.write(&[0.0; 40]), 40);
+        assert_eq!(recv.read(&mut [0.0; 40]), 40);
+
+        // The ring is much smaller than the output, so the input is passed in again until all of
+        // it has been consumed.
+        let mut resampler = Resampler::new(&config(44100, 48000, linear)).unwrap();
+        let mut consumed = 0;
+        let mut output = Vec::new();
+        let mut samples = [0.0f32; 256];
+        loop {
+            let (input_frames, output_frames) = resampler
+                .process_into_ring(&send, &Frames::wrap(&input[consumed..], Format::F32, 1))
+                .unwrap();
+            consumed += input_frames as usize;
+
+            // A read also stops at the end of the ring, so the wrapped part takes a second one.
+            let mut read = 0;
+            loop {
+                let count = recv.read(&mut samples);
+                if count == 0 {
+                    break;
+                }
+                output.extend_from_slice(&samples[..count]);
+                read += count;
+            }
+            assert_eq!(read, output_frames as usize);
+            if output_frames == 0 {
+                break;
+            }
+        }
+
+        assert_eq!(consumed, input.len());
+        assert!((output.len() as i64 - expected.len() as i64).abs() <= 1);
+        let common = output.len().min(expected.len());
+        assert_eq!(&output[..common], &expected[..common]);
+    }
+
+    #[test]
+    fn set_rate_crossfaded_fades_from_the_old_rate_to_the_new_one() {
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 4,
+            lpf_nyquist_factor: 1.0,
+        };
+        let input: Vec<f32> = (0..4800)
+            .map(|i| (std::f64::consts::TAU * 440.0 * i as f64 / 44100.0).sin() as f32)
+            .collect();
+        let (start, rest) = input.split_at(1000);
+
+        let mut resamplers: Vec<Resampler> = (0..3)
+            .map(|_| Resampler::new(&config(44100, 48000, linear)).unwrap())
+            .collect();
+        let mut outputs = vec![vec![0.0f32; 8000]; 3];
+        for (resampler, output) in resamplers.iter_mut().zip(outputs.iter_mut()) {
+            resampler
+                .process_pcm_frames(
+                    &mut FramesMut::wrap(output, Format::F32, 1),
+                    &Frames::wrap(start, Format::F32, 1),
+                )
+                .unwrap();
+        }
+
+        // One keeps the old rate, one fades to the new rate and one switches to it abruptly.
+        let fade_frames = 256;
+        resamplers[1]
+            .set_rate_crossfaded(40000, 48000, fade_frames as u64, FadeCurve::Linear)
+            .unwrap();
+        resamplers[2].set_rate(40000, 48000).unwrap();
+
+        let mut produced = Vec::new();
+        for (resampler, output) in resamplers.iter_mut().zip(outputs.iter_mut()) {
+            let (_, frames) = resampler
+                .process_pcm_frames(
+                    &mut FramesMut::wrap(output, Format::F32, 1),
+                    &Frames::wrap(rest, Format::F32, 1),
+                )
+                .unwrap();
+            produced.push(frames as usize);
+        }
+        assert!(produced[1] > fade_frames);
+        assert_eq!(produced[1], produced[2]);
+
+        // The fade starts at the output of the old rate and ends at the output of the new one.
+        let (old, faded, new) = (&outputs[0], &outputs[1], &outputs[2]);
+        assert_eq!(faded[0], old[0]);
+        assert_eq!(
+            &faded[fade_frames..produced[1]],
+            &new[fade_frames..produced[2]]
+        );
+        assert!(faded[..fade_frames] != new[..fade_frames]);
+    }
+
+    #[test]
+    fn resample_buffer_flushes_the_tail_and_can_be_cancelled() {
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 4,
+            lpf_nyquist_factor: 1.0,
+        };
+        let input = vec![0.5f32; 3 * RESAMPLE_BUFFER_CHUNK_FRAMES];
+        let input = Frames::wrap(&input, Format::F32, 1);
+
+        let mut resampler = Resampler::new(&config(44100, 48000, linear)).unwrap();
+        let latency = resampler.input_latency();
+        let expected = resampler.expected_output_frame_count(input.frame_count() as u64 + latency);
+        let output = resampler.resample_buffer(&input, || true).unwrap();
+        assert!((output.frame_count() as i64 - expected as i64).abs() <= 1);
+
+        // Cancelling after the first chunk stops before the second one is resampled.
+        resampler.reset();
+        let mut calls = 0;
+        let result = resampler.resample_buffer(&input, || {
+            calls += 1;
+            calls < 2
+        });
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert_eq!(calls, 2);
+        assert_eq!(
+            resampler.input_frames_consumed,
+            RESAMPLE_BUFFER_CHUNK_FRAMES as u64
+        );
+    }
+
+    #[test]
+    fn resample_buffer_progress_is_throttled_and_ends_at_one() {
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 0,
+            lpf_nyquist_factor: 1.0,
+        };
+        let input = vec![0.5f32; 200 * RESAMPLE_BUFFER_CHUNK_FRAMES];
+        let input = Frames::wrap(&input, Format::F32, 1);
+        let mut resampler = Resampler::new(&config(48000, 48000, linear)).unwrap();
+
+        let mut reports = Vec::new();
+        resampler
+            .resample_buffer_with_progress(&input, || true, |fraction| reports.push(fraction))
+            .unwrap();
+
+        // There are 200 chunks, but only every 1% of progress is reported.
+        assert!(reports.len() <= (1.0 / PROGRESS_REPORT_STEP) as usize + 2);
+        assert!(reports.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert_eq!(reports.last(), Some(&1.0));
+        assert_eq!(
+            reports.iter().filter(|&&fraction| fraction == 1.0).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn process_pcm_frames_partial_leaves_the_rest_of_the_buffers_untouched() {
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 0,
+            lpf_nyquist_factor: 1.0,
+        };
+        let input = vec![0.5f32; 100];
+        let mut output = vec![7.0f32; 100];
+        let mut resampler = Resampler::new(&config(48000, 48000, linear)).unwrap();
+
+        let (consumed, produced) = resampler
+            .process_pcm_frames_partial(
+                &mut FramesMut::wrap(&mut output, Format::F32, 1),
+                40,
+                &Frames::wrap(&input, Format::F32, 1),
+                60,
+            )
+            .unwrap();
+        assert!(consumed <= 60);
+        assert_eq!(produced, 40);
+        assert!(output[40..].iter().all(|&sample| sample == 7.0));
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "frame counts are larger than the buffers")
+    )]
+    fn process_pcm_frames_partial_rejects_counts_larger_than_the_buffers() {
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 0,
+            lpf_nyquist_factor: 1.0,
+        };
+        let input = vec![0.5f32; 100];
+        let mut output = vec![0.0f32; 100];
+        let mut resampler = Resampler::new(&config(48000, 48000, linear)).unwrap();
+
+        assert!(matches!(
+            resampler.process_pcm_frames_partial(
+                &mut FramesMut::wrap(&mut output, Format::F32, 1),
+                100,
+                &Frames::wrap(&input, Format::F32, 1),
+                101,
+            ),
+            Err(Error::InvalidArgs)
+        ));
+    }
+
+    #[test]
+    fn finish_drains_the_tail_over_several_calls() {
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 4,
+            lpf_nyquist_factor: 1.0,
+        };
+        let input: Vec<f32> = (0..1000).map(|i| ((i % 97) as f32 / 97.0) - 0.5).collect();
+        let input = Frames::wrap(&input, Format::F32, 1);
+
+        let expected = Resampler::new(&config(44100, 48000, linear))
+            .unwrap()
+            .resample_buffer(&input, || true)
+            .unwrap();
+        let expected = expected.frames();
+        let expected = expected.as_samples::<f32>();
+
+        let mut resampler = Resampler::new(&config(44100, 48000, linear)).unwrap();
+        let mut output = vec![0.0f32; 2000];
+        let (_, produced) = resampler
+            .process_pcm_frames(&mut FramesMut::wrap(&mut output, Format::F32, 1), &input)
+            .unwrap();
+        output.truncate(produced as usize);
+
+        // A buffer of 3 frames is too small for the tail, so this takes a few calls.
+        let mut chunk = [0.0f32; 3];
+        let mut calls = 0;
+        loop {
+            let (frames, done) = resampler
+                .finish(&mut FramesMut::wrap(&mut chunk, Format::F32, 1))
+                .unwrap();
+            output.extend_from_slice(&chunk[..frames as usize]);
+            calls += 1;
+            if done {
+                break;
+            }
+        }
+        assert!(calls > 1);
+        assert_eq!(&output[..], expected);
+
+        let (frames, done) = resampler
+            .finish(&mut FramesMut::wrap(&mut chunk, Format::F32, 1))
+            .unwrap();
+        assert_eq!(frames, 0);
+        assert!(done);
+    }
+
+    #[test]
+    fn process_ex_reports_which_buffer_ran_out() {
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 0,
+            lpf_nyquist_factor: 1.0,
+        };
+        let input = vec![0.5f32; 100];
+        let mut resampler = Resampler::new(&config(48000, 24000, linear)).unwrap();
+
+        // Half as many output frames as input frames: the input runs out first.
+        let mut output = vec![0.0f32; 100];
+        let outcome = resampler
+            .process_ex(
+                &mut FramesMut::wrap(&mut output, Format::F32, 1),
+                &Frames::wrap(&input, Format::F32, 1),
+            )
+            .unwrap();
+        assert_eq!(outcome.consumed, 100);
+        assert!(outcome.needs_more_input);
+        assert!(!outcome.output_full);
+
+        // A small output fills up before all of the input is used.
+        let mut output = vec![0.0f32; 10];
+        let outcome = resampler
+            .process_ex(
+                &mut FramesMut::wrap(&mut output, Format::F32, 1),
+                &Frames::wrap(&input, Format::F32, 1),
+            )
+            .unwrap();
+        assert_eq!(outcome.produced, 10);
+        assert!(outcome.consumed < 100);
+        assert!(!outcome.needs_more_input);
+        assert!(outcome.output_full);
+    }
+
+    #[test]
+    fn fill_device_buffer_pads_the_end_of_the_source_with_silence() {
+        use std::sync::Mutex;
+
+        let mut device = null_device(DeviceType::Playback);
+        let mut resampler =
+            Resampler::new(&ResamplerConfig::for_device(24000, &device).unwrap()).unwrap();
+
+        // 100 frames of a constant signal at half the rate of the device.
+        let mut remaining = 100;
+        let written = Arc::new(Mutex::new((Vec::new(), 0)));
+        let callback_written = Arc::clone(&written);
+        device.set_data_callback(move |_, output, _| {
+            let produced = resampler
+                .fill_device_buffer(output, |input| {
+                    let frames = input.frame_count().min(remaining);
+                    input.as_samples_mut::<i16>()[..(frames * 2)]
+                        .iter_mut()
+                        .for_each(|sample| *sample = 1000);
+                    remaining -= frames;
+                    frames as u64
+                })
+                .unwrap();
+            let mut written = callback_written.lock().unwrap();
+            written.0.extend_from_slice(output.as_samples::<i16>());
+            written.1 += produced as usize;
+        });
+        device.advance(480).unwrap();
+
+        let written = written.lock().unwrap();
+        let (samples, produced) = (&written.0, written.1);
+        assert_eq!(samples.len(), 480 * 2);
+        assert!(produced > 150 && produced < 250, "{} frames", produced);
+        assert!((samples[(produced / 2) * 2] - 1000).abs() <= 5);
+        assert!(samples[(produced * 2)..].iter().all(|&sample| sample == 0));
+    }
+
+    #[test]
+    fn process_bytes_swaps_foreign_byte_orders_on_both_sides() {
+        let foreign = if cfg!(target_endian = "little") {
+            ByteOrder::Big
+        } else {
+            ByteOrder::Little
+        };
+        let native = s16_stereo_bytes(500);
+        let mut swapped = native.clone();
+        swap_sample_bytes(&mut swapped, Format::S16, foreign);
+
+        let mut expected = vec![0u8; 4000];
+        let (_, expected_frames) = Resampler::new(&s16_reader_config())
+            .unwrap()
+            .process_bytes(&mut expected, &native, ByteOrder::Native)
+            .unwrap();
+        expected.truncate(expected_frames as usize * 4);
+        swap_sample_bytes(&mut expected, Format::S16, foreign);
+
+        let mut output = vec![0u8; 4000];
+        let (consumed, produced) = Resampler::new(&s16_reader_config())
+            .unwrap()
+            .process_bytes(&mut output, &swapped, foreign)
+            .unwrap();
+        assert_eq!(consumed, 500);
+        assert_eq!(produced, expected_frames);
+        assert_eq!(&output[..(produced as usize * 4)], &expected[..]);
+    }
+
+    #[test]
+    fn resample_roundtrip_to_length_keeps_the_length_and_alignment_of_the_input() {
+        let mut input = vec![0.0f32; 4000];
+        input[1000] = 1.0;
+
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 4,
+            lpf_nyquist_factor: 1.0,
+        };
+        let sinc = ResampleAlgorithm::Sinc {
+            window_length: 32,
+            beta: 8.0,
+        };
+
+        for &intermediate_rate in &[24000, 32000, 44100, 96000] {
+            let roundtrip = |algorithm| {
+                resample_roundtrip_to_length(
+                    &Frames::wrap(&input, Format::F32, 1),
+                    48000,
+                    intermediate_rate,
+                    algorithm,
+                )
+                .unwrap()
+            };
+
+            assert_eq!(roundtrip(linear).frame_count(), input.len());
+
+            // The sinc filter is linear phase, so the impulse comes out exactly where it went in.
+            let output = roundtrip(sinc);
+            assert_eq!(output.frame_count(), input.len());
+            let frames = output.frames();
+            let samples = frames.as_samples::<f32>();
+            let peak = (0..samples.len())
+                .max_by(|&a, &b| samples[a].partial_cmp(&samples[b]).unwrap())
+                .unwrap();
+            assert_eq!(peak, 1000, "via {}", intermediate_rate);
+        }
+    }
+
+    #[test]
+    fn set_rate_crossfaded_has_no_discontinuity_across_the_rate_change() {
+        // Without a low-pass filter a jump in the output isn't smoothed over a few frames.
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 0,
+            lpf_nyquist_factor: 1.0,
+        };
+        let input: Vec<f32> = (0..9600)
+            .map(|i| (std::f64::consts::TAU * 440.0 * i as f64 / 44100.0).sin() as f32)
+            .collect();
+
+        // The rate changes in the middle of a stream that is processed in small blocks, so the
+        // fade spans several calls that each pass in the rest of the input.
+        let mut resampler = Resampler::new(&config(44100, 48000, linear)).unwrap();
+        let mut output = Vec::new();
+        let mut consumed = 0;
+        let mut block = [0.0f32; 64];
+        while consumed < input.len() {
+            if output.len() == 2048 {
+                resampler
+                    .set_rate_crossfaded(40000, 48000, 2048, FadeCurve::Linear)
+                    .unwrap();
+            }
+            let (input_frames, output_frames) = resampler
+                .process_pcm_frames(
+                    &mut FramesMut::wrap(&mut block, Format::F32, 1),
+                    &Frames::wrap(&input[consumed..], Format::F32, 1),
+                )
+                .unwrap();
+            consumed += input_frames as usize;
+            output.extend_from_slice(&block[..output_frames as usize]);
+        }
+
+        // A 440 Hz sine at 44100 Hz plays at 404 Hz at the output rate, where it never changes by
+        // more than 0.053 from one frame to the next. Skipping or repeating input would jump.
+        let max_delta = output[100..]
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs())
+            .fold(0.0f32, f32::max);
+        assert!(max_delta < 0.07, "max delta {}", max_delta);
+    }
+
+    #[test]
+    fn resampler_pool_reuses_returned_resamplers() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<ResamplerPool>();
+
+        let sinc = ResampleAlgorithm::Sinc {
+            window_length: 32,
+            beta: 8.0,
+        };
+        let pool = ResamplerPool::new(&config(44100, 48000, sinc), 1);
+
+        // Every sinc resampler computes its own kernel, so the kernel tells them apart.
+        let kernel = |resampler: &Resampler| match &resampler.backend {
+            ResamplerBackend::Sinc(sinc) => Arc::as_ptr(sinc.kernel()),
+            ResamplerBackend::Miniaudio(_) => unreachable!(),
+        };
+
+        let mut first = pool.acquire().unwrap();
+        push(&mut first, 100);
+        let first_kernel = kernel(&first);
+        drop(first);
+        assert_eq!(pool.idle_count(), 1);
+
+        let second = pool.acquire().unwrap();
+        assert_eq!(pool.idle_count(), 0);
+        assert_eq!(kernel(&second), first_kernel);
+        assert_eq!(second.input_frames_consumed, 0);
+
+        // A second resampler that is lent out at the same time is a new one, and only one of
+        // them fits back into the pool.
+        let third = pool.acquire().unwrap();
+        assert_ne!(kernel(&third), first_kernel);
+        drop(second);
+        drop(third);
+        assert_eq!(pool.idle_count(), 1);
+        assert_eq!(kernel(&pool.acquire().unwrap()), first_kernel);
+    }
+
+    #[test]
+    fn read_in_small_chunks_gives_the_same_output_as_one_large_read() {
+        let algorithms = [
+            ResampleAlgorithm::Linear {
+                lpf_order: 4,
+                lpf_nyquist_factor: 1.0,
+            },
+            ResampleAlgorithm::Sinc {
+                window_length: 32,
+                beta: 8.0,
+            },
+        ];
+        let input: Vec<f32> = (0..4800).map(|i| ((i % 97) as f32 / 97.0) - 0.5).collect();
+
+        for algorithm in algorithms.iter() {
+            let read_all = |chunk_frames: usize| {
+                let mut resampler = Resampler::new(&config(44100, 48000, *algorithm)).unwrap();
+                let mut position = 0;
+                let mut output = Vec::new();
+                let mut chunk = vec![0.0f32; chunk_frames];
+                loop {
+                    let read = resampler
+                        .read(&mut FramesMut::wrap(&mut chunk, Format::F32, 1), |buffer| {
+                            let frames = (input.len() - position).min(buffer.frame_count());
+                            buffer.as_samples_mut::<f32>()[..frames]
+                                .copy_from_slice(&input[position..(position + frames)]);
+                            position += frames;
+                            frames as u64
+                        })
+                        .unwrap() as usize;
+                    output.extend_from_slice(&chunk[..read]);
+                    if read < chunk_frames {
+                        return output;
+                    }
+                }
+            };
+
+            let whole = read_all(6000);
+            assert!(whole.len() > 5000);
+            for &chunk_frames in &[1, 7, 64, 1000] {
+                assert_eq!(
+                    read_all(chunk_frames),
+                    whole,
+                    "{:?} in chunks of {}",
+                    algorithm,
+                    chunk_frames
+                );
+            }
+        }
+    }
+
+    /// Records the names of the spans that are created while it is the default subscriber.
+    #[cfg(feature = "tracing")]
+    #[derive(Default)]
+    struct SpanNames(std::sync::Mutex<Vec<&'static str>>);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for SpanNames {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut names = self.0.lock().unwrap();
+            names.push(span.metadata().name());
+            tracing::span::Id::from_u64(names.len() as u64)
+        }
+
+        fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+        fn event(&self, _event: &tracing::Event<'_>) {}
+
+        fn enter(&self, _span: &tracing::span::Id) {}
+
+        fn exit(&self, _span: &tracing::span::Id) {}
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn process_pcm_frames_emits_a_span() {
+        let names = Arc::new(SpanNames::default());
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 2,
+            lpf_nyquist_factor: 1.0,
+        };
+
+        tracing::subscriber::with_default(Arc::clone(&names), || {
+            let mut resampler = Resampler::new(&config(48000, 44100, linear)).unwrap();
+            push(&mut resampler, 480);
+        });
+
+        let names = names.0.lock().unwrap();
+        assert!(names.contains(&"Resampler::process_pcm_frames"));
+    }
+
+    #[cfg(feature = "dasp")]
+    #[test]
+    fn dasp_frames_go_through_the_resampler_and_back() {
+        // A 440 Hz sine on the left channel and its inverse on the right one.
+        let input: Vec<[i16; 2]> = (0..4410)
+            .map(|i| {
+                let s = (i as f32 * 440.0 * 2.0 * std::f32::consts::PI / 44100.0).sin();
+                let s = (s * 16384.0) as i16;
+                [s, -s]
+            })
+            .collect();
+        let input = FramesBuffer::from_dasp_frames(input, Format::F32);
+        assert_eq!(input.frame_count(), 4410);
+
+        let config =
+            ResamplerConfig::new(Format::F32, 2, 44100, 48000, ResampleAlgorithmType::Linear);
+        let mut resampler = Resampler::new(&config).unwrap();
+        let mut output = FramesBuffer::new(Format::F32, 2, 6000);
+        let (consumed, produced) = resampler
+            .process_pcm_frames(&mut output.frames_mut(), &input.frames())
+            .unwrap();
+        assert_eq!(consumed, 4410);
+        assert!((4790..=4800).contains(&produced));
+
+        let output = Frames::wrap(&output.as_bytes()[..produced as usize * 8], Format::F32, 2);
+        let frames = output.to_dasp_frames::<[i16; 2]>().unwrap();
+        assert_eq!(frames.len(), produced as usize);
+        assert_eq!(
+            output.as_dasp_frames::<f32, 2>().unwrap().len(),
+            frames.len()
+        );
+
+        let peak = frames.iter().map(|f| f[0]).max().unwrap();
+        assert!((16000..=16384).contains(&peak), "peak is {}", peak);
+        assert!(frames
+            .iter()
+            .all(|f| (f[0] as i32 + f[1] as i32).abs() <= 1));
+    }
+
+    /// The magnitude of the `frequency` component of `samples`, normalized so that a full scale
+    /// sine at that frequency gives about 1.
+    fn tone_magnitude(samples: &[f32], sample_rate: u32, frequency: f64) -> f64 {
+        let step = 2.0 * std::f64::consts::PI * frequency / sample_rate as f64;
+        let (re, im) = samples
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (i, &s)| {
+                let phase = step * i as f64;
+                (re + s as f64 * phase.cos(), im - s as f64 * phase.sin())
+            });
+        2.0 * (re * re + im * im).sqrt() / samples.len() as f64
+    }
+
+    fn sine(frequency: f64, sample_rate: u32, frame_count: usize) -> Vec<f32> {
+        let step = 2.0 * std::f64::consts::PI * frequency / sample_rate as f64;
+        (0..frame_count)
+            .map(|i| (step * i as f64).sin() as f32)
+            .collect()
+    }
+
+    #[test]
+    fn multi_channel_linear_resampler_matches_one_resampler_per_channel() {
+        let left = sine(1000.0, 48000, 4800);
+        let right = sine(5000.0, 48000, 4800);
+        let input: Vec<f32> = left
+            .iter()
+            .zip(&right)
+            .flat_map(|(&l, &r)| vec![l, r])
+            .collect();
+
+        let config = LinearResamplerConfig::new(Format::F32, 2, 48000, 44100);
+        let mut multi = MultiChannelLinearResampler::new(&config).unwrap();
+        multi.set_channel_lpf(1, 0, 1.0).unwrap();
+        let mut output = vec![0.0f32; 2 * 4800];
+        let (produced, consumed) = multi
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut output, Format::F32, 2),
+                &Frames::wrap(&input, Format::F32, 2),
+            )
+            .unwrap();
+        assert_eq!(consumed, 4800);
+        assert!((4400..=4410).contains(&produced));
+
+        for (channel, mono_input) in [&left, &right].iter().enumerate() {
+            let mut config = LinearResamplerConfig::new(Format::F32, 1, 48000, 44100);
+            if channel == 1 {
+                config.set_lpf_order(0);
+            }
+            let mut mono = LinearResampler::new(&config).unwrap();
+            let mut expected = vec![0.0f32; 4800];
+            let (mono_produced, mono_consumed) = mono
+                .process_pcm_frames(
+                    &mut FramesMut::wrap(&mut expected, Format::F32, 1),
+                    &Frames::wrap(&mono_input[..], Format::F32, 1),
+                )
+                .unwrap();
+            assert_eq!((mono_produced, mono_consumed), (produced, consumed));
+
+            let actual: Vec<f32> = output[..produced as usize * 2]
+                .iter()
+                .skip(channel)
+                .step_by(2)
+                .copied()
+                .collect();
+            let expected = &expected[..produced as usize];
+            let (tone, other) = if channel == 0 {
+                (1000.0, 5000.0)
+            } else {
+                (5000.0, 1000.0)
+            };
+            for &frequency in &[tone, other] {
+                let a = tone_magnitude(&actual, 44100, frequency);
+                let e = tone_magnitude(expected, 44100, frequency);
+                assert!((a - e).abs() < 1e-4, "{} Hz: {} vs {}", frequency, a, e);
+            }
+            assert!(tone_magnitude(&actual, 44100, tone) > 0.9);
+            assert!(tone_magnitude(&actual, 44100, other) < 0.01);
+        }
+    }
+
+    #[test]
+    fn two_stage_resampler_attenuates_tones_above_the_output_nyquist_frequency() {
+        // A 10kHz tone resampled from 192kHz to 8kHz aliases to 2kHz.
+        let input = sine(10000.0, 192000, 96000);
+        let config =
+            ResamplerConfig::new(Format::F32, 1, 192000, 8000, ResampleAlgorithmType::Linear);
+
+        let mut two_stage = TwoStageResampler::new(&config).unwrap();
+        assert_eq!(two_stage.decimation(), 24);
+        let mut two_stage_output = vec![0.0f32; 4096];
+        let (consumed, produced) = two_stage
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut two_stage_output, Format::F32, 1),
+                &Frames::wrap(&input, Format::F32, 1),
+            )
+            .unwrap();
+        assert_eq!(consumed, 96000);
+        assert!(produced >= 3990);
+
+        let mut single_stage = Resampler::new(&config).unwrap();
+        let mut single_stage_output = vec![0.0f32; 4096];
+        let (consumed, produced) = single_stage
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut single_stage_output, Format::F32, 1),
+                &Frames::wrap(&input, Format::F32, 1),
+            )
+            .unwrap();
+        assert_eq!(consumed, 96000);
+        assert!(produced >= 3990);
+
+        // Skip the start, where the filters are still settling.
+        let two_stage_alias = tone_magnitude(&two_stage_output[990..3990], 8000, 2000.0);
+        let single_stage_alias = tone_magnitude(&single_stage_output[990..3990], 8000, 2000.0);
+        assert!(
+            two_stage_alias < 0.001,
+            "two stage alias is {}",
+            two_stage_alias
+        );
+        assert!(
+            two_stage_alias * 10.0 < single_stage_alias,
+            "two stage alias is {}, single stage alias is {}",
+            two_stage_alias,
+            single_stage_alias
+        );
+    }
+
+    #[test]
+    fn different_resamplers_can_be_used_through_dynamic_resample() {
+        let linear_config = LinearResamplerConfig::new(Format::F32, 2, 48000, 44100);
+        let mut resamplers: Vec<Box<dyn DynamicResample>> = vec![
+            Box::new(LinearResampler::new(&linear_config).unwrap()),
+            Box::new(
+                Resampler::new(&ResamplerConfig::new(
+                    Format::F32,
+                    2,
+                    48000,
+                    44100,
+                    ResampleAlgorithmType::Sinc,
+                ))
+                .unwrap(),
+            ),
+            Box::new(MultiChannelLinearResampler::new(&linear_config).unwrap()),
+        ];
+
+        let tone = sine(1000.0, 48000, 4800);
+        let input: Vec<f32> = tone.iter().flat_map(|&s| vec![s, s]).collect();
+        let input = Frames::wrap(&input, Format::F32, 2);
+
+        for resampler in resamplers.iter_mut() {
+            let mut output = vec![0.0f32; 2 * 4800];
+            let (consumed, produced) = resampler
+                .process_bytes(
+                    FramesMut::wrap(&mut output, Format::F32, 2).as_bytes_mut(),
+                    input.as_bytes(),
+                    ByteOrder::Native,
+                )
+                .unwrap();
+            assert_eq!(consumed, 4800);
+            assert!((4300..=4410).contains(&produced), "produced {}", produced);
+
+            // Two whole periods of the tone at 44.1kHz after the start.
+            let left: Vec<f32> = output.iter().step_by(2).copied().collect();
+            assert!(tone_magnitude(&left[1000..3205], 44100, 1000.0) > 0.9);
+
+            // Halving the rate halves the number of output frames.
+            resampler.set_rate_ratio(2.0).unwrap();
+            let (consumed, produced) = resampler
+                .process_bytes(
+                    FramesMut::wrap(&mut output, Format::F32, 2).as_bytes_mut(),
+                    input.as_bytes(),
+                    ByteOrder::Native,
+                )
+                .unwrap();
+            assert_eq!(consumed, 4800);
+            assert!((2390..=2410).contains(&produced), "produced {}", produced);
+        }
+    }
+
+    #[test]
+    fn resampler_tee_outputs_match_independent_resamplers() {
+        let linear =
+            ResamplerConfig::new(Format::F32, 1, 48000, 44100, ResampleAlgorithmType::Linear);
+        let sinc = ResamplerConfig::new(Format::F32, 1, 48000, 16000, ResampleAlgorithmType::Sinc);
+        let mut tee = ResamplerTee::new(&linear, &sinc).unwrap();
+        let mut first = Resampler::new(&linear).unwrap();
+        let mut second = Resampler::new(&sinc).unwrap();
+
+        let input = sine(1000.0, 48000, 4800);
+        let (mut tee_first, mut tee_second) = (Vec::new(), Vec::new());
+        let (mut expected_first, mut expected_second) = (Vec::new(), Vec::new());
+        for block in input.chunks(480) {
+            let block = Frames::wrap(block, Format::F32, 1);
+            let mut first_output = [0.0f32; 1024];
+            let mut second_output = [0.0f32; 1024];
+            let (consumed, (first_written, second_written)) = tee
+                .process_pcm_frames(
+                    &mut FramesMut::wrap(&mut first_output[..], Format::F32, 1),
+                    &mut FramesMut::wrap(&mut second_output[..], Format::F32, 1),
+                    &block,
+                )
+                .unwrap();
+            assert_eq!(consumed, 480);
+            tee_first.extend_from_slice(&first_output[..first_written as usize]);
+            tee_second.extend_from_slice(&second_output[..second_written as usize]);
+
+            for (resampler, expected) in [
+                (&mut first, &mut expected_first),
+                (&mut second, &mut expected_second),
+            ] {
+                let mut output = [0.0f32; 1024];
+                let (consumed, written) = resampler
+                    .process_pcm_frames(
+                        &mut FramesMut::wrap(&mut output[..], Format::F32, 1),
+                        &block,
+                    )
+                    .unwrap();
+                assert_eq!(consumed, 480);
+                expected.extend_from_slice(&output[..written as usize]);
+            }
+        }
+
+        assert!(tee_first.len() > 4000);
+        assert!(tee_second.len() > 1500);
+        assert_eq!(tee_first, expected_first);
+        assert_eq!(tee_second, expected_second);
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "output and input format did not match resampler config")
+    )]
+    fn frames_with_another_format_are_rejected() {
+        let config = LinearResamplerConfig::new(Format::F32, 1, 48000, 44100);
+        let mut resampler = LinearResampler::new(&config).unwrap();
+        let input = [0i16; 64];
+        let mut output = [0i16; 64];
+        let result = resampler.process_pcm_frames(
+            &mut FramesMut::wrap(&mut output[..], Format::S16, 1),
+            &Frames::wrap(&input[..], Format::S16, 1),
+        );
+        assert!(matches!(result, Err(Error::InvalidArgs)));
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "output and input channels did not match resampler config")
+    )]
+    fn frames_with_another_channel_count_are_rejected() {
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 2,
+            lpf_nyquist_factor: 1.0,
+        };
+        let mut resampler = Resampler::new(&config(48000, 44100, linear)).unwrap();
+        let input = [0f32; 64];
+        let mut output = [0f32; 64];
+        let result = resampler.process_pcm_frames(
+            &mut FramesMut::wrap(&mut output[..], Format::F32, 2),
+            &Frames::wrap(&input[..], Format::F32, 2),
+        );
+        assert!(matches!(result, Err(Error::InvalidArgs)));
+    }
+
+    #[test]
+    fn process_pcm_frames_with_gain_scales_the_plain_output() {
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 2,
+            lpf_nyquist_factor: 1.0,
+        };
+        let input = sine(1000.0, 48000, 480);
+        let process = |gain: Option<f32>| {
+            let mut resampler = Resampler::new(&config(48000, 44100, linear)).unwrap();
+            // Frames past the written part must be left alone.
+            let mut output = vec![2.0f32; 512];
+            let mut frames = FramesMut::wrap(&mut output, Format::F32, 1);
+            let input = Frames::wrap(&input, Format::F32, 1);
+            let (consumed, produced) = match gain {
+                Some(gain) => resampler.process_pcm_frames_with_gain(&mut frames, &input, gain),
+                None => resampler.process_pcm_frames(&mut frames, &input),
+            }
+            .unwrap();
+            assert_eq!(consumed, 480);
+            assert!(produced < 512);
+            assert!(output[produced as usize..].iter().all(|&s| s == 2.0));
+            output.truncate(produced as usize);
+            output
+        };
+
+        let plain = process(None);
+        let scaled = process(Some(0.5));
+        assert_eq!(scaled.len(), plain.len());
+        assert!(plain.iter().zip(&scaled).all(|(&p, &s)| s == p * 0.5));
+        assert!(plain.iter().any(|&s| s.abs() > 0.5));
+
+        let silent = process(Some(0.0));
+        assert_eq!(silent.len(), plain.len());
+        assert!(silent.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn reinit_at_a_new_rate_matches_a_new_resampler() {
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 2,
+            lpf_nyquist_factor: 1.0,
+        };
+        let sinc = ResampleAlgorithm::Sinc {
+            window_length: DEFAULT_SINC_WINDOW_LENGTH,
+            beta: DEFAULT_SINC_BETA,
+        };
+        let input = sine(1000.0, 48000, 960);
+
+        for &algorithm in &[linear, sinc] {
+            let mut resampler = Resampler::new(&config(48000, 44100, algorithm)).unwrap();
+            push(&mut resampler, 480);
+            resampler.reinit(&config(48000, 32000, algorithm)).unwrap();
+            assert_eq!(resampler.config().sample_rate_out(), 32000);
+            let mut fresh = Resampler::new(&config(48000, 32000, algorithm)).unwrap();
+
+            let mut outputs = Vec::new();
+            for resampler in [&mut resampler, &mut fresh] {
+                let mut output = vec![0.0f32; 1024];
+                let (consumed, produced) = resampler
+                    .process_pcm_frames(
+                        &mut FramesMut::wrap(&mut output, Format::F32, 1),
+                        &Frames::wrap(&input, Format::F32, 1),
+                    )
+                    .unwrap();
+                assert_eq!(consumed, 960);
+                output.truncate(produced as usize);
+                outputs.push(output);
+            }
+            assert!(outputs[0].len() > 600);
+            assert_eq!(outputs[0], outputs[1]);
+        }
+    }
+
+    #[test]
+    fn speex_availability_follows_the_feature() {
+        assert!(ResampleAlgorithmType::Linear.is_available());
+        assert!(ResampleAlgorithmType::Sinc.is_available());
+        assert_eq!(
+            ResampleAlgorithm::Speex { quality: 3 }.is_available(),
+            cfg!(feature = "ma-enable-speex")
+        );
+    }
+
+    #[cfg(not(feature = "ma-enable-speex"))]
+    #[test]
+    fn speex_is_rejected_when_it_is_not_compiled_in() {
+        let speex = config(48000, 44100, ResampleAlgorithm::Speex { quality: 3 });
+        assert!(matches!(Resampler::new(&speex), Err(Error::NoBackend)));
+
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 2,
+            lpf_nyquist_factor: 1.0,
+        };
+        let mut resampler = Resampler::new(&config(48000, 44100, linear)).unwrap();
+        assert!(matches!(resampler.reinit(&speex), Err(Error::NoBackend)));
+        assert_eq!(resampler.config().algorithm(), linear);
+        assert!(push(&mut resampler, 480) > 0);
+    }
+
+    #[test]
+    fn has_lpf_sets_the_lpf_of_both_configs() {
+        fn halve_lpf<C: HasLpf>(config: &mut C) {
+            config.set_lpf_order(2);
+            config.set_lpf_nyquist_factor(config.lpf_nyquist_factor() / 2.0);
+        }
+
+        let mut linear = LinearResamplerConfig::new(Format::F32, 1, 48000, 44100);
+        let factor = HasLpf::lpf_nyquist_factor(&linear);
+        halve_lpf(&mut linear);
+        assert_eq!(HasLpf::lpf_order(&linear), 2);
+        assert_eq!(linear.lpf_nyquist_factor(), factor / 2.0);
+        let resampler = LinearResampler::new(&linear).unwrap();
+        assert_eq!(resampler.config().lpf_order(), 2);
+
+        let mut config =
+            ResamplerConfig::new(Format::F32, 1, 48000, 44100, ResampleAlgorithmType::Linear);
+        halve_lpf(&mut config);
+        assert_eq!(HasLpf::lpf_order(&config), 2);
+        assert_eq!(
+            config.algorithm(),
+            ResampleAlgorithm::Linear {
+                lpf_order: 2,
+                lpf_nyquist_factor: factor / 2.0,
+            }
+        );
+    }
+
+    #[test]
+    fn validate_reports_each_warning() {
+        let linear = |lpf_order, lpf_nyquist_factor| ResampleAlgorithm::Linear {
+            lpf_order,
+            lpf_nyquist_factor,
+        };
+        let sinc = |window_length| ResampleAlgorithm::Sinc {
+            window_length,
+            beta: DEFAULT_SINC_BETA,
+        };
+
+        assert_eq!(config(48000, 44100, linear(4, 1.0)).validate(), vec![]);
+        assert_eq!(config(44100, 48000, linear(0, 1.0)).validate(), vec![]);
+        assert_eq!(
+            config(48000, 44100, sinc(DEFAULT_SINC_WINDOW_LENGTH)).validate(),
+            vec![]
+        );
+        assert_eq!(
+            config(48000, 44100, ResampleAlgorithm::Speex { quality: 3 }).validate(),
+            vec![]
+        );
+
+        assert_eq!(
+            config(48000, 44100, linear(0, 1.0)).validate(),
+            vec![ConfigWarning::NoLpfWhileDownsampling]
+        );
+        assert_eq!(
+            config(48000, 44100, linear(4, 1.5)).validate(),
+            vec![ConfigWarning::LpfCutoffAboveNyquist {
+                lpf_nyquist_factor: 1.5
+            }]
+        );
+        assert_eq!(
+            config(48000, 44100, ResampleAlgorithm::Speex { quality: 0 }).validate(),
+            vec![ConfigWarning::SpeexQualityZero]
+        );
+        assert_eq!(
+            config(48000, 44100, sinc(4)).validate(),
+            vec![ConfigWarning::ShortSincWindow { window_length: 4 }]
+        );
+        assert_eq!(
+            config(48000, 48000, linear(4, 1.0)).validate(),
+            vec![ConfigWarning::SameSampleRate]
+        );
+
+        // Warnings about the rates and the algorithm are reported together.
+        assert_eq!(
+            config(48000, 48000, sinc(4)).validate(),
+            vec![
+                ConfigWarning::SameSampleRate,
+                ConfigWarning::ShortSincWindow { window_length: 4 }
+            ]
+        );
+    }
+
+    #[test]
+    fn latency_budget_is_respected() {
+        for budget in 1..=(MAX_FILTER_ORDER as u32 + 4) {
+            let mut config = LinearResamplerConfig::new(Format::F32, 1, 48000, 44100);
+            let (order, latency) = config.set_latency_budget_frames(budget).unwrap();
+            assert_eq!(order, (budget - 1).min(MAX_FILTER_ORDER as u32));
+            assert_eq!(config.lpf_order(), order);
+            assert!(latency <= budget as u64);
+
+            let mut resampler = LinearResampler::new(&config).unwrap();
+            assert_eq!(resampler.input_latency(), latency);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(
+            expected = "the linear resampler needs a latency budget of at least one frame"
+        )
+    )]
+    fn latency_budget_of_zero_is_rejected() {
+        let mut config = LinearResamplerConfig::new(Format::F32, 1, 48000, 44100);
+        assert!(matches!(
+            config.set_latency_budget_frames(0),
+            Err(Error::InvalidArgs)
+        ));
+    }
+
+    #[test]
+    fn u8_frames_resample_like_s16_frames() {
+        let tone = sine(1000.0, 48000, 960);
+        let input_u8: Vec<u8> = tone
+            .iter()
+            .map(|&s| (128.0 + s * 100.0).round() as u8)
+            .collect();
+        let input_s16: Vec<i16> = input_u8.iter().map(|&s| (s as i16 - 128) << 8).collect();
+
+        let new = |format| {
+            let mut config =
+                ResamplerConfig::new(format, 1, 48000, 44100, ResampleAlgorithmType::Linear);
+            config.set_algorithm(ResampleAlgorithm::Linear {
+                lpf_order: 2,
+                lpf_nyquist_factor: 1.0,
+            });
+            Resampler::new(&config).unwrap()
+        };
+
+        let mut output_u8 = vec![0u8; 1024];
+        let (consumed, produced_u8) = new(Format::U8)
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut output_u8[..], Format::U8, 1),
+                &Frames::wrap(&input_u8[..], Format::U8, 1),
+            )
+            .unwrap();
+        assert_eq!(consumed, 960);
+
+        let mut output_s16 = vec![0i16; 1024];
+        let (_, produced_s16) = new(Format::S16)
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut output_s16[..], Format::S16, 1),
+                &Frames::wrap(&input_s16[..], Format::S16, 1),
+            )
+            .unwrap();
+        assert_eq!(produced_u8, produced_s16);
+        assert!(produced_u8 > 800);
+
+        for (&u8_sample, &s16_sample) in
+            output_u8.iter().zip(&output_s16).take(produced_u8 as usize)
+        {
+            let expected = (s16_sample as i32 >> 8) + 128;
+            assert!(
+                (u8_sample as i32 - expected).abs() <= 1,
+                "{} vs {}",
+                u8_sample,
+                expected
+            );
+        }
+
+        // Silence stays at the bias instead of being filtered like a DC offset.
+        let silence = vec![128u8; 960];
+        let (_, produced) = new(Format::U8)
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut output_u8[..], Format::U8, 1),
+                &Frames::wrap(&silence[..], Format::U8, 1),
+            )
+            .unwrap();
+        assert!(output_u8[..produced as usize].iter().all(|&s| s == 128));
+    }
+
+    #[test]
+    fn output_dither_stays_within_one_lsb_of_the_exact_result() {
+        let tone: Vec<f32> = sine(1000.0, 48000, 960).iter().map(|&s| s * 0.25).collect();
+        let input: Vec<i16> = tone.iter().map(|&s| (s * 32768.0).round() as i16).collect();
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 2,
+            lpf_nyquist_factor: 1.0,
+        };
+
+        let resample = |dither_mode| {
+            let mut config =
+                ResamplerConfig::new(Format::S16, 1, 48000, 44100, ResampleAlgorithmType::Linear);
+            config.set_algorithm(linear);
+            config.set_output_dither(dither_mode);
+            let mut resampler = Resampler::new(&config).unwrap();
+            let mut output = vec![0i16; 1024];
+            let (consumed, produced) = resampler
+                .process_pcm_frames(
+                    &mut FramesMut::wrap(&mut output[..], Format::S16, 1),
+                    &Frames::wrap(&input[..], Format::S16, 1),
+                )
+                .unwrap();
+            assert_eq!(consumed, 960);
+            output.truncate(produced as usize);
+            output
+        };
+
+        // The same input resampled in f32, which is what the dithered output is rounded from.
+        let input_f32: Vec<f32> = input.iter().map(|&s| s as f32 / 32768.0).collect();
+        let mut reference = vec![0.0f32; 1024];
+        let mut resampler = Resampler::new(&config(48000, 44100, linear)).unwrap();
+        let (_, produced) = resampler
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut reference, Format::F32, 1),
+                &Frames::wrap(&input_f32, Format::F32, 1),
+            )
+            .unwrap();
+        reference.truncate(produced as usize);
+
+        let plain = resample(DitherMode::None);
+        assert_eq!(plain.len(), reference.len());
+        for &(mode, bound) in &[(DitherMode::Rectangle, 1.0), (DitherMode::Triangle, 1.5)] {
+            let dithered = resample(mode);
+            assert_eq!(dithered.len(), reference.len());
+            assert_ne!(dithered, plain);
+
+            let errors: Vec<f32> = dithered
+                .iter()
+                .zip(&reference)
+                .map(|(&d, &r)| d as f32 - r * 32768.0)
+                .collect();
+            assert!(errors.iter().all(|e| e.abs() <= bound));
+            // The noise is centered on the sample, so it doesn't add an offset.
+            let mean = errors.iter().sum::<f32>() / errors.len() as f32;
+            assert!(mean.abs() < 0.1, "mean error is {}", mean);
+        }
+    }
+
+    #[test]
+    fn s32_frames_resample_with_f32_precision() {
+        let tone: Vec<f32> = sine(1000.0, 48000, 960).iter().map(|&s| s * 0.5).collect();
+        let input: Vec<i32> = tone
+            .iter()
+            .map(|&s| (s as f64 * 2147483648.0) as i32)
+            .collect();
+        let linear = ResampleAlgorithm::Linear {
+            lpf_order: 2,
+            lpf_nyquist_factor: 1.0,
+        };
+
+        let mut s32_config =
+            ResamplerConfig::new(Format::S32, 1, 48000, 44100, ResampleAlgorithmType::Linear);
+        s32_config.set_algorithm(linear);
+        let mut output = vec![0i32; 1024];
+        let (consumed, produced) = Resampler::new(&s32_config)
+            .unwrap()
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut output[..], Format::S32, 1),
+                &Frames::wrap(&input[..], Format::S32, 1),
+            )
+            .unwrap();
+        assert_eq!(consumed, 960);
+
+        let mut reference = vec![0.0f32; 1024];
+        let (_, reference_produced) = Resampler::new(&config(48000, 44100, linear))
+            .unwrap()
+            .process_pcm_frames(
+                &mut FramesMut::wrap(&mut reference, Format::F32, 1),
+                &Frames::wrap(&tone, Format::F32, 1),
+            )
+            .unwrap();
+        assert_eq!(produced, reference_produced);
+        assert!(produced > 800);
+
+        // f32 has 24 bits of precision, so the s32 output is only off in the lowest 8 bits or so
+        // (a few times 2^-24 of full scale).
+        let max_error = output
+            .iter()
+            .zip(&reference)
+            .take(produced as usize)
+            .map(|(&s, &r)| (s as f64 / 2147483648.0 - r as f64).abs())
+            .fold(0.0, f64::max);
+        assert!(
+            max_error < 4.0 / (1 << 24) as f64,
+            "max error is {}",
+            max_error
+        );
+        assert!(output.iter().take(produced as usize).any(|&s| s > 1 << 29));
     }
 }
@@ -3,7 +3,8 @@
 //! the `PCMRingBuffer` operates on PCM frames. They are otherwise identical as `PCMRingBuffer` is
 //! just a wrapper around `RingBuffer`.
 
-use crate::base::{from_bool32, Error};
+use crate::base::{from_bool32, Error, Format};
+use crate::frames::{swap_sample_bytes, ByteOrder};
 use miniaudio_sys as sys;
 use std::os::raw::c_void;
 use std::ptr::NonNull;
@@ -167,6 +168,19 @@ impl<T: Clone> RingBuffer<T> {
     pub(crate) fn write<F>(&self, count_requested: usize, f: F) -> usize
     where
         F: FnOnce(&mut [T]),
+    {
+        self.write_partial(count_requested, |items| {
+            let count = items.len();
+            f(items);
+            count
+        })
+    }
+
+    /// Like `write` except that the closure returns the number of items that it wrote, and only
+    /// those are committed. The returned count must not be larger than the slice.
+    pub(crate) fn write_partial<F>(&self, count_requested: usize, f: F) -> usize
+    where
+        F: FnOnce(&mut [T]) -> usize,
     {
         let mut bytes = count_requested * std::mem::size_of::<T>();
         let mut buf_ptr: *mut c_void = std::ptr::null_mut();
@@ -187,7 +201,9 @@ impl<T: Clone> RingBuffer<T> {
 
         let items = unsafe { std::slice::from_raw_parts_mut(buf_ptr.cast::<T>(), count) };
 
-        f(items);
+        let written = f(items);
+        assert!(written <= count, "wrote more items than were acquired");
+        let bytes = written * std::mem::size_of::<T>();
 
         let commit_result =
             unsafe { sys::ma_rb_commit_write(&self.inner as *const _ as *mut _, bytes, buf_ptr) };
@@ -195,7 +211,7 @@ impl<T: Clone> RingBuffer<T> {
         // This shouldn't fail because our arguments are valid, but we debug assert just to be sure.
         debug_assert!(commit_result == 0);
 
-        count
+        written
     }
 
     // FIXME find out what to do with this and remove allow(dead_code).
@@ -283,12 +299,38 @@ impl<T: Clone> RingBufferSend<T> {
         self.inner.write(count_requested, f)
     }
 
+    /// Like `write_with` except that the closure returns the number of items that it actually
+    /// wrote, and only those are made available to the receiver. The returned count must not be
+    /// larger than the slice that was passed to the closure.
+    pub fn write_partial_with<F>(&self, count_requested: usize, f: F) -> usize
+    where
+        F: FnOnce(&mut [T]) -> usize,
+    {
+        self.inner.write_partial(count_requested, f)
+    }
+
     /// Returns the number of items that are available for writing.
     pub fn available(&mut self) -> usize {
         self.inner.available_write()
     }
 }
 
+impl RingBufferSend<u8> {
+    /// Writes samples in `byte_order` from `src` and stores them in the native byte order,
+    /// returning the number of bytes that were written. Only whole samples are written, so the
+    /// size of the ring buffer should be a multiple of the size of a sample in `format`.
+    pub fn write_samples(&self, src: &[u8], format: Format, byte_order: ByteOrder) -> usize {
+        let sample_size = format.bytes_per_sample().max(1);
+        self.inner
+            .write_partial(src.len() / sample_size * sample_size, |dest| {
+                let len = dest.len() / sample_size * sample_size;
+                dest[..len].copy_from_slice(&src[..len]);
+                swap_sample_bytes(&mut dest[..len], format, byte_order);
+                len
+            })
+    }
+}
+
 impl<T: Clone> Clone for RingBufferSend<T> {
     fn clone(&self) -> Self {
         RingBufferSend {
@@ -330,6 +372,20 @@ impl<T: Clone> RingBufferRecv<T> {
     }
 }
 
+impl RingBufferRecv<u8> {
+    /// Reads samples into `dest` in `byte_order`, returning the number of bytes that were read.
+    /// Only whole samples are read, so the size of the ring buffer should be a multiple of the
+    /// size of a sample in `format`.
+    pub fn read_samples(&self, dest: &mut [u8], format: Format, byte_order: ByteOrder) -> usize {
+        let sample_size = format.bytes_per_sample().max(1);
+        self.inner
+            .read(dest.len() / sample_size * sample_size, |src| {
+                dest[..src.len()].copy_from_slice(src);
+                swap_sample_bytes(&mut dest[..src.len()], format, byte_order);
+            })
+    }
+}
+
 impl<T: Clone> Clone for RingBufferRecv<T> {
     fn clone(&self) -> Self {
         RingBufferRecv {
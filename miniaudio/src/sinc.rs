@@ -0,0 +1,463 @@
+use crate::base::{Error, Format, MAX_CHANNELS};
+use crate::frames::{Frames, FramesMut};
+use std::sync::Arc;
+
+/// The number of entries per zero crossing in the kernel table. Values in between two entries are
+/// linearly interpolated.
+const KERNEL_OVERSAMPLING: usize = 256;
+
+/// The cutoff of the low-pass filter relative to the Nyquist frequency of the lower of the two
+/// sample rates. This leaves room for the transition band of the window so that it doesn't alias.
+const CUTOFF_FACTOR: f64 = 0.95;
+
+/// The smallest window length that is accepted.
+const MIN_SINC_WINDOW_LENGTH: u32 = 2;
+
+/// The largest window length that is accepted.
+const MAX_SINC_WINDOW_LENGTH: u32 = 256;
+
+/// Returns a value of the zeroth order modified Bessel function of the first kind.
+pub(crate) fn bessel_i0(x: f64) -> f64 {
+    let half_x = x / 2.0;
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1.0;
+
+    loop {
+        term *= (half_x / k) * (half_x / k);
+        sum += term;
+        if term < sum * 1e-12 {
+            return sum;
+        }
+        k += 1.0;
+    }
+}
+
+/// Looks up the kernel at a distance in input frames from the output position.
+#[inline]
+fn kernel_at(kernel: &[f32], distance: f64) -> f32 {
+    let position = distance.abs() * KERNEL_OVERSAMPLING as f64;
+    let index = position as usize;
+    if index + 1 >= kernel.len() {
+        return 0.0;
+    }
+
+    let fraction = (position - index as f64) as f32;
+    kernel[index] + (kernel[index + 1] - kernel[index]) * fraction
+}
+
+/// A windowed-sinc resampler written in Rust using a Kaiser window. This is used by `Resampler`
+/// when `ResampleAlgorithm::Sinc` is selected since miniaudio does not have one. All of its
+/// buffers are allocated when it is created, so processing and changing the rate never allocate
+/// unless the kernel is shared with a `ResamplePlan`.
+#[derive(Clone)]
+pub(crate) struct SincResampler {
+    format: Format,
+    channels: usize,
+    window_length: usize,
+    beta: f64,
+    sample_rate_in: u32,
+    sample_rate_out: u32,
+    /// The position of the next output frame between the two center frames of the history in
+    /// units of `1 / sample_rate_out` input frames. This is kept as an integer so that the
+    /// position doesn't drift over long streams.
+    position: u64,
+    /// One side of the windowed sinc, sampled `KERNEL_OVERSAMPLING` times per input frame. This
+    /// is shared between resamplers that were created from the same plan.
+    kernel: Arc<Vec<f32>>,
+    /// The kernel coefficients for the current output frame.
+    coefficients: Vec<f32>,
+    /// The last `window_length` input frames, interleaved.
+    history: Vec<f32>,
+}
+
+impl SincResampler {
+    /// Creates a resampler. If `kernel` is given it is used instead of computing a new one, so
+    /// it must have been computed for the same rates, window length and beta, e.g. by another
+    /// resampler created with the same arguments.
+    pub(crate) fn new(
+        format: Format,
+        channels: u32,
+        sample_rate_in: u32,
+        sample_rate_out: u32,
+        window_length: u32,
+        beta: f64,
+        kernel: Option<Arc<Vec<f32>>>,
+    ) -> Result<SincResampler, Error> {
+        if format != Format::F32 && format != Format::S16 {
+            ma_debug_panic!(
+                "the sinc resampler only supports f32 and s16 (got {:?})",
+                format
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        if channels == 0
+            || channels as usize > MAX_CHANNELS
+            || sample_rate_in == 0
+            || sample_rate_out == 0
+        {
+            return Err(Error::InvalidArgs);
+        }
+
+        if !(MIN_SINC_WINDOW_LENGTH..=MAX_SINC_WINDOW_LENGTH).contains(&window_length)
+            || window_length & 1 != 0
+        {
+            ma_debug_panic!(
+                "sinc window length must be an even number between {} and {} (got {})",
+                MIN_SINC_WINDOW_LENGTH,
+                MAX_SINC_WINDOW_LENGTH,
+                window_length
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        if !beta.is_finite() || beta < 0.0 {
+            ma_debug_panic!(
+                "sinc window beta must be positive and finite (got {})",
+                beta
+            );
+            return Err(Error::InvalidArgs);
+        }
+
+        let window_length = window_length as usize;
+        let channels = channels as usize;
+        let half_length = window_length / 2;
+
+        let shared_kernel = kernel.is_some();
+        let mut resampler = SincResampler {
+            format,
+            channels,
+            window_length,
+            beta,
+            sample_rate_in,
+            sample_rate_out,
+            // Start by pushing in the first input frame so that the output is delayed by exactly
+            // half of the window.
+            position: sample_rate_out as u64,
+            // One extra entry at the end so that interpolation never reads out of bounds.
+            kernel: kernel
+                .unwrap_or_else(|| Arc::new(vec![0.0; half_length * KERNEL_OVERSAMPLING + 2])),
+            coefficients: vec![0.0; window_length],
+            history: vec![0.0; window_length * channels],
+        };
+
+        if !shared_kernel {
+            resampler.build_kernel();
+        }
+        Ok(resampler)
+    }
+
+    /// The kernel of this resampler, so that it can be shared with others.
+    #[inline]
+    pub(crate) fn kernel(&self) -> &Arc<Vec<f32>> {
+        &self.kernel
+    }
+
+    /// Changes the sample rates and rebuilds the kernel for the new cutoff. This does not
+    /// allocate unless the kernel is shared with other resamplers, in which case it is copied
+    /// first.
+    pub(crate) fn set_rate(
+        &mut self,
+        sample_rate_in: u32,
+        sample_rate_out: u32,
+    ) -> Result<(), Error> {
+        if sample_rate_in == 0 || sample_rate_out == 0 {
+            return Err(Error::InvalidArgs);
+        }
+
+        // Keep the position at the same point in time.
+        self.position = self.position * sample_rate_out as u64 / self.sample_rate_out as u64;
+        self.sample_rate_in = sample_rate_in;
+        self.sample_rate_out = sample_rate_out;
+        self.build_kernel();
+        Ok(())
+    }
+
+    /// Computes the kernel for the current rates.
+    fn build_kernel(&mut self) {
+        // When downsampling the cutoff has to move down to the new Nyquist frequency.
+        let cutoff =
+            CUTOFF_FACTOR * (self.sample_rate_out as f64 / self.sample_rate_in as f64).min(1.0);
+        let half_length = (self.window_length / 2) as f64;
+        let i0_beta = bessel_i0(self.beta);
+
+        for (index, value) in Arc::make_mut(&mut self.kernel).iter_mut().enumerate() {
+            let distance = index as f64 / KERNEL_OVERSAMPLING as f64;
+            if distance >= half_length {
+                *value = 0.0;
+                continue;
+            }
+
+            let x = std::f64::consts::PI * cutoff * distance;
+            let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+            let ratio = distance / half_length;
+            let window = bessel_i0(self.beta * (1.0 - ratio * ratio).sqrt()) / i0_beta;
+            *value = (cutoff * sinc * window) as f32;
+        }
+    }
+
+    /// Clears the history so that the resampler can be used for a new stream.
+    pub(crate) fn reset(&mut self) {
+        self.history.iter_mut().for_each(|s| *s = 0.0);
+        self.position = self.sample_rate_out as u64;
+    }
+
+    /// Shifts the history back by one frame and appends an input frame.
+    fn push_frame(&mut self, input: &Frames, frame: usize) {
+        let channels = self.channels;
+        self.history.copy_within(channels.., 0);
+
+        let last = &mut self.history[(self.window_length - 1) * channels..];
+        let first_sample = frame * channels;
+        match self.format {
+            Format::S16 => {
+                let samples = &input.as_samples::<i16>()[first_sample..(first_sample + channels)];
+                for (dest, &src) in last.iter_mut().zip(samples) {
+                    *dest = src as f32 / 32768.0;
+                }
+            }
+
+            _ => {
+                let samples = &input.as_samples::<f32>()[first_sample..(first_sample + channels)];
+                last.copy_from_slice(samples);
+            }
+        }
+    }
+
+    /// Returns the number of input frames consumed and output frames produced respectively.
+    pub(crate) fn process_pcm_frames(
+        &mut self,
+        output: &mut FramesMut,
+        input: &Frames,
+    ) -> (u64, u64) {
+        let channels = self.channels;
+        let input_frames = input.frame_count();
+        let output_frames = output.frame_count();
+        let center = (self.window_length / 2 - 1) as f64;
+        let one = self.sample_rate_out as u64;
+
+        let mut consumed = 0;
+        let mut produced = 0;
+
+        'outer: loop {
+            while self.position >= one {
+                if consumed == input_frames {
+                    break 'outer;
+                }
+                self.push_frame(input, consumed);
+                consumed += 1;
+                self.position -= one;
+            }
+
+            if produced == output_frames {
+                break;
+            }
+
+            let position = self.position as f64 / one as f64;
+            for (index, coefficient) in self.coefficients.iter_mut().enumerate() {
+                *coefficient = kernel_at(&self.kernel, index as f64 - center - position);
+            }
+
+            let mut frame = [0.0f32; MAX_CHANNELS];
+            for (channel, sample) in frame[..channels].iter_mut().enumerate() {
+                *sample = self
+                    .coefficients
+                    .iter()
+                    .zip(self.history[channel..].iter().step_by(channels))
+                    .fold(0.0, |sum, (&c, &s)| sum + c * s);
+            }
+
+            let first_sample = produced * channels;
+            match self.format {
+                Format::S16 => {
+                    let samples = &mut output.as_samples_mut::<i16>()
+                        [first_sample..(first_sample + channels)];
+                    for (dest, &src) in samples.iter_mut().zip(frame.iter()) {
+                        *dest = (src * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+                    }
+                }
+
+                _ => output.as_samples_mut::<f32>()[first_sample..(first_sample + channels)]
+                    .copy_from_slice(&frame[..channels]),
+            }
+
+            produced += 1;
+            self.position += self.sample_rate_in as u64;
+        }
+
+        (consumed as u64, produced as u64)
+    }
+
+    /// The number of input frames required to produce `output_frame_count` output frames.
+    pub(crate) fn required_input_frame_count(&self, output_frame_count: u64) -> u64 {
+        if output_frame_count == 0 {
+            return 0;
+        }
+
+        // The input needed to advance to the last output frame, which happens before each frame
+        // is produced.
+        let end = self.position + (output_frame_count - 1) * self.sample_rate_in as u64;
+        end / self.sample_rate_out as u64
+    }
+
+    /// The number of output frames that would be produced from `input_frame_count` input frames.
+    pub(crate) fn expected_output_frame_count(&self, input_frame_count: u64) -> u64 {
+        // An output frame is produced for every position before the input runs out.
+        let end = (input_frame_count + 1) * self.sample_rate_out as u64;
+        end.saturating_sub(self.position)
+            .div_ceil(self.sample_rate_in as u64)
+    }
+
+    /// The offset from the number of consumed input frames to the position of the next output
+    /// frame in the input stream.
+    pub(crate) fn position_offset(&self) -> f64 {
+        // The newest frame in the history is the last consumed one and the output is between the
+        // two frames in the middle of the history.
+        self.position as f64 / self.sample_rate_out as f64 - (self.window_length / 2) as f64 - 1.0
+    }
+
+    /// The latency in input frames, which is half of the window.
+    #[inline]
+    pub(crate) fn input_latency(&self) -> u64 {
+        (self.window_length / 2) as u64
+    }
+
+    /// The latency in output frames.
+    #[inline]
+    pub(crate) fn output_latency(&self) -> u64 {
+        (self.input_latency() * self.sample_rate_out as u64 + self.sample_rate_in as u64 / 2)
+            / self.sample_rate_in as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resampler(sample_rate_in: u32, sample_rate_out: u32, window_length: u32) -> SincResampler {
+        SincResampler::new(
+            Format::F32,
+            1,
+            sample_rate_in,
+            sample_rate_out,
+            window_length,
+            8.0,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn process(resampler: &mut SincResampler, input: &[f32], output_frames: usize) -> Vec<f32> {
+        let mut output = vec![0.0f32; output_frames];
+        let (_, produced) = resampler.process_pcm_frames(
+            &mut FramesMut::wrap(&mut output, Format::F32, 1),
+            &Frames::wrap(input, Format::F32, 1),
+        );
+        output.truncate(produced as usize);
+        output
+    }
+
+    #[test]
+    fn bessel_i0_matches_known_values() {
+        assert_eq!(bessel_i0(0.0), 1.0);
+        assert!((bessel_i0(1.0) - 1.266_065_877_752_008_4).abs() < 1e-10);
+        assert!((bessel_i0(5.0) - 27.239_871_823_604_44).abs() < 1e-8);
+    }
+
+    #[test]
+    fn impulse_is_delayed_by_the_input_latency() {
+        let mut resampler = resampler(48000, 48000, 16);
+        let mut impulse = vec![0.0f32; 40];
+        impulse[0] = 1.0;
+
+        let output = process(&mut resampler, &impulse, 40);
+        let peak = (0..output.len())
+            .max_by(|&a, &b| output[a].partial_cmp(&output[b]).unwrap())
+            .unwrap();
+        assert_eq!(peak as u64, resampler.input_latency());
+        assert!((output[peak] - CUTOFF_FACTOR as f32).abs() < 1e-6);
+    }
+
+    #[test]
+    fn constant_input_keeps_its_level() {
+        for &(sample_rate_in, sample_rate_out) in &[(48000, 48000), (48000, 44100), (8000, 48000)] {
+            let mut resampler = resampler(sample_rate_in, sample_rate_out, 32);
+            let output = process(&mut resampler, &[0.5; 400], 4000);
+            let middle = output[output.len() / 2];
+            assert!(
+                (middle - 0.5).abs() < 0.005,
+                "{} -> {}: {}",
+                sample_rate_in,
+                sample_rate_out,
+                middle
+            );
+        }
+    }
+
+    #[test]
+    fn frame_counts_match_what_is_processed() {
+        for &(sample_rate_in, sample_rate_out) in &[(44100, 48000), (48000, 44100), (48000, 8000)] {
+            let mut resampler = resampler(sample_rate_in, sample_rate_out, 16);
+            process(&mut resampler, &[0.0; 333], 10000);
+
+            for &frames in &[1, 2, 50, 1000] {
+                let required = resampler.required_input_frame_count(frames) as usize;
+                let (consumed, produced) = resampler.clone().process_pcm_frames(
+                    &mut FramesMut::wrap(&mut vec![0.0f32; frames as usize], Format::F32, 1),
+                    &Frames::wrap(&vec![0.0f32; required], Format::F32, 1),
+                );
+                assert_eq!((consumed, produced), (required as u64, frames));
+
+                let expected = resampler.expected_output_frame_count(frames);
+                let (consumed, produced) = resampler.clone().process_pcm_frames(
+                    &mut FramesMut::wrap(&mut vec![0.0f32; 100_000], Format::F32, 1),
+                    &Frames::wrap(&vec![0.0f32; frames as usize], Format::F32, 1),
+                );
+                assert_eq!((consumed, produced), (frames, expected));
+            }
+        }
+    }
+
+    #[test]
+    fn s16_is_converted_around_the_filter() {
+        let mut resampler =
+            SincResampler::new(Format::S16, 2, 44100, 48000, 32, 8.0, None).unwrap();
+        let input = [16384i16; 800];
+        let mut output = [0i16; 800];
+        let (_, produced) = resampler.process_pcm_frames(
+            &mut FramesMut::wrap(&mut output, Format::S16, 2),
+            &Frames::wrap(&input, Format::S16, 2),
+        );
+        assert!(produced > 300);
+        let middle = (produced as usize / 2) * 2;
+        assert!((output[middle] - 16384).abs() <= 100);
+        assert!((output[middle + 1] - 16384).abs() <= 100);
+    }
+
+    #[test]
+    fn set_rate_rejects_zero() {
+        let mut resampler = resampler(48000, 44100, 16);
+        assert!(matches!(
+            resampler.set_rate(0, 44100),
+            Err(Error::InvalidArgs)
+        ));
+        assert!(matches!(
+            resampler.set_rate(48000, 0),
+            Err(Error::InvalidArgs)
+        ));
+        assert!(resampler.set_rate(44100, 48000).is_ok());
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "sinc window length must be an even number")
+    )]
+    fn odd_window_lengths_are_rejected() {
+        assert!(matches!(
+            SincResampler::new(Format::F32, 1, 48000, 44100, 15, 8.0, None),
+            Err(Error::InvalidArgs)
+        ));
+    }
+}
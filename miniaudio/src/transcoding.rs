@@ -0,0 +1,334 @@
+use crate::base::Error;
+use crate::data_conv::{DataConverter, DataConverterConfig};
+use crate::decoder::Decoder;
+use crate::encoder::Encoder;
+use crate::frames::{bytes_per_frame, Frames, FramesBuffer, FramesMut};
+use crate::resampling::{ProgressReporter, ResampleAlgorithm};
+
+type BoxedProgressCallback = Box<dyn FnMut(f32) + Send>;
+
+/// The number of frames that are decoded and converted at a time by `StreamingTranscoder`.
+const TRANSCODER_CHUNK_FRAMES: usize = 4096;
+
+/// Decodes a stream, converts it to the format, channel count and sample rate of an encoder and
+/// encodes it, a bit at a time.
+///
+/// Call `step` repeatedly until it returns 0, e.g. from an idle callback of a UI so that it can
+/// show progress in between. Only one chunk of decoded and one chunk of converted frames are kept
+/// in memory, so files of any length can be transcoded. The end of the stream is flushed out of
+/// the resampler with silence, so the output has the duration of the input converted to the new
+/// sample rate, rounded up by a few frames. The encoder finishes the file when the transcoder is
+/// dropped.
+pub struct StreamingTranscoder {
+    decoder: Decoder,
+    converter: DataConverter,
+    encoder: Encoder,
+    /// Frames from the decoder, or silence to flush the converter at the end.
+    decoded: FramesBuffer,
+    decoded_start: usize,
+    decoded_end: usize,
+    converted: FramesBuffer,
+    decoder_at_end: bool,
+    flushed: bool,
+    frames_decoded: u64,
+    frames_written: u64,
+    /// The length of the decoded stream in frames, or 0 if it's not known.
+    length: u64,
+    progress: Option<ProgressReporter<BoxedProgressCallback>>,
+}
+
+impl StreamingTranscoder {
+    /// Creates a transcoder from `decoder` to `encoder`, resampling with `resampling` when their
+    /// sample rates are different. The sinc resampler can't be used by a data converter, so
+    /// `Error::InvalidArgs` is returned for it.
+    pub fn new(
+        mut decoder: Decoder,
+        encoder: Encoder,
+        resampling: ResampleAlgorithm,
+    ) -> Result<StreamingTranscoder, Error> {
+        let mut config = DataConverterConfig::new(
+            decoder.output_format(),
+            encoder.format(),
+            decoder.output_channels(),
+            encoder.channels(),
+            decoder.output_sample_rate(),
+            encoder.sample_rate(),
+        );
+        config.try_set_resampling(resampling)?;
+        let converter = DataConverter::new(&config)?;
+        let length = decoder.length_in_pcm_frames();
+
+        let decoded = FramesBuffer::new(
+            decoder.output_format(),
+            decoder.output_channels(),
+            TRANSCODER_CHUNK_FRAMES,
+        );
+        let converted = FramesBuffer::new(
+            encoder.format(),
+            encoder.channels(),
+            TRANSCODER_CHUNK_FRAMES,
+        );
+
+        Ok(StreamingTranscoder {
+            decoder,
+            converter,
+            encoder,
+            decoded,
+            decoded_start: 0,
+            decoded_end: 0,
+            converted,
+            decoder_at_end: false,
+            flushed: false,
+            frames_decoded: 0,
+            frames_written: 0,
+            length,
+            progress: None,
+        })
+    }
+
+    /// Transcodes up to `max_frames` output frames and returns the number of frames that were
+    /// written to the encoder. Returns 0 once the whole stream has been transcoded.
+    ///
+    /// Returns `Error::InvalidOperation` if the data converter stops consuming input before the
+    /// stream is finished, since returning 0 would make it look like the transcoder is done.
+    pub fn step(&mut self, max_frames: usize) -> Result<usize, Error> {
+        let format = self.encoder.format();
+        let channels = self.encoder.channels();
+        let frame_size = bytes_per_frame(format, channels);
+        let decoded_frame_size = bytes_per_frame(self.decoded.format(), self.decoded.channels());
+
+        let mut written = 0;
+        while written < max_frames {
+            if self.decoded_start == self.decoded_end && !self.refill() {
+                break;
+            }
+
+            let output_frames = (max_frames - written).min(self.converted.frame_count());
+            let input = Frames::wrap::<u8>(
+                &self.decoded.as_bytes()[(self.decoded_start * decoded_frame_size)
+                    ..(self.decoded_end * decoded_frame_size)],
+                self.decoded.format(),
+                self.decoded.channels(),
+            );
+            let mut output = FramesMut::wrap::<u8>(
+                &mut self.converted.as_bytes_mut()[..(output_frames * frame_size)],
+                format,
+                channels,
+            );
+
+            let (produced, consumed) = self.converter.process_pcm_frames(&mut output, &input)?;
+            self.decoded_start += consumed as usize;
+
+            if produced > 0 {
+                let converted = Frames::wrap::<u8>(
+                    &self.converted.as_bytes()[..(produced as usize * frame_size)],
+                    format,
+                    channels,
+                );
+                if self.encoder.write_pcm_frames(&converted)? != produced {
+                    return Err(Error::IoError);
+                }
+                written += produced as usize;
+            } else if consumed == 0 {
+                self.frames_written += written as u64;
+                return Err(Error::InvalidOperation);
+            }
+        }
+
+        self.frames_written += written as u64;
+        let finished = self.is_finished();
+        if let Some(progress) = self.progress.as_mut() {
+            if finished {
+                progress.finish();
+            } else if self.length > 0 {
+                progress.report(self.frames_decoded as f32 / self.length as f32);
+            }
+        }
+        Ok(written)
+    }
+
+    /// Sets a callback that is called from `step` with the fraction of the input that has been
+    /// decoded so far, e.g. for a progress bar.
+    ///
+    /// Progress is only reported when it has grown by at least `PROGRESS_REPORT_STEP`, so the
+    /// callback is called about a hundred times at most however small the steps are. The values
+    /// never decrease, and 1.0 is reported once the stream has been transcoded completely. If the
+    /// decoder doesn't know the length of the stream only the completion is reported.
+    pub fn set_on_progress<F>(&mut self, on_progress: F)
+    where
+        F: FnMut(f32) + Send + 'static,
+    {
+        self.progress = Some(ProgressReporter::new(Box::new(on_progress)));
+    }
+
+    /// Reads the next chunk from the decoder, or silence once the decoder is at the end. Returns
+    /// false once there is nothing left to convert.
+    fn refill(&mut self) -> bool {
+        self.decoded_start = 0;
+        self.decoded_end = 0;
+
+        if !self.decoder_at_end {
+            let read = self.decoder.read_pcm_frames(&mut self.decoded.frames_mut()) as usize;
+            self.frames_decoded += read as u64;
+            if read > 0 {
+                self.decoded_end = read;
+                return true;
+            }
+            self.decoder_at_end = true;
+        }
+
+        if !self.flushed {
+            self.flushed = true;
+            let latency = (self.converter.input_latency() as usize).min(TRANSCODER_CHUNK_FRAMES);
+            self.decoded = FramesBuffer::new(
+                self.decoded.format(),
+                self.decoded.channels(),
+                TRANSCODER_CHUNK_FRAMES,
+            );
+            self.decoded_end = latency;
+            return latency > 0;
+        }
+
+        false
+    }
+
+    /// The number of frames that were read from the decoder so far.
+    #[inline]
+    pub fn frames_decoded(&self) -> u64 {
+        self.frames_decoded
+    }
+
+    /// The number of frames that were written to the encoder so far.
+    #[inline]
+    pub fn frames_written(&self) -> u64 {
+        self.frames_written
+    }
+
+    /// Returns true once the whole stream has been transcoded.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.flushed && self.decoded_start == self.decoded_end
+    }
+
+    #[inline]
+    pub fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    #[inline]
+    pub fn encoder(&self) -> &Encoder {
+        &self.encoder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::Format;
+    use crate::encoder::{EncoderConfig, ResourceFormat};
+    use std::io::{self, Cursor, Seek, SeekFrom, Write};
+    use std::sync::{Arc, Mutex};
+
+    /// An in-memory file that can still be read after the encoder that writes it is dropped.
+    #[derive(Clone, Default)]
+    struct SharedFile(Arc<Mutex<Cursor<Vec<u8>>>>);
+
+    impl SharedFile {
+        fn bytes(&self) -> Vec<u8> {
+            self.0.lock().unwrap().get_ref().clone()
+        }
+    }
+
+    impl Write for SharedFile {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for SharedFile {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.0.lock().unwrap().seek(pos)
+        }
+    }
+
+    /// A stereo 16-bit WAV file with a few seconds of tones.
+    fn wav_fixture(sample_rate: u32, seconds: u32) -> Vec<u8> {
+        let frame_count = (sample_rate * seconds) as usize;
+        let mut samples = Vec::with_capacity(frame_count * 2);
+        for i in 0..frame_count {
+            let t = i as f32 / sample_rate as f32;
+            samples.push(((std::f32::consts::TAU * 440.0 * t).sin() * 16000.0) as i16);
+            samples.push(((std::f32::consts::TAU * 660.0 * t).sin() * 16000.0) as i16);
+        }
+
+        let file = SharedFile::default();
+        let config = EncoderConfig::new(ResourceFormat::Wav, Format::S16, 2, sample_rate);
+        let mut encoder = Encoder::from_write(file.clone(), &config).unwrap();
+        assert_eq!(
+            encoder
+                .write_pcm_frames(&Frames::wrap(&samples, Format::S16, 2))
+                .unwrap(),
+            frame_count as u64
+        );
+        drop(encoder);
+        file.bytes()
+    }
+
+    #[test]
+    fn multi_second_files_are_transcoded_in_steps() {
+        let decoder = Decoder::from_memory(wav_fixture(44100, 3), None).unwrap();
+        let output = SharedFile::default();
+        let config = EncoderConfig::new(ResourceFormat::Wav, Format::S16, 2, 48000);
+        let encoder = Encoder::from_write(output.clone(), &config).unwrap();
+        let mut transcoder = StreamingTranscoder::new(
+            decoder,
+            encoder,
+            ResampleAlgorithm::Linear {
+                lpf_order: 4,
+                lpf_nyquist_factor: 1.0,
+            },
+        )
+        .unwrap();
+
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let reported = Arc::clone(&progress);
+        transcoder.set_on_progress(move |fraction| reported.lock().unwrap().push(fraction));
+
+        let mut steps = 0;
+        loop {
+            let written = transcoder.step(10000).unwrap();
+            assert!(written <= 10000);
+            if written == 0 {
+                break;
+            }
+            steps += 1;
+        }
+        assert!(transcoder.is_finished());
+        assert!(steps > 10);
+        assert_eq!(transcoder.frames_decoded(), 3 * 44100);
+
+        // The output has the duration of the input at the new rate, rounded up by a few frames.
+        let frames_written = transcoder.frames_written();
+        assert!(frames_written >= 3 * 48000);
+        assert!(frames_written < 3 * 48000 + 16);
+
+        let progress = progress.lock().unwrap();
+        assert!(progress.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(progress.last(), Some(&1.0));
+        drop(progress);
+
+        // The encoder finishes the file when the transcoder is dropped.
+        drop(transcoder);
+        let metadata = Decoder::from_memory(output.bytes(), None)
+            .unwrap()
+            .metadata();
+        assert_eq!(metadata.format, Format::S16);
+        assert_eq!(metadata.channels, 2);
+        assert_eq!(metadata.sample_rate, 48000);
+        assert_eq!(metadata.length_in_pcm_frames, frames_written);
+    }
+}
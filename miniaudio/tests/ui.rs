@@ -0,0 +1,12 @@
+//! Checks that `Resampler::process_frames` rejects sample types and channel counts that the
+//! resampler can't handle at compile time.
+//!
+//! The expected compiler output in `tests/ui` changes with the compiler version and with the line
+//! numbers in `src/resampling.rs`. Refresh it with `TRYBUILD=overwrite cargo test --test ui`.
+#[test]
+fn ui() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/process-frames-valid.rs");
+    cases.compile_fail("tests/ui/process-frames-no-channels.rs");
+    cases.compile_fail("tests/ui/process-frames-unsupported-sample.rs");
+}
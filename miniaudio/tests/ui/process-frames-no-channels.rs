@@ -0,0 +1,9 @@
+use miniaudio::{Format, ResampleAlgorithmType, Resampler, ResamplerConfig};
+
+fn main() {
+    let config = ResamplerConfig::new(Format::F32, 1, 44100, 48000, ResampleAlgorithmType::Linear);
+    let mut resampler = Resampler::new(&config).unwrap();
+    let input: [[f32; 0]; 64] = [[]; 64];
+    let mut output: [[f32; 0]; 80] = [[]; 80];
+    resampler.process_frames(&mut output, &input).unwrap();
+}
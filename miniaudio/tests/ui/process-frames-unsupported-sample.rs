@@ -0,0 +1,9 @@
+use miniaudio::{Format, ResampleAlgorithmType, Resampler, ResamplerConfig};
+
+fn main() {
+    let config = ResamplerConfig::new(Format::F32, 2, 44100, 48000, ResampleAlgorithmType::Linear);
+    let mut resampler = Resampler::new(&config).unwrap();
+    let input = [[0.0f64; 2]; 64];
+    let mut output = [[0.0f64; 2]; 80];
+    resampler.process_frames(&mut output, &input).unwrap();
+}